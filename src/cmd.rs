@@ -14,7 +14,7 @@ use serenity::{
   async_trait,
   builder::CreateCommand,
   futures::future,
-  model::{channel::Message, gateway::Ready},
+  model::{channel::Message, gateway::Ready, voice::VoiceState},
   prelude::*,
 };
 use std::sync::Arc;
@@ -22,13 +22,14 @@ use tracing::error;
 
 mod arg_util;
 pub mod check_in;
+mod component_util;
 mod dice_roll;
 pub mod poll;
 mod ready;
 mod reddit_prev;
-mod server;
+pub mod server;
 mod shrug;
-mod voice;
+pub mod voice;
 
 #[async_trait]
 trait MessageListener: Send + Sync {
@@ -42,6 +43,9 @@ trait AppInteractor: Send + Sync {
   async fn msg_interact(&self, _: &Context, _: &ComponentInteraction) {
     // Default is no-op
   }
+  async fn autocomplete(&self, _: &Context, _: &CommandInteraction) {
+    // Default is no-op
+  }
 }
 
 #[async_trait]
@@ -63,6 +67,7 @@ pub struct Handler {
   listeners: Vec<Box<dyn MessageListener>>,
   app_interactors: Vec<Box<dyn AppInteractor>>,
   ready: ready::ReadyHandler,
+  listener_watch: voice::ListenerWatch,
 }
 
 impl Handler {
@@ -90,6 +95,27 @@ impl Handler {
       },
       shutdown,
     );
+    let voice = voice::Voice::new(
+      config.clone(),
+      emoji.clone(),
+      http.clone(),
+      persistence.clone(),
+      shutdown,
+    );
+    let servers = server::GameServers::new(
+      &config,
+      emoji.clone(),
+      http.clone(),
+      docker,
+      persistence.clone(),
+    );
+    let ready = ready::ReadyHandler::new(
+      poll_handle.clone(),
+      chk_handle.clone(),
+      voice.resumer(),
+      servers.watchers(),
+    );
+    let listener_watch = voice.listener_watch();
     Handler {
       listeners: vec![
         Box::new(shrug::ShrugHandler::new(config.clone(), emoji.clone())),
@@ -99,10 +125,16 @@ impl Handler {
         Box::new(poll::Poll::new(poll_handle.clone())),
         Box::new(check_in::CheckIn::new(emoji.clone(), chk_handle.clone())),
         Box::new(dice_roll::DiceRoll::new(emoji.clone())),
-        Box::new(voice::Voice::new(config, emoji.clone(), shutdown)),
-        Box::new(server::GameServers::new(emoji, http, docker)),
+        Box::new(voice::Soundboard::new(
+          config.clone(),
+          voice.connector(),
+          persistence,
+        )),
+        Box::new(voice),
+        Box::new(servers),
       ],
-      ready: ready::ReadyHandler::new(poll_handle, chk_handle),
+      ready,
+      listener_watch,
     }
   }
 }
@@ -136,6 +168,12 @@ impl EventHandler for Handler {
     self.ready.ready(&ctx, &rdy).await;
   }
 
+  async fn voice_state_update(&self, ctx: Context, _: Option<VoiceState>, new: VoiceState) {
+    if let Some(guild_id) = new.guild_id {
+      self.listener_watch.voice_state_update(&ctx, guild_id).await;
+    }
+  }
+
   async fn interaction_create(&self, ctx: Context, itx: Interaction) {
     match itx {
       Interaction::Component(d) => {
@@ -156,6 +194,15 @@ impl EventHandler for Handler {
         )
         .await;
       }
+      Interaction::Autocomplete(d) => {
+        future::join_all(
+          self
+            .app_interactors
+            .iter()
+            .map(|f| f.autocomplete(&ctx, &d)),
+        )
+        .await;
+      }
       _ => (),
     }
   }
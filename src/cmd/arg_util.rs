@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use anyhow::anyhow;
-use serenity::all::{ResolvedOption, ResolvedValue, Role};
+use serenity::all::{Attachment, PartialChannel, ResolvedOption, ResolvedValue, Role};
 
 #[derive(Debug)]
 pub struct Args<'a>(HashMap<&'a str, &'a ResolvedValue<'a>>);
@@ -37,6 +37,69 @@ impl<'a> Args<'a> {
       })
   }
 
+  pub fn attachment(&self, key: &str) -> Result<&Attachment, anyhow::Error> {
+    self
+      .0
+      .get(key)
+      .ok_or_else(|| anyhow!("Could not get arg: {}", key))
+      .and_then(|d| match d {
+        ResolvedValue::Attachment(v) => Ok(*v),
+        _ => Err(anyhow!("{} is not an Attachment", key)),
+      })
+  }
+
+  /// The subcommand picked within a subcommand group, along with its args
+  pub fn subcommand(&self) -> Result<(&'a str, Args<'a>), anyhow::Error> {
+    self
+      .0
+      .iter()
+      .find_map(|(k, v)| match v {
+        ResolvedValue::SubCommand(c) => Some((*k, Args::from(c))),
+        _ => None,
+      })
+      .ok_or_else(|| anyhow!("No subcommand given"))
+  }
+
+  pub fn opt_str(&self, key: &str) -> Result<Option<&str>, anyhow::Error> {
+    if let Some(d) = self.0.get(key) {
+      return match d {
+        ResolvedValue::String(v) => Ok(Some(*v)),
+        _ => Err(anyhow!("{} is not a String", key)),
+      };
+    }
+    Ok(None)
+  }
+
+  pub fn opt_bool(&self, key: &str) -> Result<Option<bool>, anyhow::Error> {
+    if let Some(d) = self.0.get(key) {
+      return match d {
+        ResolvedValue::Boolean(v) => Ok(Some(*v)),
+        _ => Err(anyhow!("{} is not an Boolean", key)),
+      };
+    }
+    Ok(None)
+  }
+
+  pub fn opt_i64(&self, key: &str) -> Result<Option<i64>, anyhow::Error> {
+    if let Some(d) = self.0.get(key) {
+      return match d {
+        ResolvedValue::Integer(v) => Ok(Some(*v)),
+        _ => Err(anyhow!("{} is not an Integer", key)),
+      };
+    }
+    Ok(None)
+  }
+
+  pub fn opt_channel(&self, key: &str) -> Result<Option<&PartialChannel>, anyhow::Error> {
+    if let Some(d) = self.0.get(key) {
+      return match d {
+        ResolvedValue::Channel(v) => Ok(Some(*v)),
+        _ => Err(anyhow!("{} is not an Channel", key)),
+      };
+    }
+    Ok(None)
+  }
+
   pub fn opt_role(&self, key: &str) -> Result<Option<&Role>, anyhow::Error> {
     if let Some(d) = self.0.get(key) {
       return match d {
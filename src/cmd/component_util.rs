@@ -0,0 +1,31 @@
+use serenity::{
+  all::ComponentInteraction,
+  builder::{CreateInteractionResponse, CreateInteractionResponseMessage},
+  client::Context,
+};
+
+/// Answers a button or menu press, either updating its message in place and
+/// clearing the components, or replying privately to whoever pressed it.
+/// Discord only waits a few seconds for this, so answer before any slow work
+/// and edit the response afterwards.
+pub async fn respond(
+  ctx: &Context,
+  itx: &ComponentInteraction,
+  content: String,
+  update: bool,
+) -> Result<(), anyhow::Error> {
+  let resp = match update {
+    true => CreateInteractionResponse::UpdateMessage(
+      CreateInteractionResponseMessage::new()
+        .content(content)
+        .components(vec![]),
+    ),
+    false => CreateInteractionResponse::Message(
+      CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true),
+    ),
+  };
+  itx.create_response(&ctx.http, resp).await?;
+  Ok(())
+}
@@ -87,6 +87,10 @@ impl AppInteractor for Poll {
 
   #[instrument(name = NAME, level = "INFO", skip(self, ctx, itx))]
   async fn msg_interact(&self, ctx: &Context, itx: &ComponentInteraction) {
+    // Poll menus are keyed by their poll id, anything else belongs to someone else
+    if Uuid::parse_str(&itx.data.custom_id).is_err() {
+      return;
+    }
     let mut err = false;
     if let Err(e) = self._handle_msg(ctx, itx).await {
       error!("Failed to update poll {:?}", e);
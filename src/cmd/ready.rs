@@ -1,14 +1,17 @@
-use super::{check_in::CheckInMessage, poll::PollMessage};
+use super::{check_in::CheckInMessage, poll::PollMessage, server::Watchers, voice::Resumer};
 use crate::cmd::CallContext;
 use derive_new::new;
 use kitchen_sink::actor::ActorHandle;
 use serenity::{model::gateway::Ready, prelude::Context};
+use std::sync::Arc;
 use tracing::{info, instrument};
 
 #[derive(new)]
 pub struct ReadyHandler {
   poll_handle: ActorHandle<PollMessage>,
   checkin_handle: ActorHandle<CheckInMessage>,
+  resumer: Arc<Resumer>,
+  server_watchers: Watchers,
 }
 
 impl ReadyHandler {
@@ -34,5 +37,10 @@ impl ReadyHandler {
       .checkin_handle
       .send(CheckInMessage::RestoreConfig(cctx))
       .await;
+
+    // Offer to pick back up any voice queues from before the restart
+    self.resumer.offer_on_ready(&ctx.http).await;
+
+    self.server_watchers.start(ctx.http.clone());
   }
 }
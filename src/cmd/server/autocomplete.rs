@@ -0,0 +1,99 @@
+use super::list::{extract_name, label};
+use crate::docker::{GAME_LABEL, VERSION_LABEL};
+use bollard::service::{
+  ContainerSummary,
+  ContainerSummaryStateEnum::{CREATED, EXITED, RUNNING},
+};
+use itertools::Itertools;
+
+/// Discord won't show more suggestions than this
+const MAX_CHOICES: usize = 25;
+
+/// Servers worth suggesting for the subcommand: stopped ones to start, running
+/// ones to stop, restart or check on, any for logs and backups. Narrowed down
+/// by whatever's been typed so far. Each comes as what to show alongside the
+/// container name to fill in.
+pub fn suggestions(
+  summaries: &[ContainerSummary],
+  subcommand: &str,
+  typed: &str,
+) -> Vec<(String, String)> {
+  let typed = typed.to_lowercase();
+  summaries
+    .iter()
+    .filter(|s| {
+      matches!(
+        (subcommand, s.state),
+        ("start", Some(CREATED | EXITED))
+          | ("stop" | "restart" | "status", Some(RUNNING))
+          | ("logs" | "backup" | "backups", _)
+      )
+    })
+    .map(|s| (extract_name(s), describe(s)))
+    .filter(|(_, shown)| shown.to_lowercase().contains(&typed))
+    .sorted()
+    .take(MAX_CHOICES)
+    .map(|(name, shown)| (shown, name.to_string()))
+    .collect()
+}
+
+fn describe(summary: &ContainerSummary) -> String {
+  format!(
+    "{} ({} {})",
+    extract_name(summary),
+    label(summary, GAME_LABEL),
+    label(summary, VERSION_LABEL)
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bollard::service::ContainerSummaryStateEnum;
+  use test_case::test_case;
+
+  fn summary(name: &str, game: &str, state: ContainerSummaryStateEnum) -> ContainerSummary {
+    ContainerSummary {
+      names: Some(vec![format!("/{name}")]),
+      labels: Some(
+        [("game", game), ("version", "vanilla")]
+          .iter()
+          .map(|(k, v)| (k.to_string(), v.to_string()))
+          .collect(),
+      ),
+      state: Some(state),
+      ..Default::default()
+    }
+  }
+
+  fn servers() -> Vec<ContainerSummary> {
+    vec![
+      summary("valheim", "valheim", ContainerSummaryStateEnum::RUNNING),
+      summary("vanilla", "minecraft", ContainerSummaryStateEnum::EXITED),
+      summary("modded", "minecraft", ContainerSummaryStateEnum::CREATED),
+    ]
+  }
+
+  #[test_case("start", "" => vec!["modded", "vanilla"]; "start offers stopped")]
+  #[test_case("stop", "" => vec!["valheim"]; "stop offers running")]
+  #[test_case("status", "" => vec!["valheim"]; "status offers running")]
+  #[test_case("logs", "" => vec!["modded", "valheim", "vanilla"]; "logs offers all")]
+  #[test_case("restart", "" => vec!["valheim"]; "restart offers running")]
+  #[test_case("backups", "" => vec!["modded", "valheim", "vanilla"]; "backups offers all")]
+  #[test_case("start", "MOD" => vec!["modded"]; "typing narrows")]
+  #[test_case("start", "minecraft" => vec!["modded", "vanilla"]; "matches on game")]
+  #[test_case("stop", "minecraft" => Vec::<String>::new(); "nothing fits")]
+  fn suggests_by_state(subcommand: &str, typed: &str) -> Vec<String> {
+    suggestions(&servers(), subcommand, typed)
+      .into_iter()
+      .map(|(_, name)| name)
+      .collect()
+  }
+
+  #[test]
+  fn labels_shown_in_suggestion() {
+    let (shown, name) = suggestions(&servers(), "stop", "").remove(0);
+    assert_eq!("valheim (valheim vanilla)", shown);
+    assert_eq!("valheim", name);
+  }
+}
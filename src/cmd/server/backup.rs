@@ -0,0 +1,359 @@
+use crate::{
+  cmd::{arg_util::Args, SubCommandHandler},
+  docker::{DockerClient, BACKUP_LABEL},
+};
+use anyhow::anyhow;
+use bollard::service::{ContainerInspectResponse, MountPointTypeEnum};
+use chrono::Utc;
+use derive_new::new;
+use futures::TryStreamExt;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  utils::MessageBuilder,
+};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Instant,
+};
+use tokio::{fs::File, io::AsyncWriteExt};
+use tracing::{info, warn};
+
+/// Sorts the same alphabetically as it does by time, which rotation relies on
+const SNAPSHOT_FORMAT: &str = "%Y-%m-%dT%H-%M-%SZ";
+
+/// One backup of a server, a directory holding a tar per backed up path
+#[derive(Debug, PartialEq)]
+pub struct Snapshot {
+  pub taken: String,
+  pub bytes: u64,
+}
+
+/// Snapshots of each server's data, kept under `dir/<server>/<taken>/`
+#[derive(new)]
+pub struct Backups {
+  docker: Arc<Box<dyn DockerClient>>,
+  dir: PathBuf,
+  /// How many snapshots of each server to hold on to
+  keep: usize,
+}
+
+impl Backups {
+  /// Where the server's snapshots live, however its name was typed
+  fn server_dir(&self, name: &str) -> Result<PathBuf, anyhow::Error> {
+    let name = self.docker.canonical(name)?;
+    // Names carry their host, which shouldn't make for nested directories
+    Ok(self.dir.join(name.replace('/', "-")))
+  }
+
+  /// Tars up the server's data while it runs, then drops the oldest snapshots
+  pub async fn snapshot(&self, name: &str) -> Result<Snapshot, anyhow::Error> {
+    let server_dir = self.server_dir(name)?;
+    let container = self.docker.inspect(name).await?;
+    let paths = backup_paths(&container);
+    if paths.is_empty() {
+      return Err(anyhow!(
+        "{name} has nothing mounted to back up, try a {BACKUP_LABEL} label"
+      ));
+    }
+
+    let taken = Utc::now().format(SNAPSHOT_FORMAT).to_string();
+    let snapshot_dir = server_dir.join(&taken);
+    tokio::fs::create_dir_all(&snapshot_dir).await?;
+    let started = Instant::now();
+    for path in &paths {
+      if let Err(e) = self.archive(name, path, &snapshot_dir).await {
+        // Half a snapshot is worse than none, it'd pass for a good one later
+        let _ = tokio::fs::remove_dir_all(&snapshot_dir).await;
+        return Err(anyhow!("Failed backing up {path}: {e}"));
+      }
+    }
+    info!(
+      "Backed up {} of {} in {:?}",
+      paths.join(", "),
+      name,
+      started.elapsed()
+    );
+
+    // Walking and deleting directories blocks, keep it off the runtime's threads
+    let keep = self.keep;
+    let (removed, bytes) = tokio::task::spawn_blocking(move || {
+      rotate(&server_dir, keep).map(|removed| (removed, dir_size(&snapshot_dir)))
+    })
+    .await??;
+    if removed > 0 {
+      info!("Rotated out {} old backups of {}", removed, name);
+    }
+    Ok(Snapshot { bytes, taken })
+  }
+
+  async fn archive(&self, name: &str, path: &str, into: &Path) -> Result<(), anyhow::Error> {
+    let file_name = format!("{}.tar", path.trim_matches('/').replace('/', "-"));
+    let mut file = File::create(into.join(file_name)).await?;
+    let mut tar = self.docker.archive(name, path).await?;
+    while let Some(chunk) = tar.try_next().await? {
+      file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+  }
+
+  pub async fn list(&self, name: &str) -> Result<Vec<Snapshot>, anyhow::Error> {
+    let server_dir = self.server_dir(name)?;
+    tokio::task::spawn_blocking(move || list(&server_dir)).await?
+  }
+}
+
+/// Where the server keeps what's worth saving: the paths in its backup label,
+/// or failing that whatever it has mounted writable
+pub fn backup_paths(container: &ContainerInspectResponse) -> Vec<String> {
+  let label = container
+    .config
+    .as_ref()
+    .and_then(|c| c.labels.as_ref())
+    .and_then(|l| l.get(BACKUP_LABEL));
+  if let Some(paths) = label {
+    return paths
+      .split(',')
+      .map(str::trim)
+      .filter(|p| !p.is_empty())
+      .map(str::to_string)
+      .collect();
+  }
+  let mut paths: Vec<String> = container
+    .mounts
+    .iter()
+    .flatten()
+    .filter(|m| {
+      matches!(
+        m.typ,
+        Some(MountPointTypeEnum::BIND | MountPointTypeEnum::VOLUME)
+      )
+    })
+    .filter(|m| m.rw.unwrap_or(false))
+    .filter_map(|m| m.destination.clone())
+    .collect();
+  paths.sort();
+  paths
+}
+
+/// Snapshots of a server, oldest first
+fn list(server_dir: &Path) -> Result<Vec<Snapshot>, anyhow::Error> {
+  if !server_dir.exists() {
+    return Ok(vec![]);
+  }
+  let mut snapshots: Vec<Snapshot> = fs::read_dir(server_dir)?
+    .filter_map(|e| e.ok())
+    .filter(|e| e.path().is_dir())
+    .map(|e| Snapshot {
+      taken: e.file_name().to_string_lossy().to_string(),
+      bytes: dir_size(&e.path()),
+    })
+    .collect();
+  snapshots.sort_by(|a, b| a.taken.cmp(&b.taken));
+  Ok(snapshots)
+}
+
+/// Removes all but the newest `keep` snapshots, saying how many went. The
+/// newest always stays, it's the one that was just taken.
+fn rotate(server_dir: &Path, keep: usize) -> Result<usize, anyhow::Error> {
+  let snapshots = list(server_dir)?;
+  let excess = snapshots.len().saturating_sub(keep.max(1));
+  for old in &snapshots[..excess] {
+    if let Err(e) = fs::remove_dir_all(server_dir.join(&old.taken)) {
+      warn!("Failed to remove old backup {}: {}", old.taken, e);
+    }
+  }
+  Ok(excess)
+}
+
+fn dir_size(dir: &Path) -> u64 {
+  fs::read_dir(dir)
+    .into_iter()
+    .flatten()
+    .filter_map(|e| e.ok()?.metadata().ok())
+    .map(|m| m.len())
+    .sum()
+}
+
+fn human_size(bytes: u64) -> String {
+  match bytes {
+    b if b >= 1 << 30 => format!("{:.1} GiB", b as f64 / (1u64 << 30) as f64),
+    b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1u64 << 20) as f64),
+    b => format!("{:.1} KiB", b as f64 / 1024.0),
+  }
+}
+
+#[derive(new)]
+pub struct Backup {
+  backups: Arc<Backups>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Backup {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let name = args
+      .str("server-name")
+      .map_err(|e| anyhow!("Must provide a server name").context(e))?;
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(format!("Backing up {name}...")),
+      )
+      .await?;
+    let msg = match self.backups.snapshot(name).await {
+      Ok(s) => MessageBuilder::new()
+        .push("Backed up ")
+        .push_bold(name)
+        .push(" as ")
+        .push_mono(&s.taken)
+        .push(format!(" ({})", human_size(s.bytes)))
+        .build(),
+      Err(e) => format!("{e}"),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+      .await?;
+    Ok(())
+  }
+}
+
+#[derive(new)]
+pub struct BackupList {
+  backups: Arc<Backups>,
+}
+
+#[async_trait]
+impl SubCommandHandler for BackupList {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let name = args
+      .str("server-name")
+      .map_err(|e| anyhow!("Must provide a server name").context(e))?;
+    let msg = match self.backups.list(name).await {
+      Ok(snapshots) if snapshots.is_empty() => format!("No backups of {name} yet"),
+      Ok(snapshots) => {
+        let mut msg = MessageBuilder::new();
+        msg.push_bold(format!("Backups of {name}\n"));
+        for s in snapshots.iter().rev() {
+          msg
+            .push_mono(&s.taken)
+            .push(format!(" {}\n", human_size(s.bytes)));
+        }
+        msg.build()
+      }
+      Err(e) => format!("{e}"),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+      .await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::docker::fake::{local, FakeDocker};
+  use bollard::service::{ContainerConfig, MountPoint};
+  use std::collections::HashMap;
+  use tempfile::tempdir;
+
+  fn mount(destination: &str, typ: MountPointTypeEnum, rw: bool) -> MountPoint {
+    MountPoint {
+      typ: Some(typ),
+      destination: Some(destination.to_string()),
+      rw: Some(rw),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn backs_up_writable_mounts() {
+    let mut container = ContainerInspectResponse {
+      mounts: Some(vec![
+        mount("/modpacks", MountPointTypeEnum::BIND, false),
+        mount("/data", MountPointTypeEnum::BIND, true),
+        mount("/config", MountPointTypeEnum::VOLUME, true),
+        mount("/tmp", MountPointTypeEnum::TMPFS, true),
+      ]),
+      ..Default::default()
+    };
+    assert_eq!(vec!["/config", "/data"], backup_paths(&container));
+
+    container.config = Some(ContainerConfig {
+      labels: Some(HashMap::from([(
+        BACKUP_LABEL.to_string(),
+        "/config/worlds_local, /data/world".to_string(),
+      )])),
+      ..Default::default()
+    });
+    assert_eq!(
+      vec!["/config/worlds_local", "/data/world"],
+      backup_paths(&container)
+    );
+  }
+
+  #[test]
+  fn rotation_keeps_newest() {
+    let dir = tempdir().unwrap();
+    for taken in [
+      "2024-01-03T00-00-00Z",
+      "2024-01-01T00-00-00Z",
+      "2024-01-02T00-00-00Z",
+    ] {
+      fs::create_dir(dir.path().join(taken)).unwrap();
+      fs::write(dir.path().join(taken).join("data.tar"), [0u8; 10]).unwrap();
+    }
+
+    assert_eq!(1, rotate(dir.path(), 2).unwrap());
+    let left = list(dir.path()).unwrap();
+    assert_eq!(
+      vec![
+        Snapshot {
+          taken: "2024-01-02T00-00-00Z".to_string(),
+          bytes: 10
+        },
+        Snapshot {
+          taken: "2024-01-03T00-00-00Z".to_string(),
+          bytes: 10
+        },
+      ],
+      left
+    );
+    assert_eq!(0, rotate(dir.path(), 2).unwrap());
+    assert!(list(&dir.path().join("nothing")).unwrap().is_empty());
+
+    assert_eq!(1, rotate(dir.path(), 0).unwrap());
+    assert_eq!(1, list(dir.path()).unwrap().len());
+  }
+
+  #[tokio::test]
+  async fn one_directory_however_the_server_is_named() {
+    let dir = tempdir().unwrap();
+    let docker: Box<dyn DockerClient> = Box::new(local(FakeDocker::default()));
+    let backups = Backups::new(Arc::new(docker), dir.path().to_path_buf(), 5);
+    fs::create_dir_all(dir.path().join("local-valheim/2024-01-01T00-00-00Z")).unwrap();
+
+    for name in ["valheim", "local/valheim"] {
+      let taken: Vec<_> = backups
+        .list(name)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|s| s.taken)
+        .collect();
+      assert_eq!(vec!["2024-01-01T00-00-00Z"], taken);
+    }
+  }
+}
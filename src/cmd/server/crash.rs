@@ -0,0 +1,174 @@
+use crate::docker::{ContainerEvent, DockerClient, LogRange};
+use derive_new::new;
+use futures::StreamExt;
+use serenity::{
+  all::{ChannelId, Http},
+  builder::CreateMessage,
+  utils::MessageBuilder,
+};
+use std::{
+  collections::HashSet,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+/// How many of the crashed server's last words to share
+const CRASH_LINES: usize = 15;
+/// Room left for the log lines once the rest of the message is written
+const REPORT_LOG_MAX: usize = 1800;
+const RECONNECT: Duration = Duration::from_secs(60);
+
+/// Tells crashes apart from deliberate stops. Docker kills a container before
+/// it dies when asked to stop it, whereas a crash just dies.
+#[derive(Default)]
+pub struct Deaths {
+  killed: HashSet<String>,
+}
+
+impl Deaths {
+  pub fn crashed(&mut self, event: &ContainerEvent) -> bool {
+    match event.action.as_str() {
+      "kill" => {
+        self.killed.insert(event.name.clone());
+        false
+      }
+      "die" => !self.killed.remove(&event.name) && event.exit_code.is_some_and(|c| c != 0),
+      _ => false,
+    }
+  }
+}
+
+/// Follows docker's events in the background, posting to the alerts channel
+/// whenever a managed server dies without being asked to
+#[derive(new)]
+pub struct CrashWatch {
+  docker: Arc<Box<dyn DockerClient>>,
+  channel: Option<ChannelId>,
+  #[new(default)]
+  running: AtomicBool,
+}
+
+impl CrashWatch {
+  /// Starts watching, unless there's nowhere to report to or we already are
+  pub fn start(self: &Arc<Self>, http: Arc<Http>) {
+    let Some(channel) = self.channel else {
+      return;
+    };
+    if self.running.swap(true, Ordering::SeqCst) {
+      return;
+    }
+    let watch = self.clone();
+    tokio::spawn(async move {
+      loop {
+        if let Err(e) = watch.follow(&http, channel).await {
+          warn!("Lost docker events, retrying in {:?}: {}", RECONNECT, e);
+        }
+        sleep(RECONNECT).await;
+      }
+    });
+  }
+
+  async fn follow(&self, http: &Http, channel: ChannelId) -> Result<(), anyhow::Error> {
+    let mut events = self.docker.events().await?;
+    let mut deaths = Deaths::default();
+    info!("Watching for server crashes");
+    while let Some(event) = events.next().await {
+      // A bad event needn't cost us the ones after it, the stream ends if it's done for
+      let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+          warn!("Bad docker event: {}", e);
+          continue;
+        }
+      };
+      if !deaths.crashed(&event) {
+        continue;
+      }
+      warn!("{} crashed with {:?}", event.name, event.exit_code);
+      let logs = self
+        .docker
+        .logs(&event.name, LogRange::Last(CRASH_LINES))
+        .await
+        .unwrap_or_else(|e| vec![format!("(Couldn't fetch logs: {e})")]);
+      if let Err(e) = channel
+        .send_message(http, CreateMessage::new().content(report(&event, &logs)))
+        .await
+      {
+        error!("Failed to report crash of {} {:?}", event.name, e);
+      }
+    }
+    Ok(())
+  }
+}
+
+/// The alert for a crash, keeping as many of the final log lines as fit
+pub fn report(event: &ContainerEvent, logs: &[String]) -> String {
+  let mut budget = REPORT_LOG_MAX;
+  let mut kept: Vec<&str> = logs
+    .iter()
+    .rev()
+    .take_while(|l| match budget.checked_sub(l.len() + 1) {
+      Some(left) => {
+        budget = left;
+        true
+      }
+      None => false,
+    })
+    .map(String::as_str)
+    .collect();
+  kept.reverse();
+
+  let mut msg = MessageBuilder::new();
+  msg
+    .push_bold(&event.name)
+    .push(" crashed")
+    .push(match event.exit_code {
+      Some(code) => format!(" (exit code {code})"),
+      None => String::new(),
+    });
+  if !kept.is_empty() {
+    msg.push_codeblock(kept.join("\n"), None);
+  }
+  msg.build()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  fn event(action: &str, code: Option<i64>) -> ContainerEvent {
+    ContainerEvent {
+      name: "nas/valheim".to_string(),
+      action: action.to_string(),
+      exit_code: code,
+    }
+  }
+
+  #[test_case(&[("die", Some(137))] => vec![true]; "crash")]
+  #[test_case(&[("die", Some(0))] => vec![false]; "shut itself down")]
+  #[test_case(&[("kill", None), ("die", Some(143)), ("stop", None)] => vec![false, false, false]; "stopped")]
+  #[test_case(&[("kill", None), ("die", Some(143)), ("die", Some(1))] => vec![false, false, true]; "crash after restart")]
+  fn spots_crashes(events: &[(&str, Option<i64>)]) -> Vec<bool> {
+    let mut deaths = Deaths::default();
+    events
+      .iter()
+      .map(|(action, code)| deaths.crashed(&event(action, *code)))
+      .collect()
+  }
+
+  #[test]
+  fn report_keeps_last_lines_that_fit() {
+    let logs: Vec<String> = (0..100).map(|i| format!("{i:0>50}")).collect();
+    let msg = report(&event("die", Some(1)), &logs);
+    assert!(msg.starts_with("**nas/valheim** crashed (exit code 1)"));
+    assert!(msg.len() <= 2000);
+    assert!(msg.contains(&logs[99]));
+    assert!(!msg.contains(&logs[0]));
+  }
+}
@@ -0,0 +1,208 @@
+use super::{list::extract_name, status::query_game};
+use crate::{
+  config::DockerHost,
+  docker::{DockerClient, IDLE_LABEL},
+};
+use bollard::service::ContainerSummaryStateEnum::RUNNING;
+use derive_new::new;
+use serenity::{
+  all::{ChannelId, Http},
+  builder::CreateMessage,
+  utils::MessageBuilder,
+};
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+use tokio::{sync::Mutex, time::sleep};
+use tracing::{error, info, warn};
+
+const POLL: Duration = Duration::from_secs(60);
+
+/// How long the container may sit empty before it's stopped, going by its
+/// `idle` label: `off` opts out, a duration like `2h` overrides the default
+pub fn idle_period(
+  labels: Option<&HashMap<String, String>>,
+  default: Duration,
+) -> Option<Duration> {
+  let Some(label) = labels.and_then(|l| l.get(IDLE_LABEL)) else {
+    return Some(default);
+  };
+  match label.as_str() {
+    "off" | "false" | "never" => None,
+    period => humantime::parse_duration(period)
+      .inspect_err(|e| warn!("Ignoring bad idle label {}: {}", period, e))
+      .ok()
+      .or(Some(default)),
+  }
+}
+
+/// Remembers since when each server has been empty
+#[derive(Default)]
+pub struct IdleTracker {
+  empty_since: HashMap<String, Instant>,
+}
+
+impl IdleTracker {
+  /// Notes the player count, saying whether it's been empty for long enough to stop
+  pub fn observe(&mut self, name: &str, players: u32, now: Instant, period: Duration) -> bool {
+    if players > 0 {
+      self.empty_since.remove(name);
+      return false;
+    }
+    let since = *self.empty_since.entry(name.to_string()).or_insert(now);
+    match now.duration_since(since) >= period {
+      true => {
+        self.empty_since.remove(name);
+        true
+      }
+      false => false,
+    }
+  }
+
+  /// Drops servers that have since stopped, so they start afresh next time
+  pub fn retain(&mut self, running: &[String]) {
+    self.empty_since.retain(|name, _| running.contains(name));
+  }
+}
+
+/// Stops servers nobody's playing on, checking in on their player counts
+#[derive(new)]
+pub struct IdleMonitor {
+  docker: Arc<Box<dyn DockerClient>>,
+  hosts: Vec<DockerHost>,
+  channel: Option<ChannelId>,
+  /// Zero turns the monitor off
+  period: Duration,
+  #[new(default)]
+  tracker: Mutex<IdleTracker>,
+  #[new(default)]
+  running: AtomicBool,
+}
+
+impl IdleMonitor {
+  pub fn start(self: &Arc<Self>, http: Arc<Http>) {
+    if self.period.is_zero() || self.running.swap(true, Ordering::SeqCst) {
+      return;
+    }
+    info!("Stopping servers idle for {:?}", self.period);
+    let monitor = self.clone();
+    tokio::spawn(async move {
+      loop {
+        sleep(POLL).await;
+        if let Err(e) = monitor.check(&http).await {
+          warn!("Failed to check for idle servers: {}", e);
+        }
+      }
+    });
+  }
+
+  async fn check(&self, http: &Http) -> Result<(), anyhow::Error> {
+    let running: Vec<_> = self
+      .docker
+      .list()
+      .await?
+      .into_iter()
+      .filter(|c| c.state == Some(RUNNING))
+      .collect();
+    let names: Vec<String> = running
+      .iter()
+      .map(|c| extract_name(c).to_string())
+      .collect();
+    self.tracker.lock().await.retain(&names);
+
+    for (container, name) in running.iter().zip(names) {
+      let Some(period) = idle_period(container.labels.as_ref(), self.period) else {
+        continue;
+      };
+      // Servers still booting or that we can't ask aren't known to be empty
+      let players = match query_game(&**self.docker, &self.hosts, &name).await {
+        Ok((_, info)) => info.online,
+        Err(_) => continue,
+      };
+      let idle = self
+        .tracker
+        .lock()
+        .await
+        .observe(&name, players, Instant::now(), period);
+      if !idle {
+        continue;
+      }
+
+      info!("Stopping {} after {:?} without players", name, period);
+      let msg = match self.docker.stop(&name).await {
+        Ok(_) => MessageBuilder::new()
+          .push("Stopped ")
+          .push_bold(&name)
+          .push(format!(
+            ", nobody's played for {}",
+            humantime::format_duration(period)
+          ))
+          .build(),
+        Err(e) => {
+          error!("Failed to stop idle server {}: {:?}", name, e);
+          format!("Tried stopping {name} since nobody's playing, but: {e}")
+        }
+      };
+      if let Some(channel) = self.channel {
+        if let Err(e) = channel
+          .send_message(http, CreateMessage::new().content(msg))
+          .await
+        {
+          error!("Failed to announce idle stop of {} {:?}", name, e);
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  const DEFAULT: Duration = Duration::from_secs(30 * 60);
+
+  #[test_case(None => Some(DEFAULT); "unlabelled")]
+  #[test_case(Some("off") => None; "opted out")]
+  #[test_case(Some("2h") => Some(Duration::from_secs(7200)); "override")]
+  #[test_case(Some("soon") => Some(DEFAULT); "nonsense")]
+  fn idle_periods(label: Option<&str>) -> Option<Duration> {
+    let labels = label.map(|l| HashMap::from([(IDLE_LABEL.to_string(), l.to_string())]));
+    idle_period(labels.as_ref(), DEFAULT)
+  }
+
+  #[test]
+  fn stops_after_empty_period() {
+    let mut tracker = IdleTracker::default();
+    let start = Instant::now();
+    let at = |mins: u64| start + Duration::from_secs(mins * 60);
+
+    assert!(!tracker.observe("mc", 0, at(0), DEFAULT));
+    assert!(!tracker.observe("mc", 0, at(29), DEFAULT));
+    assert!(tracker.observe("mc", 0, at(30), DEFAULT));
+    // Stopping it resets the clock
+    assert!(!tracker.observe("mc", 0, at(31), DEFAULT));
+  }
+
+  #[test]
+  fn players_reset_the_clock() {
+    let mut tracker = IdleTracker::default();
+    let start = Instant::now();
+    let at = |mins: u64| start + Duration::from_secs(mins * 60);
+
+    tracker.observe("mc", 0, at(0), DEFAULT);
+    tracker.observe("mc", 2, at(20), DEFAULT);
+    assert!(!tracker.observe("mc", 0, at(40), DEFAULT));
+    assert!(tracker.observe("mc", 0, at(70), DEFAULT));
+
+    tracker.observe("valheim", 0, at(0), DEFAULT);
+    tracker.retain(&[]);
+    assert!(!tracker.observe("valheim", 0, at(45), DEFAULT));
+  }
+}
@@ -4,24 +4,112 @@ use crate::{
 };
 use anyhow::anyhow;
 use derive_new::new;
-use rand::rng;
-use rand::seq::SliceRandom;
+use futures::future::join_all;
 use reqwest::Client;
 use serenity::{
-  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  all::{ChannelId, CommandInteraction, Http},
+  async_trait,
+  builder::{CreateMessage, EditInteractionResponse},
+  client::Context,
   utils::MessageBuilder,
 };
-use std::time::Duration;
+use std::{
+  net::IpAddr,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+use tokio::{sync::Mutex, time::sleep};
+use tracing::{error, info, warn};
 
-const IP_ECHOERS: &[&str; 3] = &[
-  "https://api.ipify.org/",
-  "https://api.my-ip.io/v1/ip",
-  "https://ip.seeip.org/",
-];
+const ECHO_TIMEOUT: Duration = Duration::from_secs(3);
 
+/// Works out where the world sees us from by asking the echo services, remembering
+/// the answer for a little while so a flurry of commands doesn't hammer them
 #[derive(new)]
-pub struct Ip {
+pub struct PublicIp {
   http: Client,
+  echoers: Vec<String>,
+  ttl: Duration,
+  #[new(default)]
+  cached: Mutex<Option<(IpAddr, Instant)>>,
+}
+
+impl PublicIp {
+  /// The public IP, from the cache while it's fresh
+  pub async fn get(&self) -> Option<IpAddr> {
+    let mut cached = self.cached.lock().await;
+    if let Some((ip, at)) = *cached {
+      if at.elapsed() < self.ttl {
+        return Some(ip);
+      }
+    }
+    let ip = self.resolve().await?;
+    *cached = Some((ip, Instant::now()));
+    Some(ip)
+  }
+
+  /// Asks every echoer at once and goes with what most of them say. Ties go to
+  /// whichever is listed first, so it takes three echoers before one bad
+  /// service gets outvoted.
+  async fn resolve(&self) -> Option<IpAddr> {
+    let answers = join_all(self.echoers.iter().map(|e| self.ask(e))).await;
+    let family = same_family(&answers);
+    let ip = consensus(&family)?;
+    if family.iter().any(|a| *a != ip) {
+      warn!("IP echoers disagree, going with {}: {:?}", ip, answers);
+    }
+    Some(ip)
+  }
+
+  async fn ask(&self, addr: &str) -> Option<IpAddr> {
+    let body = self
+      .http
+      .get(addr)
+      .timeout(ECHO_TIMEOUT)
+      .send()
+      .await
+      .and_then(|r| r.error_for_status())
+      .inspect_err(|e| warn!("IP echoer {} failed: {}", addr, e))
+      .ok()?
+      .text()
+      .await
+      .ok()?;
+    body
+      .trim()
+      .parse()
+      .inspect_err(|_| warn!("IP echoer {} answered with nonsense", addr))
+      .ok()
+  }
+}
+
+/// The IPv4 answers, or the IPv6 ones if nobody gave a v4 address. Dual stack
+/// hosts get asked over either, and game servers are reached over v4, so the
+/// families are voted on apart rather than flip flopping between them.
+fn same_family(answers: &[Option<IpAddr>]) -> Vec<IpAddr> {
+  let (v4, v6): (Vec<IpAddr>, Vec<IpAddr>) = answers.iter().flatten().partition(|a| a.is_ipv4());
+  match v4.is_empty() {
+    true => v6,
+    false => v4,
+  }
+}
+
+/// The answer given most, earlier echoers winning ties
+fn consensus(answers: &[IpAddr]) -> Option<IpAddr> {
+  let votes = |ip: &IpAddr| answers.iter().filter(|v| *v == ip).count();
+  answers
+    .iter()
+    .fold(None, |best: Option<IpAddr>, ip| match best {
+      Some(b) if votes(&b) >= votes(ip) => Some(b),
+      _ => Some(*ip),
+    })
+}
+
+#[derive(new)]
+pub struct Ip {
+  ip: Arc<PublicIp>,
   emoji: EmojiLookup,
 }
 
@@ -40,16 +128,7 @@ impl SubCommandHandler for Ip {
       }
     };
 
-    let mut maybe_the_ip = None;
-    let mut ip_echoers = *IP_ECHOERS;
-    ip_echoers.shuffle(&mut rng());
-    for addr in ip_echoers {
-      if let Ok(ip) = attempt_resolve(&self.http, addr).await {
-        maybe_the_ip = Some(ip);
-        break;
-      }
-    }
-    let Some(the_ip) = maybe_the_ip else {
+    let Some(the_ip) = self.ip.get().await else {
       itx
         .edit_response(
           &ctx.http,
@@ -64,7 +143,7 @@ impl SubCommandHandler for Ip {
     let mut build = MessageBuilder::new();
     build
       .push_bold("Ya boi shruggin at ")
-      .push_mono(the_ip)
+      .push_mono(the_ip.to_string())
       .push_bold(" I guess")
       .emoji(&emoji);
     itx
@@ -78,12 +157,151 @@ impl SubCommandHandler for Ip {
   }
 }
 
-async fn attempt_resolve(http: &Client, addr: &str) -> Result<String, reqwest::Error> {
-  http
-    .get(addr)
-    .timeout(Duration::from_secs(3))
-    .send()
-    .await?
-    .text()
-    .await
+/// Checks in on the public IP now and then, telling the alerts channel when it
+/// moves so everyone knows to update their saved servers
+#[derive(new)]
+pub struct IpWatch {
+  ip: Arc<PublicIp>,
+  channel: Option<ChannelId>,
+  /// Zero turns the watch off
+  interval: Duration,
+  #[new(default)]
+  last: Mutex<Option<IpAddr>>,
+  #[new(default)]
+  running: AtomicBool,
+}
+
+impl IpWatch {
+  pub fn start(self: &Arc<Self>, http: Arc<Http>) {
+    let Some(channel) = self.channel else {
+      return;
+    };
+    if self.interval.is_zero() || self.running.swap(true, Ordering::SeqCst) {
+      return;
+    }
+    info!("Checking for public IP changes every {:?}", self.interval);
+    let watch = self.clone();
+    tokio::spawn(async move {
+      loop {
+        if let Some((old, new)) = watch.check().await {
+          info!("Public IP changed from {} to {}", old, new);
+          let msg = MessageBuilder::new()
+            .push("Our IP moved from ")
+            .push_mono(old.to_string())
+            .push(" to ")
+            .push_mono(new.to_string())
+            .push(", update your saved servers")
+            .build();
+          if let Err(e) = channel
+            .send_message(&http, CreateMessage::new().content(msg))
+            .await
+          {
+            error!("Failed to announce IP change {:?}", e);
+          }
+        }
+        sleep(watch.interval).await;
+      }
+    });
+  }
+
+  /// Looks again, giving the old and new IP if it's moved since last time
+  async fn check(&self) -> Option<(IpAddr, IpAddr)> {
+    let now = self.ip.get().await?;
+    let mut last = self.last.lock().await;
+    match last.replace(now) {
+      Some(old) if old != now => Some((old, now)),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::AtomicUsize;
+  use test_case::test_case;
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+  };
+
+  /// Stands in for an echo service, answering every request with the body
+  /// it's handed and counting how many it got
+  async fn echoer(body: Arc<std::sync::Mutex<String>>) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = format!("http://{}/", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    tokio::spawn(async move {
+      loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut buf = [0u8; 1024];
+        let _ = stream.read(&mut buf).await;
+        let body = body.lock().unwrap().clone();
+        let resp = format!(
+          "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+          body.len(),
+          body
+        );
+        let _ = stream.write_all(resp.as_bytes()).await;
+      }
+    });
+    (addr, hits)
+  }
+
+  fn body(s: &str) -> Arc<std::sync::Mutex<String>> {
+    Arc::new(std::sync::Mutex::new(s.to_string()))
+  }
+
+  fn ip(s: &str) -> Option<IpAddr> {
+    s.parse().ok()
+  }
+
+  #[test_case(&[] => None; "nobody answered")]
+  #[test_case(&[None, ip("1.2.3.4")] => ip("1.2.3.4"); "one answer")]
+  #[test_case(&[ip("6.6.6.6"), ip("1.2.3.4"), ip("1.2.3.4")] => ip("1.2.3.4"); "majority")]
+  #[test_case(&[ip("1.2.3.4"), ip("6.6.6.6")] => ip("1.2.3.4"); "tie goes to first")]
+  #[test_case(&[ip("::1"), ip("1.2.3.4")] => ip("1.2.3.4"); "v4 wins")]
+  #[test_case(&[ip("1.2.3.4"), ip("::1"), ip("::1")] => ip("1.2.3.4"); "v6 doesn't outvote v4")]
+  #[test_case(&[None, ip("::1")] => ip("::1"); "v6 when that's all there is")]
+  fn agrees_on_ip(answers: &[Option<IpAddr>]) -> Option<IpAddr> {
+    consensus(&same_family(answers))
+  }
+
+  #[tokio::test]
+  async fn ignores_answers_that_arent_ips() {
+    let (good, _) = echoer(body("203.0.113.7\n")).await;
+    let (bad, _) = echoer(body("<html>rate limited</html>")).await;
+    let public = PublicIp::new(Client::new(), vec![bad.clone()], Duration::ZERO);
+    assert_eq!(None, public.get().await);
+
+    let public = PublicIp::new(Client::new(), vec![bad, good], Duration::ZERO);
+    assert_eq!(ip("203.0.113.7"), public.get().await);
+  }
+
+  #[tokio::test]
+  async fn caches_within_ttl() {
+    let (addr, hits) = echoer(body("203.0.113.7")).await;
+    let public = PublicIp::new(Client::new(), vec![addr], Duration::from_secs(60));
+    assert_eq!(ip("203.0.113.7"), public.get().await);
+    assert_eq!(ip("203.0.113.7"), public.get().await);
+    assert_eq!(1, hits.load(Ordering::SeqCst));
+  }
+
+  #[tokio::test]
+  async fn notices_ip_changes() {
+    let answer = body("203.0.113.7");
+    let (addr, _) = echoer(answer.clone()).await;
+    let public = Arc::new(PublicIp::new(Client::new(), vec![addr], Duration::ZERO));
+    let watch = IpWatch::new(public, None, Duration::from_secs(60));
+
+    assert_eq!(None, watch.check().await);
+    assert_eq!(None, watch.check().await);
+    *answer.lock().unwrap() = "198.51.100.2".to_string();
+    assert_eq!(
+      Some((ip("203.0.113.7").unwrap(), ip("198.51.100.2").unwrap())),
+      watch.check().await
+    );
+  }
 }
@@ -1,6 +1,6 @@
 use crate::{
   cmd::{arg_util::Args, SubCommandHandler},
-  docker::DockerClient,
+  docker::{DockerClient, GAME_LABEL, VERSION_LABEL},
 };
 use bollard::service::ContainerSummary;
 use derive_new::new;
@@ -38,24 +38,42 @@ impl SubCommandHandler for List {
 async fn build_list_msg(docker: &dyn DockerClient) -> Result<MessageBuilder, anyhow::Error> {
   let mut bdy = MessageBuilder::new();
   let summaries = docker.list().await?;
+  bdy.push_codeblock(render_table(&summaries), None);
+  Ok(bdy)
+}
+
+fn render_table(summaries: &[ContainerSummary]) -> String {
   let stat_len = 10;
-  let max_len = summaries
-    .iter()
-    .map(extract_name)
-    .map(|s| s.len())
-    .max()
-    .unwrap_or(1);
+  let width = |header: &str, col: &dyn Fn(&ContainerSummary) -> &str| {
+    summaries
+      .iter()
+      .map(|s| col(s).len())
+      .chain([header.len()])
+      .max()
+      .unwrap_or(1)
+  };
+  let name_len = width("Name", &extract_name);
+  let game_len = width("Game", &|s| label(s, GAME_LABEL));
+  let ver_len = width("Version", &|s| label(s, VERSION_LABEL));
 
   let mut table = String::new();
-  table.push_str(&format!("{:<max_len$} | {:<stat_len$}\n", "Name", "Status"));
-  table.push_str(&format!("{:-<max_len$}---{:-<stat_len$}\n", "", ""));
-  let msg = summaries
+  table.push_str(&format!(
+    "{:<name_len$} | {:<game_len$} | {:<ver_len$} | {:<stat_len$}\n",
+    "Name", "Game", "Version", "Status"
+  ));
+  table.push_str(&format!(
+    "{:-<name_len$}---{:-<game_len$}---{:-<ver_len$}---{:-<stat_len$}\n",
+    "", "", "", ""
+  ));
+  summaries
     .iter()
-    .sorted_by_key(|summary| extract_name(summary))
+    .sorted_by_key(|summary| (label(summary, GAME_LABEL), extract_name(summary)))
     .fold(table, |mut acc, summary| {
       acc.push_str(&format!(
-        "{:<max_len$} | {:<stat_len$}\n",
+        "{:<name_len$} | {:<game_len$} | {:<ver_len$} | {:<stat_len$}\n",
         extract_name(summary),
+        label(summary, GAME_LABEL),
+        label(summary, VERSION_LABEL),
         summary
           .state
           .as_ref()
@@ -63,12 +81,10 @@ async fn build_list_msg(docker: &dyn DockerClient) -> Result<MessageBuilder, any
           .unwrap_or_else(|| "(No State)".into()),
       ));
       acc
-    });
-  bdy.push_codeblock(msg, None);
-  Ok(bdy)
+    })
 }
 
-fn extract_name(summary: &ContainerSummary) -> &str {
+pub fn extract_name(summary: &ContainerSummary) -> &str {
   summary
     .names
     .as_ref()
@@ -76,3 +92,52 @@ fn extract_name(summary: &ContainerSummary) -> &str {
     .and_then(|s| s.strip_prefix("/"))
     .unwrap_or("(No Name)")
 }
+
+/// The container's value for the label, or a dash if it has none
+pub fn label<'a>(summary: &'a ContainerSummary, key: &str) -> &'a str {
+  summary
+    .labels
+    .as_ref()
+    .and_then(|l| l.get(key))
+    .map_or("-", |v| v.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bollard::service::ContainerSummaryStateEnum;
+  use std::collections::HashMap;
+
+  fn summary(name: &str, labels: &[(&str, &str)]) -> ContainerSummary {
+    ContainerSummary {
+      names: Some(vec![format!("/{name}")]),
+      labels: Some(
+        labels
+          .iter()
+          .map(|(k, v)| (k.to_string(), v.to_string()))
+          .collect::<HashMap<_, _>>(),
+      ),
+      state: Some(ContainerSummaryStateEnum::RUNNING),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn table_shows_game_and_version() {
+    let table = render_table(&[
+      summary("valheim", &[("game", "valheim"), ("version", "vanilla")]),
+      summary(
+        "valhelsia",
+        &[("game", "minecraft"), ("version", "valhelsia")],
+      ),
+      summary("mystery", &[]),
+    ]);
+    let rows: Vec<_> = table.lines().collect();
+    assert_eq!(5, rows.len());
+    assert!(rows[0].starts_with("Name      | Game      | Version   | Status"));
+    // Grouped by game, unlabelled first
+    assert!(rows[2].starts_with("mystery   | -         | -         |"));
+    assert!(rows[3].starts_with("valhelsia | minecraft | valhelsia |"));
+    assert!(rows[4].starts_with("valheim   | valheim   | vanilla   |"));
+  }
+}
@@ -0,0 +1,55 @@
+use crate::{
+  cmd::{arg_util::Args, SubCommandHandler},
+  docker::{DockerClient, LogRange},
+};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction,
+  async_trait,
+  builder::{CreateAttachment, EditInteractionResponse},
+  client::Context,
+};
+use std::sync::Arc;
+
+pub const DEFAULT_LINES: usize = 100;
+pub const MAX_LINES: usize = 5000;
+
+#[derive(new)]
+pub struct Logs {
+  docker: Arc<Box<dyn DockerClient>>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Logs {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let name = args
+      .str("server-name")
+      .map_err(|e| anyhow!("Must provide a server name").context(e))?;
+    let lines = args
+      .opt_i64("lines")?
+      .map(|n| n.clamp(1, MAX_LINES as i64) as usize)
+      .unwrap_or(DEFAULT_LINES);
+
+    let edit = match self.docker.logs(name, LogRange::Last(lines)).await {
+      Ok(logs) if logs.is_empty() => {
+        EditInteractionResponse::new().content(format!("{name} hasn't logged anything"))
+      }
+      Ok(logs) => EditInteractionResponse::new()
+        .content(format!("Last {} lines from {name}", logs.len()))
+        .new_attachment(CreateAttachment::bytes(
+          logs.join("\n"),
+          // Names can carry their host, which makes for an odd file name
+          format!("{}.log", name.replace('/', "-")),
+        )),
+      Err(e) => EditInteractionResponse::new().content(format!("{e}")),
+    };
+    itx.edit_response(&ctx.http, edit).await?;
+    Ok(())
+  }
+}
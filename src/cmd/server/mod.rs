@@ -1,44 +1,157 @@
+mod autocomplete;
+mod backup;
+mod crash;
+mod idle;
 mod ip;
 mod list;
+mod logs;
+mod permissions;
+mod power;
+mod query;
+mod restart;
 mod start;
+mod startup;
+mod status;
 mod stop;
 
 use super::{arg_util::Args, AppInteractor, SubCommandHandler};
-use crate::{docker::DockerClient, emoji::EmojiLookup};
+use crate::{
+  config::Config, docker::DockerClient, emoji::EmojiLookup, persistence::PersistentStore,
+};
+use backup::{BackupList, Backups};
+use crash::CrashWatch;
+use idle::IdleMonitor;
 use ip::*;
 use list::*;
+use logs::*;
+pub use permissions::ServerPermissions;
+use permissions::{permitted, Permissions};
+use power::*;
 use reqwest::Client;
+use restart::Restart;
 use serenity::{
-  all::{CommandInteraction, CommandOptionType, CommandType, CreateCommandOption},
+  all::{
+    ChannelId, CommandInteraction, CommandOptionType, CommandType, ComponentInteraction,
+    CreateCommandOption, Http, InteractionContext, Permissions as DiscordPermissions,
+  },
   async_trait,
-  builder::{CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage},
+  builder::{
+    AutocompleteChoice, CreateAutocompleteResponse, CreateCommand, CreateInteractionResponse,
+    CreateInteractionResponseMessage,
+  },
   prelude::Context,
 };
 use start::*;
-use std::error::Error;
+use status::*;
+use std::{error::Error, sync::Arc};
 use stop::*;
 use tracing::{error, instrument};
 
 const NAME: &str = "servers";
 
 pub struct GameServers {
+  docker: Arc<Box<dyn DockerClient>>,
   list: List,
   start: Start,
   stop: Stop,
   ip: Ip,
+  wake: Wake,
+  sleep: Sleep,
+  logs: Logs,
+  status: Status,
+  restart: Restart,
+  backup: backup::Backup,
+  backups: BackupList,
+  permissions: Permissions,
+  persistence: Arc<PersistentStore>,
+  watchers: Watchers,
+}
+
+/// Keeps an eye on the servers in the background, once we're connected
+#[derive(Clone)]
+pub struct Watchers {
+  crash: Arc<CrashWatch>,
+  idle: Arc<IdleMonitor>,
+  ip: Arc<IpWatch>,
+}
+
+impl Watchers {
+  pub fn start(&self, http: Arc<Http>) {
+    self.crash.start(http.clone());
+    self.idle.start(http.clone());
+    self.ip.start(http);
+  }
 }
 
 impl GameServers {
-  pub fn new(emoji: EmojiLookup, http: Client, docker: Box<dyn DockerClient>) -> Self {
-    use std::sync::Arc;
+  pub fn new(
+    config: &Config,
+    emoji: EmojiLookup,
+    http: Client,
+    docker: Box<dyn DockerClient>,
+    persistence: Arc<PersistentStore>,
+  ) -> Self {
     let docker = Arc::new(docker);
+    let shell: Arc<dyn RemoteShell> = Arc::new(Ssh::new(
+      config.server_user.clone(),
+      config.server_host.clone(),
+    ));
+    let alerts = config.server_alerts_channel.map(ChannelId::new);
+    let public_ip = Arc::new(PublicIp::new(
+      http,
+      config.ip_echoers.clone(),
+      config.ip_cache_ttl,
+    ));
+    let backups = Arc::new(Backups::new(
+      docker.clone(),
+      config.backup_dir.clone().into(),
+      config.backup_keep,
+    ));
     GameServers {
+      docker: docker.clone(),
       list: List::new(docker.clone()),
-      start: Start::new(docker.clone()),
-      stop: Stop::new(docker),
-      ip: Ip::new(http, emoji),
+      start: Start::new(
+        docker.clone(),
+        public_ip.clone(),
+        config.server_start_timeout,
+      ),
+      stop: Stop::new(
+        docker.clone(),
+        config.docker_hosts.clone(),
+        persistence.clone(),
+      ),
+      ip: Ip::new(public_ip.clone(), emoji),
+      wake: Wake::new(config.server_mac.clone(), shell.clone()),
+      sleep: Sleep::new(docker.clone(), shell),
+      logs: Logs::new(docker.clone()),
+      status: Status::new(docker.clone(), config.docker_hosts.clone()),
+      restart: Restart::new(
+        docker.clone(),
+        config.docker_hosts.clone(),
+        persistence.clone(),
+        public_ip.clone(),
+        config.server_start_timeout,
+      ),
+      backup: backup::Backup::new(backups.clone()),
+      backups: BackupList::new(backups),
+      permissions: Permissions::new(persistence.clone()),
+      persistence,
+      watchers: Watchers {
+        crash: Arc::new(CrashWatch::new(docker.clone(), alerts)),
+        idle: Arc::new(IdleMonitor::new(
+          docker,
+          config.docker_hosts.clone(),
+          alerts,
+          config.server_idle_timeout,
+        )),
+        ip: Arc::new(IpWatch::new(public_ip, alerts, config.ip_check_interval)),
+      },
     }
   }
+
+  pub fn watchers(&self) -> Watchers {
+    self.watchers.clone()
+  }
 }
 
 #[async_trait]
@@ -48,6 +161,10 @@ impl AppInteractor for GameServers {
     vec![CreateCommand::new(NAME)
       .description("Binkies trying out IT")
       .kind(CommandType::ChatInput)
+      // A baseline guild admins can tighten under Integrations, the roles set
+      // with /servers permissions then decide who can actually touch servers
+      .default_member_permissions(DiscordPermissions::USE_APPLICATION_COMMANDS)
+      .contexts(vec![InteractionContext::Guild])
       .add_option(CreateCommandOption::new(
         CommandOptionType::SubCommand,
         "ip",
@@ -58,35 +175,102 @@ impl AppInteractor for GameServers {
         "list",
         "Binkies will take a guess at what servers exist",
       ))
+      .add_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "wake",
+        "Binkies will poke the game server awake",
+      ))
+      .add_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "sleep",
+        "Binkies will tuck the game server in, once nobody's playing",
+      ))
       .add_option(
         CreateCommandOption::new(
           CommandOptionType::SubCommand,
-          "stop",
-          "Make Binkies stop a server",
+          "restart",
+          "Make Binkies turn a server off and on again",
+        )
+        .add_sub_option(server_name_option()),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "backup",
+          "Binkies will squirrel away a copy of a server's world",
+        )
+        .add_sub_option(server_name_option()),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "backups",
+          "Binkies will show what copies of a server she has",
+        )
+        .add_sub_option(server_name_option()),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "permissions",
+          "Binkies will say who can manage servers, or change it",
+        )
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::Role,
+          "role",
+          "Role to allow starting, stopping and reading logs",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::Boolean,
+          "remove",
+          "Take the role's access away instead",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::Boolean,
+          "confirm-stop",
+          "Check before stopping or restarting a server with players on",
+        )),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "status",
+          "Binkies will ask a server who's playing",
         )
+        .add_sub_option(server_name_option()),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "logs",
+          "Binkies will read out what a server's been saying",
+        )
+        .add_sub_option(server_name_option())
         .add_sub_option(
           CreateCommandOption::new(
-            CommandOptionType::String,
-            "server-name",
-            "name of server from list command",
+            CommandOptionType::Integer,
+            "lines",
+            format!("How many of the latest lines, {DEFAULT_LINES} by default"),
           )
-          .required(true),
+          .min_int_value(1)
+          .max_int_value(MAX_LINES as u64),
         ),
       )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "stop",
+          "Make Binkies stop a server",
+        )
+        .add_sub_option(server_name_option()),
+      )
       .add_option(
         CreateCommandOption::new(
           CommandOptionType::SubCommand,
           "start",
           "Make Binkies start a server",
         )
-        .add_sub_option(
-          CreateCommandOption::new(
-            CommandOptionType::String,
-            "server-name",
-            "name of server from list command",
-          )
-          .required(true),
-        ),
+        .add_sub_option(server_name_option()),
       )]
   }
 
@@ -111,6 +295,56 @@ impl AppInteractor for GameServers {
       }
     }
   }
+
+  #[instrument(name = "Servers", level = "INFO", skip(self, ctx, itx))]
+  async fn msg_interact(&self, ctx: &Context, itx: &ComponentInteraction) {
+    if let Err(e) = self.stop.msg_interact(ctx, itx).await {
+      error!("{:?}", e);
+    }
+  }
+
+  #[instrument(name = "Servers", level = "INFO", skip(self, ctx, itx))]
+  async fn autocomplete(&self, ctx: &Context, itx: &CommandInteraction) {
+    if !itx.data.name.as_str().eq(NAME) {
+      return;
+    }
+    let Some(typed) = itx.data.autocomplete() else {
+      return;
+    };
+    let Some(subcommand) = itx.data.options().first().map(|o| o.name) else {
+      return;
+    };
+    // Offering nothing beats leaving them hanging if docker's down
+    let summaries = self.docker.list().await.unwrap_or_else(|e| {
+      error!("Failed to list servers for autocomplete {:?}", e);
+      vec![]
+    });
+    let choices = autocomplete::suggestions(&summaries, subcommand, typed.value)
+      .into_iter()
+      .map(|(shown, name)| AutocompleteChoice::new(shown, name))
+      .collect();
+    if let Err(e) = itx
+      .create_response(
+        &ctx.http,
+        CreateInteractionResponse::Autocomplete(
+          CreateAutocompleteResponse::new().set_choices(choices),
+        ),
+      )
+      .await
+    {
+      error!("Failed to send autocomplete suggestions {:?}", e);
+    }
+  }
+}
+
+fn server_name_option() -> CreateCommandOption {
+  CreateCommandOption::new(
+    CommandOptionType::String,
+    "server-name",
+    "name of server from list command",
+  )
+  .required(true)
+  .set_autocomplete(true)
 }
 
 impl GameServers {
@@ -122,15 +356,6 @@ impl GameServers {
     if !itx.data.name.as_str().eq(NAME) {
       return Ok(());
     }
-    itx
-      .create_response(
-        &ctx.http,
-        CreateInteractionResponse::Message(
-          CreateInteractionResponseMessage::new().content("Loading..."),
-        ),
-      )
-      .await?;
-
     // This is a bit annoying of an interface but when we're talking
     // subcommands here the options vec should only ever be 1 long
     // and its gonna have the option on it.
@@ -141,11 +366,44 @@ impl GameServers {
       _ => return Err("Dev error - subopt registered that's not a subcommand".into()),
     };
 
+    if !permitted(&self.persistence, itx, subopt.name) {
+      itx
+        .create_response(
+          &ctx.http,
+          CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+              .content(format!(
+                "You don't have a role that can {} servers, see /servers permissions",
+                subopt.name
+              ))
+              .ephemeral(true),
+          ),
+        )
+        .await?;
+      return Ok(());
+    }
+    itx
+      .create_response(
+        &ctx.http,
+        CreateInteractionResponse::Message(
+          CreateInteractionResponseMessage::new().content("Loading..."),
+        ),
+      )
+      .await?;
+
     match subopt.name {
       "start" => self.start.handle(ctx, itx, &args).await?,
       "stop" => self.stop.handle(ctx, itx, &args).await?,
       "list" => self.list.handle(ctx, itx, &args).await?,
       "ip" => self.ip.handle(ctx, itx, &args).await?,
+      "wake" => self.wake.handle(ctx, itx, &args).await?,
+      "sleep" => self.sleep.handle(ctx, itx, &args).await?,
+      "logs" => self.logs.handle(ctx, itx, &args).await?,
+      "status" => self.status.handle(ctx, itx, &args).await?,
+      "permissions" => self.permissions.handle(ctx, itx, &args).await?,
+      "restart" => self.restart.handle(ctx, itx, &args).await?,
+      "backup" => self.backup.handle(ctx, itx, &args).await?,
+      "backups" => self.backups.handle(ctx, itx, &args).await?,
       _ => unreachable!(),
     };
 
@@ -0,0 +1,197 @@
+use crate::{
+  cmd::{arg_util::Args, SubCommandHandler},
+  persistence::PersistentStore,
+  types::Rol,
+};
+use anyhow::anyhow;
+use bincode::{Decode, Encode};
+use derive_new::new;
+use serenity::{
+  all::{CommandInteraction, GuildId, RoleId},
+  async_trait,
+  builder::EditInteractionResponse,
+  client::Context,
+  utils::MessageBuilder,
+};
+use std::sync::Arc;
+use tracing::error;
+
+/// Subcommands that touch the servers themselves, rather than just looking
+const GUARDED: [&str; 6] = ["start", "stop", "restart", "logs", "sleep", "backup"];
+
+/// Who gets to run the guarded /servers subcommands in a guild. Server managers
+/// always can, and until roles are added they're the only ones.
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct ServerPermissions {
+  pub roles: Vec<Rol>,
+  /// Ask before stopping a server with players still on it
+  pub confirm_stop: bool,
+}
+
+impl Default for ServerPermissions {
+  fn default() -> Self {
+    Self {
+      roles: vec![],
+      confirm_stop: true,
+    }
+  }
+}
+
+impl ServerPermissions {
+  pub fn load(persistence: &PersistentStore, guild_id: GuildId) -> Self {
+    match persistence.server_permissions().load(&guild_id) {
+      Ok(p) => p.unwrap_or_default(),
+      Err(e) => {
+        error!("Failed to load server permissions for {}: {}", guild_id, e);
+        Self::default()
+      }
+    }
+  }
+
+  pub fn save(
+    &self,
+    persistence: &PersistentStore,
+    guild_id: GuildId,
+  ) -> Result<(), anyhow::Error> {
+    persistence.server_permissions().save(&guild_id, self)
+  }
+
+  pub fn allows(&self, subcommand: &str, member_roles: &[RoleId], is_admin: bool) -> bool {
+    !GUARDED.contains(&subcommand)
+      || is_admin
+      || self.roles.iter().any(|r| member_roles.contains(&r.0))
+  }
+
+  fn describe(&self) -> String {
+    let mut msg = MessageBuilder::new();
+    msg.push_bold("Managing servers: ");
+    match self.roles.is_empty() {
+      true => msg.push("server managers only"),
+      false => msg.push(
+        self
+          .roles
+          .iter()
+          .map(|r| r.to_string())
+          .collect::<Vec<_>>()
+          .join(", "),
+      ),
+    };
+    msg
+      .push("\n")
+      .push_bold("Confirm stopping with players on: ")
+      .push(if self.confirm_stop { "yes" } else { "no" })
+      .build()
+  }
+}
+
+fn is_admin(itx: &CommandInteraction) -> bool {
+  itx
+    .member
+    .as_ref()
+    .and_then(|m| m.permissions)
+    .is_some_and(|p| p.manage_guild())
+}
+
+/// Whether whoever ran the command may use that subcommand
+pub fn permitted(
+  persistence: &PersistentStore,
+  itx: &CommandInteraction,
+  subcommand: &str,
+) -> bool {
+  let (Some(guild_id), Some(member)) = (itx.guild_id, itx.member.as_ref()) else {
+    return false;
+  };
+  ServerPermissions::load(persistence, guild_id).allows(subcommand, &member.roles, is_admin(itx))
+}
+
+/// Lets server managers choose who else can run the guarded subcommands
+#[derive(new)]
+pub struct Permissions {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Permissions {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let role = args.opt_role("role")?.map(|r| r.id);
+    let remove = args.opt_bool("remove")?.unwrap_or(false);
+    let confirm = args.opt_bool("confirm-stop")?;
+
+    let mut perms = ServerPermissions::load(&self.persistence, guild_id);
+    let changing = role.is_some() || confirm.is_some();
+    let msg = match (changing, is_admin(itx)) {
+      (true, false) => "Only server managers can change who manages servers".to_string(),
+      (true, true) => {
+        if let Some(role) = role {
+          perms.roles.retain(|r| **r != role);
+          if !remove {
+            perms.roles.push(Rol(role));
+          }
+        }
+        if let Some(confirm) = confirm {
+          perms.confirm_stop = confirm;
+        }
+        perms.save(&self.persistence, guild_id)?;
+        perms.describe()
+      }
+      (false, _) => perms.describe(),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+      .await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+  use test_case::test_case;
+
+  const MOD: RoleId = RoleId::new(1);
+  const PLAYER: RoleId = RoleId::new(2);
+
+  #[test_case("list", &[], false => true; "looking is free")]
+  #[test_case("stop", &[], false => false; "stop needs a role")]
+  #[test_case("stop", &[], true => true; "managers can always")]
+  #[test_case("stop", &[PLAYER], false => false; "wrong role")]
+  #[test_case("logs", &[PLAYER, MOD], false => true; "right role")]
+  fn guards_subcommands(subcommand: &str, roles: &[RoleId], admin: bool) -> bool {
+    let perms = ServerPermissions {
+      roles: vec![Rol(MOD)],
+      confirm_stop: true,
+    };
+    perms.allows(subcommand, roles, admin)
+  }
+
+  #[test]
+  fn permissions_persist_per_guild() {
+    let temp_dir = tempdir().unwrap();
+    let store = PersistentStore::new(temp_dir.path().join("test.db")).unwrap();
+    let guild_id = GuildId::new(111111111);
+
+    assert!(ServerPermissions::load(&store, guild_id).roles.is_empty());
+    let perms = ServerPermissions {
+      roles: vec![Rol(MOD)],
+      confirm_stop: false,
+    };
+    perms.save(&store, guild_id).unwrap();
+
+    let loaded = ServerPermissions::load(&store, guild_id);
+    assert_eq!(
+      vec![MOD],
+      loaded.roles.iter().map(|r| r.0).collect::<Vec<_>>()
+    );
+    assert!(!loaded.confirm_stop);
+    assert!(ServerPermissions::load(&store, GuildId::new(2)).confirm_stop);
+  }
+}
@@ -0,0 +1,266 @@
+use crate::{
+  cmd::{arg_util::Args, SubCommandHandler},
+  docker::DockerClient,
+};
+use anyhow::anyhow;
+use bollard::service::ContainerSummaryStateEnum;
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{net::UdpSocket, process::Command, time::sleep};
+use tracing::{info, warn};
+
+use super::list::extract_name;
+
+/// Wake-on-LAN listens on the discard port by convention
+const WOL_ADDR: &str = "255.255.255.255:9";
+/// How long the host gets to boot before we stop checking on it
+const WAKE_TIMEOUT: Duration = Duration::from_secs(180);
+const WAKE_POLL: Duration = Duration::from_secs(15);
+
+/// Builds a Wake-on-LAN magic packet: 6 bytes of 0xFF then the MAC 16 times over
+pub fn magic_packet(mac: &str) -> Result<[u8; 102], anyhow::Error> {
+  let octets: Vec<u8> = mac
+    .split([':', '-'])
+    .map(|o| match o.len() {
+      2 => u8::from_str_radix(o, 16).ok(),
+      _ => None,
+    })
+    .collect::<Option<_>>()
+    .filter(|o: &Vec<u8>| o.len() == 6)
+    .ok_or_else(|| anyhow!("{mac} isn't a MAC address"))?;
+  let mut packet = [0xFF; 102];
+  for chunk in packet[6..].chunks_exact_mut(6) {
+    chunk.copy_from_slice(&octets);
+  }
+  Ok(packet)
+}
+
+async fn send_magic_packet(mac: &str) -> Result<(), anyhow::Error> {
+  let packet = magic_packet(mac)?;
+  let socket = UdpSocket::bind("0.0.0.0:0").await?;
+  socket.set_broadcast(true)?;
+  socket.send_to(&packet, WOL_ADDR).await?;
+  Ok(())
+}
+
+/// Runs commands on the game server host
+#[async_trait]
+pub trait RemoteShell: Send + Sync {
+  async fn run(&self, command: &str) -> Result<String, anyhow::Error>;
+
+  /// Whether there's anywhere to run commands at all
+  fn configured(&self) -> bool {
+    true
+  }
+}
+
+/// Shells out to the system's ssh, which needs key based auth set up for the user
+#[derive(new)]
+pub struct Ssh {
+  user: String,
+  host: String,
+}
+
+#[async_trait]
+impl RemoteShell for Ssh {
+  async fn run(&self, command: &str) -> Result<String, anyhow::Error> {
+    if !self.configured() {
+      return Err(anyhow!("server_user and server_host need configuring"));
+    }
+    let output = Command::new("ssh")
+      // Never sit waiting on a password prompt nobody will answer
+      .args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"])
+      .arg(format!("{}@{}", self.user, self.host))
+      .arg(command)
+      .output()
+      .await?;
+    match output.status.success() {
+      true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+      false => Err(anyhow!(
+        "ssh failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+      )),
+    }
+  }
+
+  fn configured(&self) -> bool {
+    !self.user.is_empty() && !self.host.is_empty()
+  }
+}
+
+#[derive(new)]
+pub struct Wake {
+  mac: String,
+  shell: Arc<dyn RemoteShell>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Wake {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    _args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let update = |msg: String| async move {
+      itx
+        .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+        .await
+    };
+    let polling = self.shell.configured();
+    if polling && self.shell.run("true").await.is_ok() {
+      update("Server's already awake".to_string()).await?;
+      return Ok(());
+    }
+
+    let mac = &self.mac;
+    if let Err(e) = send_magic_packet(mac).await {
+      update(format!("Couldn't send the wake up call: {e}")).await?;
+      return Ok(());
+    }
+    info!("Sent magic packet to {}", mac);
+    // Without ssh there's no asking the server whether it's up yet
+    if !polling {
+      update("Wake up call sent".to_string()).await?;
+      return Ok(());
+    }
+    update("Wake up call sent, waiting on the server...".to_string()).await?;
+
+    // Boots take a while, keep checking until it answers or we give up
+    let mut waited = Duration::ZERO;
+    while waited < WAKE_TIMEOUT {
+      sleep(WAKE_POLL).await;
+      waited += WAKE_POLL;
+      if self.shell.run("true").await.is_ok() {
+        update(format!("Server's awake after {}s", waited.as_secs())).await?;
+        return Ok(());
+      }
+    }
+    warn!("Server didn't wake within {:?}", WAKE_TIMEOUT);
+    update(format!(
+      "No sign of the server after {}s, it may need a kick",
+      WAKE_TIMEOUT.as_secs()
+    ))
+    .await?;
+    Ok(())
+  }
+}
+
+#[derive(new)]
+pub struct Sleep {
+  docker: Arc<Box<dyn DockerClient>>,
+  shell: Arc<dyn RemoteShell>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Sleep {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    _args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let msg = match put_to_sleep(&**self.docker, self.shell.as_ref()).await {
+      Ok(_) => "Server's going to sleep".to_string(),
+      Err(e) => format!("{e}"),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+      .await?;
+    Ok(())
+  }
+}
+
+/// Shuts the host down, so long as nobody's still playing on it
+async fn put_to_sleep(
+  docker: &dyn DockerClient,
+  shell: &dyn RemoteShell,
+) -> Result<(), anyhow::Error> {
+  let running: Vec<_> = docker
+    .list()
+    .await?
+    .into_iter()
+    .filter(|c| c.state == Some(ContainerSummaryStateEnum::RUNNING))
+    .collect();
+  if !running.is_empty() {
+    let names: Vec<_> = running.iter().map(extract_name).collect();
+    return Err(anyhow!(
+      "Not while servers are running: {}",
+      names.join(", ")
+    ));
+  }
+  info!("Shutting down the server host");
+  shell.run("sudo shutdown -h now").await?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::docker::fake::FakeDocker;
+  use std::sync::Mutex;
+  use test_case::test_case;
+
+  #[test]
+  fn magic_packet_repeats_mac() {
+    let packet = magic_packet("01:23:45:67:89:ab").unwrap();
+    assert_eq!([0xFF; 6], packet[..6]);
+    for copy in packet[6..].chunks(6) {
+      assert_eq!([0x01, 0x23, 0x45, 0x67, 0x89, 0xAB], copy);
+    }
+  }
+
+  #[test_case("01-23-45-67-89-AB" => true; "dashes")]
+  #[test_case("01:23:45:67:89" => false; "too short")]
+  #[test_case("01:23:45:67:89:ab:cd" => false; "too long")]
+  #[test_case("01:23:45:67:89:zz" => false; "not hex")]
+  #[test_case("0123:45:67:89:ab" => false; "run together")]
+  #[test_case("" => false; "unset")]
+  fn magic_packet_needs_a_mac(mac: &str) -> bool {
+    magic_packet(mac).is_ok()
+  }
+
+  #[test_case("binkies", "gameserver" => true; "both set")]
+  #[test_case("", "gameserver" => false; "no user")]
+  #[test_case("binkies", "" => false; "no host")]
+  fn ssh_needs_user_and_host(user: &str, host: &str) -> bool {
+    Ssh::new(user.to_string(), host.to_string()).configured()
+  }
+
+  #[derive(Default)]
+  struct FakeShell(Mutex<Vec<String>>);
+
+  #[async_trait]
+  impl RemoteShell for FakeShell {
+    async fn run(&self, command: &str) -> Result<String, anyhow::Error> {
+      self.0.lock().unwrap().push(command.to_string());
+      Ok(String::new())
+    }
+  }
+
+  #[tokio::test]
+  async fn sleeps_once_servers_stopped() {
+    let shell = FakeShell::default();
+    let docker = FakeDocker::new(&[
+      ("server0", ContainerSummaryStateEnum::EXITED),
+      ("server1", ContainerSummaryStateEnum::CREATED),
+    ]);
+    put_to_sleep(&docker, &shell).await.unwrap();
+    assert_eq!(vec!["sudo shutdown -h now"], *shell.0.lock().unwrap());
+  }
+
+  #[tokio::test]
+  async fn stays_up_while_servers_run() {
+    let shell = FakeShell::default();
+    let docker = FakeDocker::new(&[
+      ("server0", ContainerSummaryStateEnum::EXITED),
+      ("server1", ContainerSummaryStateEnum::RUNNING),
+    ]);
+    let err = put_to_sleep(&docker, &shell).await.unwrap_err();
+    assert_eq!("Not while servers are running: server1", err.to_string());
+    assert!(shell.0.lock().unwrap().is_empty());
+  }
+}
@@ -0,0 +1,394 @@
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::{future::Future, time::Duration};
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::{TcpStream, UdpSocket},
+  time::timeout,
+};
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Sent in place of a real protocol version, which servers accept for pings
+const SLP_ANY_VERSION: i32 = -1;
+/// Longest status the protocol allows, anything claiming more is garbage
+const SLP_MAX_STATUS: i32 = 32 * 1024;
+const A2S_HEADER: [u8; 4] = [0xFF; 4];
+const A2S_INFO: &[u8] = b"TSource Engine Query\0";
+const A2S_CHALLENGE: u8 = 0x41;
+const A2S_INFO_REPLY: u8 = 0x49;
+// Plenty for an info reply, which Steam keeps to a single packet
+const A2S_MAX_PACKET: usize = 1400;
+
+/// What the game server says about itself
+#[derive(Debug, PartialEq)]
+pub struct ServerInfo {
+  pub online: u32,
+  pub max: u32,
+  pub version: String,
+  pub motd: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Protocol {
+  /// Minecraft's Server List Ping, on the game port
+  Slp,
+  /// Steam's A2S_INFO, on the port after the game port
+  A2s,
+}
+
+impl Protocol {
+  /// Picks the protocol from a container's `game` label
+  pub fn for_game(game: &str) -> Option<Protocol> {
+    match game.to_lowercase().as_str() {
+      "minecraft" => Some(Protocol::Slp),
+      "valheim" => Some(Protocol::A2s),
+      _ => None,
+    }
+  }
+
+  pub fn query_port(&self, game_port: u16) -> u16 {
+    match self {
+      Protocol::Slp => game_port,
+      Protocol::A2s => game_port + 1,
+    }
+  }
+
+  pub async fn query(&self, host: &str, port: u16) -> Result<ServerInfo, anyhow::Error> {
+    match self {
+      Protocol::Slp => within(slp(host, port)).await,
+      Protocol::A2s => within(a2s(host, port)).await,
+    }
+  }
+}
+
+async fn within<F: Future<Output = Result<ServerInfo, anyhow::Error>>>(
+  query: F,
+) -> Result<ServerInfo, anyhow::Error> {
+  timeout(QUERY_TIMEOUT, query)
+    .await
+    .map_err(|_| anyhow!("Server didn't answer within {:?}", QUERY_TIMEOUT))?
+}
+
+#[derive(Deserialize)]
+struct SlpStatus {
+  version: SlpVersion,
+  players: SlpPlayers,
+  #[serde(default)]
+  description: Chat,
+}
+
+#[derive(Deserialize)]
+struct SlpVersion {
+  name: String,
+}
+
+#[derive(Deserialize)]
+struct SlpPlayers {
+  online: u32,
+  max: u32,
+}
+
+/// Minecraft's text, either plain or a component with children
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Chat {
+  Plain(String),
+  Component {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    extra: Vec<Chat>,
+  },
+}
+
+impl Default for Chat {
+  fn default() -> Self {
+    Chat::Plain(String::new())
+  }
+}
+
+impl Chat {
+  fn flatten(&self, out: &mut String) {
+    match self {
+      Chat::Plain(s) => out.push_str(s),
+      Chat::Component { text, extra } => {
+        out.push_str(text);
+        extra.iter().for_each(|e| e.flatten(out));
+      }
+    }
+  }
+}
+
+/// Drops the `§` colour and formatting codes older servers still put in text
+fn strip_formatting(s: &str) -> String {
+  let mut out = String::new();
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '§' => {
+        chars.next();
+      }
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+  let mut value = value as u32;
+  loop {
+    let byte = (value & 0x7F) as u8;
+    value >>= 7;
+    match value {
+      0 => return buf.push(byte),
+      _ => buf.push(byte | 0x80),
+    }
+  }
+}
+
+async fn read_varint(stream: &mut TcpStream) -> Result<i32, anyhow::Error> {
+  let mut value = 0u32;
+  for i in 0..5 {
+    let byte = stream.read_u8().await?;
+    value |= ((byte & 0x7F) as u32) << (7 * i);
+    if byte & 0x80 == 0 {
+      return Ok(value as i32);
+    }
+  }
+  Err(anyhow!("VarInt too long"))
+}
+
+fn packet(id: i32, body: &[u8]) -> Vec<u8> {
+  let mut inner = vec![];
+  write_varint(&mut inner, id);
+  inner.extend_from_slice(body);
+  let mut framed = vec![];
+  write_varint(&mut framed, inner.len() as i32);
+  framed.extend(inner);
+  framed
+}
+
+async fn slp(host: &str, port: u16) -> Result<ServerInfo, anyhow::Error> {
+  let mut stream = TcpStream::connect((host, port)).await?;
+
+  let mut handshake = vec![];
+  write_varint(&mut handshake, SLP_ANY_VERSION);
+  write_varint(&mut handshake, host.len() as i32);
+  handshake.extend_from_slice(host.as_bytes());
+  handshake.extend_from_slice(&port.to_be_bytes());
+  // Next state: status
+  write_varint(&mut handshake, 1);
+  stream.write_all(&packet(0x00, &handshake)).await?;
+  stream.write_all(&packet(0x00, &[])).await?;
+
+  let _length = read_varint(&mut stream).await?;
+  let id = read_varint(&mut stream).await?;
+  if id != 0x00 {
+    return Err(anyhow!("Unexpected status packet {id}"));
+  }
+  let json_len = read_varint(&mut stream).await?;
+  if !(0..=SLP_MAX_STATUS).contains(&json_len) {
+    return Err(anyhow!("Implausible status length {json_len}"));
+  }
+  let mut json = vec![0; json_len as usize];
+  stream.read_exact(&mut json).await?;
+
+  let status: SlpStatus =
+    serde_json::from_slice(&json).map_err(|e| anyhow!("Unreadable server status").context(e))?;
+  let mut motd = String::new();
+  status.description.flatten(&mut motd);
+  Ok(ServerInfo {
+    online: status.players.online,
+    max: status.players.max,
+    version: strip_formatting(&status.version.name),
+    motd: strip_formatting(&motd),
+  })
+}
+
+async fn a2s(host: &str, port: u16) -> Result<ServerInfo, anyhow::Error> {
+  let socket = UdpSocket::bind("0.0.0.0:0").await?;
+  socket.connect((host, port)).await?;
+  let request = [&A2S_HEADER[..], A2S_INFO].concat();
+  socket.send(&request).await?;
+
+  let mut buf = [0u8; A2S_MAX_PACKET];
+  let mut len = socket.recv(&mut buf).await?;
+  // Newer servers want their challenge echoed back before they'll say anything
+  if len >= 9 && buf[4] == A2S_CHALLENGE {
+    let challenged = [&request[..], &buf[5..9]].concat();
+    socket.send(&challenged).await?;
+    len = socket.recv(&mut buf).await?;
+  }
+  parse_a2s_info(&buf[..len])
+}
+
+fn parse_a2s_info(packet: &[u8]) -> Result<ServerInfo, anyhow::Error> {
+  let body = packet
+    .strip_prefix(&A2S_HEADER)
+    .and_then(|p| p.strip_prefix(&[A2S_INFO_REPLY]))
+    .ok_or_else(|| anyhow!("Not an A2S_INFO reply"))?;
+  // Skip the protocol version
+  let mut rest = body.get(1..).ok_or_else(cut_short)?;
+  let name = take_cstr(&mut rest)?;
+  // Map, folder and game
+  for _ in 0..3 {
+    take_cstr(&mut rest)?;
+  }
+  // App id (2), players, max players, bots, server type, environment, visibility, VAC
+  let fixed = rest.get(..9).ok_or_else(cut_short)?;
+  let (online, max) = (fixed[2], fixed[3]);
+  rest = &rest[9..];
+  Ok(ServerInfo {
+    online: online as u32,
+    max: max as u32,
+    version: take_cstr(&mut rest)?,
+    motd: name,
+  })
+}
+
+fn take_cstr(rest: &mut &[u8]) -> Result<String, anyhow::Error> {
+  let end = rest.iter().position(|b| *b == 0).ok_or_else(cut_short)?;
+  let s = String::from_utf8_lossy(&rest[..end]).to_string();
+  *rest = &rest[end + 1..];
+  Ok(s)
+}
+
+fn cut_short() -> anyhow::Error {
+  anyhow!("A2S_INFO reply cut short")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+  use tokio::net::TcpListener;
+
+  #[test_case(0 => vec![0x00]; "zero")]
+  #[test_case(300 => vec![0xAC, 0x02]; "two bytes")]
+  #[test_case(-1 => vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]; "negative")]
+  fn varints(value: i32) -> Vec<u8> {
+    let mut buf = vec![];
+    write_varint(&mut buf, value);
+    buf
+  }
+
+  #[test_case("minecraft" => Some(Protocol::Slp); "minecraft")]
+  #[test_case("Valheim" => Some(Protocol::A2s); "valheim")]
+  #[test_case("factorio" => None; "unknown")]
+  fn protocol_from_game(game: &str) -> Option<Protocol> {
+    Protocol::for_game(game)
+  }
+
+  /// Answers one status ping the way a minecraft server would
+  async fn fake_minecraft(status: &'static str) -> u16 {
+    fake_minecraft_claiming(status.len() as i32, status).await
+  }
+
+  /// Answers with whatever status length it's told to, true or not
+  async fn fake_minecraft_claiming(len: i32, status: &'static str) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+      let (mut stream, _) = listener.accept().await.unwrap();
+      // Handshake then status request, each length prefixed
+      for _ in 0..2 {
+        let len = read_varint(&mut stream).await.unwrap();
+        let mut skip = vec![0; len as usize];
+        stream.read_exact(&mut skip).await.unwrap();
+      }
+      let mut body = vec![];
+      write_varint(&mut body, len);
+      body.extend_from_slice(status.as_bytes());
+      stream.write_all(&packet(0x00, &body)).await.unwrap();
+    });
+    port
+  }
+
+  #[tokio::test]
+  async fn pings_minecraft() {
+    let port = fake_minecraft(
+      r#"{
+        "version": {"name": "1.16.5", "protocol": 754},
+        "players": {"max": 20, "online": 3},
+        "description": {"text": "§aValhelsia ", "extra": [{"text": "3"}]}
+      }"#,
+    )
+    .await;
+    let info = Protocol::Slp.query("127.0.0.1", port).await.unwrap();
+    assert_eq!(
+      ServerInfo {
+        online: 3,
+        max: 20,
+        version: "1.16.5".to_string(),
+        motd: "Valhelsia 3".to_string(),
+      },
+      info
+    );
+  }
+
+  #[tokio::test]
+  async fn pings_old_minecraft_motd() {
+    let port = fake_minecraft(
+      r#"{"version": {"name": "1.12.2"}, "players": {"max": 10, "online": 0}, "description": "Hi"}"#,
+    )
+    .await;
+    let info = Protocol::Slp.query("127.0.0.1", port).await.unwrap();
+    assert_eq!("Hi", info.motd);
+  }
+
+  #[tokio::test]
+  async fn rejects_implausible_status_lengths() {
+    for len in [-1, SLP_MAX_STATUS + 1, i32::MAX] {
+      let port = fake_minecraft_claiming(len, "{}").await;
+      let err = Protocol::Slp.query("127.0.0.1", port).await.unwrap_err();
+      assert_eq!(format!("Implausible status length {len}"), err.to_string());
+    }
+  }
+
+  /// Answers A2S_INFO like a Steam server, challenging the first request
+  async fn fake_steam() -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+    tokio::spawn(async move {
+      let mut buf = [0u8; A2S_MAX_PACKET];
+      let (_, from) = socket.recv_from(&mut buf).await.unwrap();
+      socket
+        .send_to(&[0xFF, 0xFF, 0xFF, 0xFF, A2S_CHALLENGE, 1, 2, 3, 4], from)
+        .await
+        .unwrap();
+      let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+      assert_eq!(&[1, 2, 3, 4], &buf[len - 4..len]);
+
+      let mut reply = vec![0xFF, 0xFF, 0xFF, 0xFF, A2S_INFO_REPLY, 17];
+      for s in ["Vikings Only", "Midgard", "valheim", "Valheim"] {
+        reply.extend_from_slice(s.as_bytes());
+        reply.push(0);
+      }
+      reply.extend_from_slice(&[0x00, 0x00, 2, 10, 0, b'd', b'l', 0, 1]);
+      reply.extend_from_slice(b"0.217.46\0");
+      socket.send_to(&reply, from).await.unwrap();
+    });
+    port
+  }
+
+  #[tokio::test]
+  async fn queries_steam() {
+    let port = fake_steam().await;
+    let info = Protocol::A2s.query("127.0.0.1", port).await.unwrap();
+    assert_eq!(
+      ServerInfo {
+        online: 2,
+        max: 10,
+        version: "0.217.46".to_string(),
+        motd: "Vikings Only".to_string(),
+      },
+      info
+    );
+  }
+
+  #[test]
+  fn rejects_truncated_steam_reply() {
+    assert!(parse_a2s_info(&[0xFF, 0xFF, 0xFF, 0xFF, A2S_INFO_REPLY, 17, b'a']).is_err());
+    assert!(parse_a2s_info(b"nonsense").is_err());
+  }
+}
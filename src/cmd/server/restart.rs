@@ -0,0 +1,75 @@
+use super::{ip::PublicIp, startup, stop::players_on};
+use crate::{
+  cmd::{arg_util::Args, SubCommandHandler},
+  config::DockerHost,
+  docker::DockerClient,
+  persistence::PersistentStore,
+};
+use anyhow::anyhow;
+use chrono::Utc;
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+};
+use std::{sync::Arc, time::Duration};
+
+async fn send_response(
+  ctx: &Context,
+  itx: &CommandInteraction,
+  message: String,
+) -> Result<(), anyhow::Error> {
+  itx
+    .edit_response(&ctx.http, EditInteractionResponse::new().content(message))
+    .await?;
+  Ok(())
+}
+
+#[derive(new)]
+pub struct Restart {
+  docker: Arc<Box<dyn DockerClient>>,
+  hosts: Vec<DockerHost>,
+  persistence: Arc<PersistentStore>,
+  public_ip: Arc<PublicIp>,
+  timeout: Duration,
+}
+
+#[async_trait]
+impl SubCommandHandler for Restart {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let name = args
+      .str("server-name")
+      .map_err(|e| anyhow!("Must provide a server name").context(e))?;
+
+    let online = players_on(&**self.docker, &self.hosts, &self.persistence, itx, name).await;
+    if online > 0 {
+      let msg = format!("{name} has {online} playing, restart it once they're off");
+      return send_response(ctx, itx, msg).await;
+    }
+
+    send_response(ctx, itx, format!("Restarting {name}...")).await?;
+    let since = Utc::now().timestamp();
+    if let Err(e) = self.docker.restart(name).await {
+      return send_response(ctx, itx, format!("{e}")).await;
+    }
+
+    let outcome = startup::watch(
+      ctx,
+      itx,
+      &**self.docker,
+      &self.public_ip,
+      name,
+      since,
+      self.timeout,
+    )
+    .await;
+    match outcome {
+      Ok(msg) => send_response(ctx, itx, msg).await,
+      Err(e) => send_response(ctx, itx, format!("Lost track of {name}: {e}")).await,
+    }
+  }
+}
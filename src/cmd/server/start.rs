@@ -1,14 +1,16 @@
+use super::{ip::PublicIp, startup};
 use crate::{
   cmd::{arg_util::Args, SubCommandHandler},
   docker::DockerClient,
 };
 use anyhow::anyhow;
 use bollard::service::ContainerStateStatusEnum::{CREATED, EXITED};
+use chrono::Utc;
 use derive_new::new;
 use serenity::{
   all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 // Helper function to send error response
 async fn send_error_response(
@@ -25,6 +27,8 @@ async fn send_error_response(
 #[derive(new)]
 pub struct Start {
   docker: Arc<Box<dyn DockerClient>>,
+  public_ip: Arc<PublicIp>,
+  timeout: Duration,
 }
 
 #[async_trait]
@@ -35,7 +39,6 @@ impl SubCommandHandler for Start {
     itx: &CommandInteraction,
     args: &Args,
   ) -> Result<(), anyhow::Error> {
-    // TODO: Let's move to autocomplete on these
     let name = args
       .str("server-name")
       .map_err(|e| anyhow!("Must provide a server name").context(e))?;
@@ -55,9 +58,25 @@ impl SubCommandHandler for Start {
       }
     }
 
-    match self.docker.start(name).await {
-      Ok(_) => send_error_response(ctx, itx, "Server starting".to_string()).await,
-      Err(e) => send_error_response(ctx, itx, format!("{e}")).await,
+    let since = Utc::now().timestamp();
+    if let Err(e) = self.docker.start(name).await {
+      return send_error_response(ctx, itx, format!("{e}")).await;
+    }
+    send_error_response(ctx, itx, "Server starting".to_string()).await?;
+
+    let outcome = startup::watch(
+      ctx,
+      itx,
+      &**self.docker,
+      &self.public_ip,
+      name,
+      since,
+      self.timeout,
+    )
+    .await;
+    match outcome {
+      Ok(msg) => send_error_response(ctx, itx, msg).await,
+      Err(e) => send_error_response(ctx, itx, format!("Lost track of {name}: {e}")).await,
     }
   }
 }
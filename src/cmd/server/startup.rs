@@ -0,0 +1,209 @@
+use super::ip::PublicIp;
+use crate::docker::{DockerClient, LogRange, READY_LABEL};
+use bollard::service::{
+  ContainerInspectResponse, ContainerState,
+  ContainerStateStatusEnum::{DEAD, EXITED, RUNNING},
+  HealthStatusEnum,
+};
+use chrono::Utc;
+use regex::Regex;
+use serenity::{all::CommandInteraction, builder::EditInteractionResponse, client::Context};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+const POLL: Duration = Duration::from_secs(5);
+// Log lines can be enormous stack traces, only a taste is needed for progress
+const LINE_MAX: usize = 200;
+
+#[derive(Debug, PartialEq)]
+pub enum Progress {
+  /// Still coming up, with the latest thing it logged if we're watching logs
+  Starting(Option<String>),
+  Ready,
+  Failed(String),
+}
+
+/// Decides how far along the server is from its container state and whatever it
+/// logged since the last look. Without a ready pattern or a healthcheck there's
+/// nothing more to go on than it running.
+pub fn progress(state: &ContainerState, ready: Option<&Regex>, lines: &[String]) -> Progress {
+  if let Some(EXITED | DEAD) = state.status {
+    return Progress::Failed(format!(
+      "Server exited while starting (code {})",
+      state.exit_code.unwrap_or_default()
+    ));
+  }
+  let health = state
+    .health
+    .as_ref()
+    .and_then(|h| h.status)
+    .filter(|h| !matches!(h, HealthStatusEnum::EMPTY | HealthStatusEnum::NONE));
+  if health == Some(HealthStatusEnum::UNHEALTHY) {
+    return Progress::Failed("Server reports itself unhealthy".to_string());
+  }
+  let logged_ready = ready.is_some_and(|r| lines.iter().any(|l| r.is_match(l)));
+  let running_is_enough = ready.is_none() && health.is_none() && state.status == Some(RUNNING);
+  if logged_ready || health == Some(HealthStatusEnum::HEALTHY) || running_is_enough {
+    return Progress::Ready;
+  }
+  Progress::Starting(lines.iter().rev().find(|l| !l.trim().is_empty()).cloned())
+}
+
+/// The lowest host port the container publishes, which is the game's for every
+/// server we run so far
+pub fn published_port(container: &ContainerInspectResponse) -> Option<u16> {
+  container
+    .network_settings
+    .as_ref()
+    .and_then(|n| n.ports.as_ref())
+    .into_iter()
+    .flat_map(|ports| ports.values().flatten().flatten())
+    .filter_map(|b| b.host_port.as_ref()?.parse::<u16>().ok())
+    .min()
+}
+
+fn ready_pattern(container: &ContainerInspectResponse, name: &str) -> Option<Regex> {
+  let pattern = container
+    .config
+    .as_ref()
+    .and_then(|c| c.labels.as_ref())
+    .and_then(|l| l.get(READY_LABEL))?;
+  Regex::new(pattern)
+    .inspect_err(|e| warn!("Ignoring bad ready pattern on {}: {}", name, e))
+    .ok()
+}
+
+/// Keeps the interaction updated while a freshly started server comes up,
+/// returning the final word on how it went
+pub async fn watch(
+  ctx: &Context,
+  itx: &CommandInteraction,
+  docker: &dyn DockerClient,
+  public_ip: &PublicIp,
+  name: &str,
+  since: i64,
+  timeout: Duration,
+) -> Result<String, anyhow::Error> {
+  let container = docker.inspect(name).await?;
+  let ready = ready_pattern(&container, name);
+  let started = Instant::now();
+  let mut since = since;
+  let mut latest = None;
+  while started.elapsed() < timeout {
+    sleep(POLL).await;
+    let state = docker.inspect(name).await?.state.unwrap_or_default();
+    let lines = match ready {
+      Some(_) => docker.logs(name, LogRange::Since(since)).await?,
+      None => vec![],
+    };
+    // Docker's since is inclusive and only to the second, overlap beats a gap
+    since = Utc::now().timestamp() - 1;
+
+    match progress(&state, ready.as_ref(), &lines) {
+      Progress::Ready => {
+        info!("{} ready after {:?}", name, started.elapsed());
+        let at = match (public_ip.get().await, published_port(&container)) {
+          (Some(ip), Some(port)) => format!(" at {ip}:{port}"),
+          (Some(ip), None) => format!(" at {ip}"),
+          _ => String::new(),
+        };
+        return Ok(format!("{name} ready{at}"));
+      }
+      Progress::Failed(why) => {
+        warn!("{} failed to start: {}", name, why);
+        return Ok(match latest {
+          Some(line) => format!("{why}, last words:\n```{line}```"),
+          None => why,
+        });
+      }
+      Progress::Starting(line) => {
+        if let Some(line) = line {
+          latest = Some(line.chars().take(LINE_MAX).collect::<String>());
+        }
+        let mut msg = format!("Starting {name}... {}s", started.elapsed().as_secs());
+        if let Some(line) = &latest {
+          msg.push_str(&format!("\n```{line}```"));
+        }
+        itx
+          .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+          .await?;
+      }
+    }
+  }
+  warn!("{} not ready within {:?}", name, timeout);
+  Ok(format!(
+    "{name} still isn't ready after {}s, it may need looking at",
+    timeout.as_secs()
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bollard::service::{ContainerStateStatusEnum, Health, NetworkSettings, PortBinding};
+  use std::collections::HashMap;
+  use test_case::test_case;
+
+  fn state(status: ContainerStateStatusEnum, health: Option<HealthStatusEnum>) -> ContainerState {
+    ContainerState {
+      status: Some(status),
+      exit_code: Some(1),
+      health: health.map(|h| Health {
+        status: Some(h),
+        ..Default::default()
+      }),
+      ..Default::default()
+    }
+  }
+
+  fn lines(l: &[&str]) -> Vec<String> {
+    l.iter().map(|s| s.to_string()).collect()
+  }
+
+  #[test_case(RUNNING, None, false, &[] => Progress::Ready; "running is all we know")]
+  #[test_case(RUNNING, None, true, &["Preparing spawn area: 42%"] => Progress::Starting(Some("Preparing spawn area: 42%".to_string())); "still loading")]
+  #[test_case(RUNNING, None, true, &["Loading", "Done (41.2s)! For help, type \"help\""] => Progress::Ready; "logged ready")]
+  #[test_case(RUNNING, None, true, &[] => Progress::Starting(None); "quiet")]
+  #[test_case(RUNNING, Some(HealthStatusEnum::STARTING), false, &[] => Progress::Starting(None); "health starting")]
+  #[test_case(RUNNING, Some(HealthStatusEnum::HEALTHY), true, &[] => Progress::Ready; "healthy before logging")]
+  #[test_case(RUNNING, Some(HealthStatusEnum::NONE), false, &[] => Progress::Ready; "no healthcheck")]
+  #[test_case(RUNNING, Some(HealthStatusEnum::UNHEALTHY), false, &[] => Progress::Failed("Server reports itself unhealthy".to_string()); "unhealthy")]
+  #[test_case(EXITED, None, true, &["Done ("] => Progress::Failed("Server exited while starting (code 1)".to_string()); "exited")]
+  fn tracks_startup(
+    status: ContainerStateStatusEnum,
+    health: Option<HealthStatusEnum>,
+    watch_logs: bool,
+    logged: &[&str],
+  ) -> Progress {
+    let ready = Regex::new(r"Done \(").unwrap();
+    progress(
+      &state(status, health),
+      watch_logs.then_some(&ready),
+      &lines(logged),
+    )
+  }
+
+  #[test]
+  fn finds_game_port() {
+    let binding = |port: &str| {
+      Some(vec![PortBinding {
+        host_ip: Some("0.0.0.0".to_string()),
+        host_port: Some(port.to_string()),
+      }])
+    };
+    let container = ContainerInspectResponse {
+      network_settings: Some(NetworkSettings {
+        ports: Some(HashMap::from([
+          ("25575/tcp".to_string(), binding("25575")),
+          ("25565/tcp".to_string(), binding("25565")),
+          ("8080/tcp".to_string(), None),
+        ])),
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+    assert_eq!(Some(25565), published_port(&container));
+    assert_eq!(None, published_port(&ContainerInspectResponse::default()));
+  }
+}
@@ -0,0 +1,145 @@
+use super::{
+  query::{Protocol, ServerInfo},
+  startup::published_port,
+};
+use crate::{
+  cmd::{arg_util::Args, SubCommandHandler},
+  config::DockerHost,
+  docker::{DockerClient, GAME_LABEL},
+};
+use anyhow::anyhow;
+use bollard::service::ContainerStateStatusEnum::RUNNING;
+use derive_new::new;
+use reqwest::Url;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  utils::MessageBuilder,
+};
+use std::sync::Arc;
+
+#[derive(new)]
+pub struct Status {
+  docker: Arc<Box<dyn DockerClient>>,
+  hosts: Vec<DockerHost>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Status {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let name = args
+      .str("server-name")
+      .map_err(|e| anyhow!("Must provide a server name").context(e))?;
+    let msg = match self.status(name).await {
+      Ok(msg) => msg,
+      Err(e) => format!("{e}"),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+      .await?;
+    Ok(())
+  }
+}
+
+impl Status {
+  async fn status(&self, name: &str) -> Result<String, anyhow::Error> {
+    let (game, info) = query_game(&**self.docker, &self.hosts, name).await?;
+    let mut msg = MessageBuilder::new();
+    msg
+      .push_bold(name)
+      .push(format!(" ({game} {})\n", info.version))
+      .push(format!("Players: {}/{}", info.online, info.max));
+    if !info.motd.trim().is_empty() {
+      msg.push("\n").push_mono(info.motd.trim());
+    }
+    Ok(msg.build())
+  }
+}
+
+/// Asks the game itself how it's doing, docker only knows it's running.
+/// Comes back with the game it asked, along with the answer.
+pub async fn query_game(
+  docker: &dyn DockerClient,
+  hosts: &[DockerHost],
+  name: &str,
+) -> Result<(String, ServerInfo), anyhow::Error> {
+  let container = docker.inspect(name).await?;
+  if container.state.as_ref().and_then(|s| s.status) != Some(RUNNING) {
+    return Err(anyhow!("{name} isn't running"));
+  }
+  let game = container
+    .config
+    .as_ref()
+    .and_then(|c| c.labels.as_ref())
+    .and_then(|l| l.get(GAME_LABEL))
+    .ok_or_else(|| anyhow!("{name} has no {GAME_LABEL} label to go on"))?;
+  let protocol = Protocol::for_game(game)
+    .ok_or_else(|| anyhow!("Binkies doesn't know how to talk to {game} servers"))?;
+  let port =
+    published_port(&container).ok_or_else(|| anyhow!("{name} doesn't publish any ports"))?;
+  let address = host_address(hosts, name)
+    .ok_or_else(|| anyhow!("Couldn't work out which host {name} is on"))?;
+
+  let info = protocol
+    .query(&address, protocol.query_port(port))
+    .await
+    .map_err(|e| anyhow!("{name} is running but not answering: {e}"))?;
+  Ok((game.to_string(), info))
+}
+
+/// Where a container's game can be reached, going by the docker host it's on.
+/// Containers on a local socket are on this machine.
+fn host_address(hosts: &[DockerHost], name: &str) -> Option<String> {
+  let host = match (name.split_once('/'), hosts) {
+    (Some((host, _)), _) => hosts.iter().find(|h| h.name == host)?,
+    (None, [only]) => only,
+    (None, _) => return None,
+  };
+  let url = Url::parse(&host.address).ok()?;
+  match url.host_str() {
+    Some(h) if !h.is_empty() => Some(h.to_string()),
+    _ => Some("127.0.0.1".to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  fn hosts(hosts: &[(&str, &str)]) -> Vec<DockerHost> {
+    hosts
+      .iter()
+      .map(|(name, address)| DockerHost {
+        name: name.to_string(),
+        address: address.to_string(),
+        tls: None,
+      })
+      .collect()
+  }
+
+  #[test_case("nas/valheim" => Some("10.0.0.5".to_string()); "remote host")]
+  #[test_case("local/minecraft" => Some("127.0.0.1".to_string()); "local socket")]
+  #[test_case("pi/minecraft" => None; "unknown host")]
+  #[test_case("minecraft" => None; "ambiguous")]
+  fn finds_host_address(name: &str) -> Option<String> {
+    let hosts = hosts(&[
+      ("local", "unix:///var/run/docker.sock"),
+      ("nas", "tcp://10.0.0.5:2376"),
+    ]);
+    host_address(&hosts, name)
+  }
+
+  #[test]
+  fn lone_host_needs_no_name() {
+    let hosts = hosts(&[("nas", "tcp://10.0.0.5:2375")]);
+    assert_eq!(
+      Some("10.0.0.5".to_string()),
+      host_address(&hosts, "valheim")
+    );
+  }
+}
@@ -1,13 +1,26 @@
+use super::{permissions::ServerPermissions, status::query_game};
 use crate::{
-  cmd::{arg_util::Args, SubCommandHandler},
+  cmd::{arg_util::Args, component_util::respond, SubCommandHandler},
+  config::DockerHost,
   docker::DockerClient,
+  persistence::PersistentStore,
 };
 use anyhow::anyhow;
 use derive_new::new;
 use serenity::{
-  all::CommandInteraction, async_trait, builder::EditInteractionResponse, prelude::Context,
+  all::{ButtonStyle, CommandInteraction, ComponentInteraction, UserId},
+  async_trait,
+  builder::{CreateActionRow, CreateButton, EditInteractionResponse},
+  prelude::{Context, Mutex},
+  utils::MessageBuilder,
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::info;
+use uuid::Uuid;
+
+const CONFIRM_PREFIX: &str = "servers-stop:";
+const CANCEL_PREFIX: &str = "servers-keep:";
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
 
 // Helper function to send response
 async fn send_response(
@@ -21,9 +34,41 @@ async fn send_response(
   Ok(())
 }
 
+/// How many are playing on the server, as far as taking it down should care.
+/// Always none if the guild turned confirm-stop off.
+pub async fn players_on(
+  docker: &dyn DockerClient,
+  hosts: &[DockerHost],
+  persistence: &PersistentStore,
+  itx: &CommandInteraction,
+  name: &str,
+) -> u32 {
+  let confirm = itx
+    .guild_id
+    .map(|g| ServerPermissions::load(persistence, g).confirm_stop)
+    .unwrap_or(true);
+  // Can't ask the server means can't tell anyone's on, so don't hold things up
+  match confirm {
+    true => query_game(docker, hosts, name)
+      .await
+      .map(|(_, info)| info.online)
+      .unwrap_or_default(),
+    false => 0,
+  }
+}
+
+struct PendingStop {
+  name: String,
+  user_id: UserId,
+}
+
 #[derive(new)]
 pub struct Stop {
   docker: Arc<Box<dyn DockerClient>>,
+  hosts: Vec<DockerHost>,
+  persistence: Arc<PersistentStore>,
+  #[new(default)]
+  pending: Arc<Mutex<HashMap<Uuid, PendingStop>>>,
 }
 
 #[async_trait]
@@ -38,9 +83,123 @@ impl SubCommandHandler for Stop {
       .str("server-name")
       .map_err(|e| anyhow!("Must provide a server name").context(e))?;
 
+    let online = players_on(&**self.docker, &self.hosts, &self.persistence, itx, name).await;
+    if online > 0 {
+      return self.ask(ctx, itx, name, online).await;
+    }
+
     match self.docker.stop(name).await {
       Ok(_) => send_response(ctx, itx, "Server stopped".to_string()).await,
       Err(e) => send_response(ctx, itx, format!("{e}")).await,
     }
   }
 }
+
+impl Stop {
+  /// Asks the caller to confirm with buttons, which go away if nobody answers
+  async fn ask(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    name: &str,
+    online: u32,
+  ) -> Result<(), anyhow::Error> {
+    let id = Uuid::new_v4();
+    self.pending.lock().await.insert(
+      id,
+      PendingStop {
+        name: name.to_string(),
+        user_id: itx.user.id,
+      },
+    );
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new()
+          .content(
+            MessageBuilder::new()
+              .push_bold(name)
+              .push(format!(" has {online} playing, stop it anyway?"))
+              .build(),
+          )
+          .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(format!("{CONFIRM_PREFIX}{id}"))
+              .label("Stop it")
+              .style(ButtonStyle::Danger),
+            CreateButton::new(format!("{CANCEL_PREFIX}{id}"))
+              .label("Leave it")
+              .style(ButtonStyle::Secondary),
+          ])]),
+      )
+      .await?;
+
+    let pending = self.pending.clone();
+    let (ctx, itx) = (ctx.clone(), itx.clone());
+    tokio::spawn(async move {
+      tokio::time::sleep(CONFIRM_TIMEOUT).await;
+      if pending.lock().await.remove(&id).is_none() {
+        return;
+      }
+      info!("Stop confirmation {} timed out", id);
+      let _ = itx
+        .edit_response(
+          &ctx.http,
+          EditInteractionResponse::new()
+            .content("Nobody answered, leaving it running")
+            .components(vec![]),
+        )
+        .await;
+    });
+    Ok(())
+  }
+
+  /// Handles the confirm buttons, returning false if the interaction isn't ours
+  pub async fn msg_interact(
+    &self,
+    ctx: &Context,
+    itx: &ComponentInteraction,
+  ) -> Result<bool, anyhow::Error> {
+    let custom_id = itx.data.custom_id.as_str();
+    let (id, confirmed) = match (
+      custom_id.strip_prefix(CONFIRM_PREFIX),
+      custom_id.strip_prefix(CANCEL_PREFIX),
+    ) {
+      (Some(id), _) => (id, true),
+      (_, Some(id)) => (id, false),
+      _ => return Ok(false),
+    };
+    let id: Uuid = id
+      .parse()
+      .map_err(|e| anyhow!("Bad id on stop confirmation {id}").context(e))?;
+
+    let pending = {
+      let mut pending = self.pending.lock().await;
+      match pending.get(&id) {
+        None => {
+          respond(ctx, itx, "This has expired, ask again".to_string(), true).await?;
+          return Ok(true);
+        }
+        Some(p) if p.user_id != itx.user.id => {
+          respond(ctx, itx, "Not your call to make".to_string(), false).await?;
+          return Ok(true);
+        }
+        Some(_) => pending.remove(&id).expect("Checked present under lock"),
+      }
+    };
+    if !confirmed {
+      respond(ctx, itx, format!("Leaving {} be", pending.name), true).await?;
+      return Ok(true);
+    }
+
+    info!("Stopping {} with players on, as confirmed", pending.name);
+    respond(ctx, itx, format!("Stopping {}...", pending.name), true).await?;
+    let msg = match self.docker.stop(&pending.name).await {
+      Ok(_) => "Server stopped".to_string(),
+      Err(e) => format!("{e}"),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+      .await?;
+    Ok(true)
+  }
+}
@@ -0,0 +1,66 @@
+use super::{resume::save_queue, SubCommandHandler};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore, types::Chan};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::{ChannelId, CommandInteraction},
+  async_trait,
+  builder::EditInteractionResponse,
+  client::Context,
+};
+use std::sync::Arc;
+
+/// Empties the queue but lets the current track play out
+#[derive(new)]
+pub struct Clear {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Clear {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    _: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+    let handler_lock = manager
+      .get(guild_id)
+      .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
+    let handler = handler_lock.lock().await;
+
+    let removed = handler.queue().modify_queue(|queue| match queue.len() {
+      0 | 1 => vec![],
+      _ => queue.drain(1..).collect::<Vec<_>>(),
+    });
+    let count = removed.len();
+    for queued in removed {
+      let _ = queued.stop();
+    }
+
+    if let Some(voice) = handler.current_channel() {
+      save_queue(
+        &self.persistence,
+        guild_id,
+        handler.queue(),
+        Chan(ChannelId::from(voice.0)),
+        Chan(itx.channel_id),
+      );
+    }
+
+    let content = match count {
+      0 => "Nothing queued up to clear".to_string(),
+      n => format!("Cleared {n} tracks from the queue"),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+      .await?;
+    Ok(())
+  }
+}
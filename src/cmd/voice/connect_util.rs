@@ -1,142 +1,279 @@
-use derive_new::new;
-use kitchen_sink::{
-  actor::{Actor, ActorHandle},
-  shutdown::ShutdownHook,
+use super::{
+  disconnect::{DisconnectEventHandler, DisconnectMessage, SongbirdCall},
+  fair::interleave,
+  history::HistoryRecorder,
+  looping::QueueLooper,
+  now_playing::{NowPlaying, NowPlayingHandler},
+  play::{enqueue_lazy, ListMetadata},
+  resume::{save_queue, QueuePersister},
+  settings::VoiceSettings,
+  sources::Sources,
 };
+use crate::{emoji::EmojiLookup, persistence::PersistentStore, types::Chan};
+use anyhow::anyhow;
+use derive_new::new;
+use kitchen_sink::actor::ActorHandle;
 use serenity::{
-  async_trait,
-  http::Http,
-  model::{guild::Emoji, id::ChannelId},
+  all::{GuildId, UserId},
+  client::Context,
+  model::id::ChannelId,
   prelude::Mutex,
-  utils::MessageBuilder,
 };
-use songbird::{Call, Event, EventContext, EventHandler};
-use std::{sync::Arc, time::Duration};
-use tokio::sync::mpsc::Receiver;
-use tracing::{info, instrument};
-
-#[derive(Clone)]
-pub enum DisconnectMessage {
-  Enqueue,
-  Dequeue,
-  Details(DisconnectDetails),
-  Disconnect(bool), // Forced = true
+use songbird::Call;
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Resolves the voice channel the given user is currently sitting in
+pub fn caller_channel(
+  ctx: &Context,
+  guild_id: GuildId,
+  user_id: UserId,
+) -> Result<ChannelId, anyhow::Error> {
+  ctx
+    .cache
+    .guild(guild_id)
+    .and_then(|g| g.voice_states.get(&user_id).and_then(|vs| vs.channel_id))
+    .ok_or_else(|| anyhow!("Not in a voice channel"))
 }
 
-#[derive(new, Clone)]
-pub struct DisconnectDetails {
-  voice: Arc<Mutex<Call>>,
-  http: Arc<Http>,
-  emoji: Emoji,
+/// The channel asked for, else the one the caller sits in, else the guild's default
+fn choose_channel(
+  requested: Option<ChannelId>,
+  caller: Option<ChannelId>,
+  default: Option<ChannelId>,
+) -> Option<ChannelId> {
+  requested.or(caller).or(default)
 }
 
-pub struct DisconnectActor {
-  receiver: Receiver<DisconnectMessage>,
-  in_progress_count: usize,
-  disconnect_details: Mutex<Option<DisconnectDetails>>,
+/// People, not bots, sitting in the given voice channel
+pub fn listeners(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> usize {
+  let me = ctx.cache.current_user().id;
+  ctx.cache.guild(guild_id).map_or(0, |g| {
+    g.voice_states
+      .values()
+      .filter(|vs| vs.channel_id == Some(channel_id))
+      .filter(|vs| {
+        // Voice states from joining a guild rarely carry their member, the
+        // guild's own member list usually knows them though
+        let bot = vs
+          .member
+          .as_ref()
+          .or_else(|| g.members.get(&vs.user_id))
+          .map(|m| m.user.bot);
+        is_listener(vs.user_id, me, bot)
+      })
+      .count()
+  })
 }
 
-impl ShutdownHook for DisconnectActor {}
+/// Only someone known to be a person counts, which is never us
+fn is_listener(user_id: UserId, me: UserId, bot: Option<bool>) -> bool {
+  user_id != me && bot == Some(false)
+}
 
-#[async_trait]
-impl Actor<DisconnectMessage> for DisconnectActor {
-  fn receiver(&mut self) -> &mut Receiver<DisconnectMessage> {
-    &mut self.receiver
+#[derive(new, Clone)]
+pub struct VoiceConnector {
+  emoji: EmojiLookup,
+  persistence: Arc<PersistentStore>,
+  now_playing: Arc<NowPlaying>,
+  disconnect: ActorHandle<DisconnectMessage>,
+  sources: Sources,
+}
+
+impl VoiceConnector {
+  pub fn sources(&self) -> &Sources {
+    &self.sources
   }
 
-  async fn handle_msg(&mut self, msg: DisconnectMessage) {
-    match msg {
-      DisconnectMessage::Enqueue => self.in_progress_count += 1,
-      DisconnectMessage::Dequeue => self.in_progress_count -= 1,
-      DisconnectMessage::Details(det) => {
-        let mut det_lock = self.disconnect_details.lock().await;
-        *det_lock = Some(det);
-      }
-      DisconnectMessage::Disconnect(forced) => self.disconnect(forced).await,
-    }
+  /// Works out which voice channel to play in for this user, see [choose_channel]
+  pub fn target_channel(
+    &self,
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    requested: Option<ChannelId>,
+  ) -> Result<ChannelId, anyhow::Error> {
+    let caller = caller_channel(ctx, guild_id, user_id).ok();
+    let default = VoiceSettings::load(&self.persistence, guild_id)
+      .default_channel
+      .map(|c| *c);
+    choose_channel(requested, caller, default)
+      .ok_or_else(|| anyhow!("Not in a voice channel, and no default channel set"))
   }
-}
 
-impl DisconnectActor {
-  pub fn new(receiver: Receiver<DisconnectMessage>) -> Self {
-    Self {
-      receiver,
-      in_progress_count: 0,
-      disconnect_details: Mutex::new(None),
+  /// Fetches the call for this guild, joining the given channel if we aren't
+  /// in one already. First joins also register the inactivity listener, start
+  /// keeping the queue on disk, posting what's playing and applying loop modes.
+  pub async fn connect(
+    &self,
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+  ) -> Result<Arc<Mutex<Call>>, anyhow::Error> {
+    let manager = songbird::get(ctx)
+      .await
+      .ok_or_else(|| anyhow!("Songbird Voice client not initialized"))?;
+    // Check if we're already in the channel or not, connecting if not
+    match manager.get(guild_id) {
+      None => {
+        info!("Joining voice for first time...");
+        let handler_lock = manager
+          .join(guild_id, channel_id)
+          .await
+          .map_err(|e| anyhow!("Error joining voice channel").context(format!("{e:?}")))?;
+
+        // Register an event handler to listen for the duration of the call
+        DisconnectEventHandler::register(guild_id, self.disconnect.clone(), &handler_lock).await;
+        QueuePersister::register(guild_id, self.persistence.clone(), &handler_lock).await;
+        QueueLooper::register(
+          guild_id,
+          self.sources.clone(),
+          self.persistence.clone(),
+          &handler_lock,
+        )
+        .await;
+        NowPlayingHandler::register(
+          guild_id,
+          ctx.http.clone(),
+          self.now_playing.clone(),
+          &handler_lock,
+        )
+        .await;
+        HistoryRecorder::register(guild_id, self.persistence.clone(), &handler_lock).await;
+
+        // Inform disconnect of where to disconnect from
+        self
+          .disconnect
+          .send(DisconnectMessage::Details(
+            guild_id,
+            Arc::new(SongbirdCall::new(
+              handler_lock.clone(),
+              ctx.http.clone(),
+              self.emoji.get(&ctx.http, guild_id).await?,
+            )),
+          ))
+          .await;
+
+        Ok(handler_lock)
+      }
+      Some(l) => {
+        {
+          // Rejoin the channel if we're not in it already, but we previously were
+          let mut lock = l.lock().await;
+          if lock.current_channel().is_none() {
+            let _ = lock.join(channel_id).await;
+          }
+        }
+        Ok(l)
+      }
     }
   }
 
-  async fn disconnect(&mut self, force: bool) {
-    let det_lock = self.disconnect_details.lock().await;
-    let Some(details) = det_lock.as_ref() else {
-      // Nothing to disconnect from silly
-      info!("No disconnect details present, nothing to disconnect");
-      return;
+  /// Moves the call over to the given channel, keeping the queue playing.
+  /// Joins afresh if we've not been in voice in this guild yet.
+  pub async fn move_to(
+    &self,
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+  ) -> Result<Arc<Mutex<Call>>, anyhow::Error> {
+    let manager = songbird::get(ctx)
+      .await
+      .ok_or_else(|| anyhow!("Songbird Voice client not initialized"))?;
+    let Some(l) = manager.get(guild_id) else {
+      return self.connect(ctx, guild_id, channel_id).await;
     };
-    let mut handler = details.voice.lock().await;
 
-    let Some(channel) = handler.current_channel() else {
-      info!("Not in a channel, nothing to disconnect");
-      return;
+    info!("Moving voice to {}", channel_id);
+    let join = {
+      let mut lock = l.lock().await;
+      if lock.current_channel() == Some(channel_id.into()) {
+        return Ok(l.clone());
+      }
+      // Joining on the existing call swaps channel without dropping the driver
+      lock.join(channel_id).await
     };
+    join
+      .map_err(|e| anyhow!("Error joining voice channel").context(format!("{e:?}")))?
+      .await
+      .map_err(|e| anyhow!("Error joining voice channel").context(format!("{e:?}")))?;
 
-    if force {
-      info!("Force stopping");
-      handler.queue().stop();
-    } else if !handler.queue().is_empty() || self.in_progress_count != 0 {
-      info!(
-        "Queue not empty or queuing ({}), will not disconnect",
-        self.in_progress_count
-      );
-      return;
+    // Keep any restart picking up in the new channel
+    let store = self.persistence.voice_queues();
+    if let Ok(Some(mut saved)) = store.load(&guild_id) {
+      saved.voice_channel = Chan(channel_id);
+      if let Err(e) = store.save(&guild_id, &saved) {
+        error!("Failed to persist queue for guild {}: {}", guild_id, e);
+      }
     }
+    Ok(l)
+  }
 
-    info!("Disconnecting client from voice");
-    let s_channel = ChannelId::from(channel.0);
-    let _dc = handler.leave().await;
-    let _rep = s_channel
-      .say(
-        &details.http,
-        MessageBuilder::new()
-          .emoji(&details.emoji)
-          .push(" Cya later NERD ")
-          .emoji(&details.emoji)
-          .build(),
-      )
-      .await;
-    info!("Disconnected");
-
-    // Don't reset the details since the call is still valid, and may reconnect
-    // we'll let it tell us when to. On the contrary, though, we should reset queuing
-    // progress
-    self.in_progress_count = 0;
+  /// Queues a track we already have the details of, say from a search or the
+  /// history, joining the requester's channel if need be, or moving to the one
+  /// they asked for. Returns the queue length.
+  pub async fn enqueue_known(
+    &self,
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    requested: Option<ChannelId>,
+    text_channel: ChannelId,
+    meta: ListMetadata,
+  ) -> Result<usize, anyhow::Error> {
+    let channel_id = self.target_channel(ctx, guild_id, user_id, requested)?;
+    let handler_lock = match requested {
+      Some(_) => self.move_to(ctx, guild_id, channel_id).await?,
+      None => self.connect(ctx, guild_id, channel_id).await?,
+    };
+    let mut handler = handler_lock.lock().await;
+    let settings = VoiceSettings::load(&self.persistence, guild_id);
+    let input = settings.filters.apply(self.sources.source(&meta));
+    // No need to fetch the metadata again
+    enqueue_lazy(
+      &mut handler,
+      input,
+      meta.requested(user_id),
+      settings.gain(),
+    );
+    if settings.fair_queue {
+      interleave(handler.queue());
+    }
+    save_queue(
+      &self.persistence,
+      guild_id,
+      handler.queue(),
+      Chan(channel_id),
+      Chan(text_channel),
+    );
+    Ok(handler.queue().len())
   }
 }
 
-pub struct DisconnectEventHandler {
-  handle: ActorHandle<DisconnectMessage>,
-}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
 
-impl DisconnectEventHandler {
-  pub async fn register(
-    timeout: u64,
-    handle: ActorHandle<DisconnectMessage>,
-    call: &Arc<Mutex<Call>>,
-  ) {
-    let mut call_lock = call.lock().await;
-    call_lock.add_global_event(
-      Event::Periodic(Duration::from_secs(timeout), None),
-      Self { handle },
-    );
+  #[test_case(2, Some(false) => true; "person")]
+  #[test_case(2, Some(true) => false; "another bot")]
+  #[test_case(2, None => false; "unknown")]
+  #[test_case(1, Some(false) => false; "ourselves")]
+  #[test_case(1, None => false; "ourselves without a member")]
+  fn counts_listeners(user_id: u64, bot: Option<bool>) -> bool {
+    is_listener(UserId::new(user_id), UserId::new(1), bot)
   }
-}
 
-#[async_trait]
-impl EventHandler for DisconnectEventHandler {
-  #[instrument(name = "VoiceTimeoutListener", level = "INFO", skip(self, _ctx))]
-  async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
-    info!("Checking for inactivity...");
-    let _ = self.handle.send(DisconnectMessage::Disconnect(false)).await;
-    None
+  #[test_case(Some(1), Some(2), Some(3) => Some(1); "asked for wins")]
+  #[test_case(None, Some(2), Some(3) => Some(2); "caller's channel next")]
+  #[test_case(None, None, Some(3) => Some(3); "default last")]
+  #[test_case(None, None, None => None; "nowhere to go")]
+  fn channel_precedence(
+    requested: Option<u64>,
+    caller: Option<u64>,
+    default: Option<u64>,
+  ) -> Option<u64> {
+    let id = |c: Option<u64>| c.map(ChannelId::new);
+    choose_channel(id(requested), id(caller), id(default)).map(|c| c.get())
   }
 }
@@ -0,0 +1,43 @@
+use super::{settings::VoiceSettings, SubCommandHandler};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore, types::Chan};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+};
+use std::sync::Arc;
+
+/// Sets where the bot plays for anyone asking from outside voice. Leaving the
+/// channel out forgets it again.
+#[derive(new)]
+pub struct DefaultChannel {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for DefaultChannel {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    // 1 optional arg: channel. Voice channel
+    let mut settings = VoiceSettings::load(&self.persistence, guild_id);
+    settings.default_channel = args.opt_channel("channel")?.map(|c| Chan(c.id));
+    settings.save(&self.persistence, guild_id)?;
+
+    let content = match &settings.default_channel {
+      Some(channel) => format!("Default music channel is now {channel}"),
+      None => "No default music channel, you'll need to be in voice".to_string(),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+      .await?;
+    Ok(())
+  }
+}
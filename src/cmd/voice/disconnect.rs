@@ -0,0 +1,535 @@
+use super::connect_util::listeners;
+use derive_new::new;
+use kitchen_sink::{
+  actor::{Actor, ActorHandle},
+  shutdown::ShutdownHook,
+};
+use serenity::{
+  all::GuildId,
+  async_trait,
+  client::Context,
+  http::Http,
+  model::{guild::Emoji, id::ChannelId},
+  prelude::Mutex,
+  utils::MessageBuilder,
+};
+use songbird::{Call, Event, EventContext, EventHandler};
+use std::{
+  collections::HashMap,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+use tokio::sync::mpsc::Receiver;
+use tracing::{info, instrument};
+
+/// How often a call checks in on whether it's still needed
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The parts of a guild's voice connection the disconnect actor drives,
+/// kept behind a trait so it can be exercised without a live call
+#[async_trait]
+pub trait GuildCall: Send + Sync {
+  async fn is_connected(&self) -> bool;
+  async fn is_idle(&self) -> bool;
+  async fn stop(&self);
+  async fn leave(&self);
+}
+
+/// A songbird call, along with what's needed to say goodbye on the way out
+#[derive(new)]
+pub struct SongbirdCall {
+  call: Arc<Mutex<Call>>,
+  http: Arc<Http>,
+  emoji: Emoji,
+}
+
+#[async_trait]
+impl GuildCall for SongbirdCall {
+  async fn is_connected(&self) -> bool {
+    self.call.lock().await.current_channel().is_some()
+  }
+
+  async fn is_idle(&self) -> bool {
+    self.call.lock().await.queue().is_empty()
+  }
+
+  async fn stop(&self) {
+    self.call.lock().await.queue().stop();
+  }
+
+  async fn leave(&self) {
+    let mut handler = self.call.lock().await;
+    let Some(channel) = handler.current_channel() else {
+      return;
+    };
+    let s_channel = ChannelId::from(channel.0);
+    let _dc = handler.leave().await;
+    let _rep = s_channel
+      .say(
+        &self.http,
+        MessageBuilder::new()
+          .emoji(&self.emoji)
+          .push(" Cya later NERD ")
+          .emoji(&self.emoji)
+          .build(),
+      )
+      .await;
+  }
+}
+
+#[derive(Clone)]
+pub enum DisconnectMessage {
+  Enqueue(GuildId),
+  Dequeue(GuildId),
+  Details(GuildId, Arc<dyn GuildCall>),
+  Disconnect(GuildId, bool), // Forced = true
+  /// Someone joined or left, this is how many people share the bot's channel now
+  Listeners(GuildId, usize),
+  /// Starts, cancels or finishes the countdown to leaving
+  Check(GuildId),
+}
+
+#[derive(Default)]
+struct GuildVoice {
+  call: Option<Arc<dyn GuildCall>>,
+  in_progress_count: usize,
+  // Unknown until the first voice state update after joining
+  listeners: Option<usize>,
+  // When we first noticed there was no reason to stay
+  idle_since: Option<Instant>,
+}
+
+impl GuildVoice {
+  fn alone(&self) -> bool {
+    self.listeners == Some(0)
+  }
+}
+
+pub struct DisconnectActor {
+  receiver: Receiver<DisconnectMessage>,
+  timeout: Duration,
+  guilds: HashMap<GuildId, GuildVoice>,
+}
+
+impl ShutdownHook for DisconnectActor {}
+
+#[async_trait]
+impl Actor<DisconnectMessage> for DisconnectActor {
+  fn receiver(&mut self) -> &mut Receiver<DisconnectMessage> {
+    &mut self.receiver
+  }
+
+  async fn handle_msg(&mut self, msg: DisconnectMessage) {
+    match msg {
+      DisconnectMessage::Enqueue(guild_id) => {
+        self.guilds.entry(guild_id).or_default().in_progress_count += 1
+      }
+      DisconnectMessage::Dequeue(guild_id) => {
+        // Disconnecting resets progress, so a straggler may finish after the fact
+        let guild = self.guilds.entry(guild_id).or_default();
+        guild.in_progress_count = guild.in_progress_count.saturating_sub(1);
+      }
+      DisconnectMessage::Details(guild_id, call) => {
+        self.guilds.entry(guild_id).or_default().call = Some(call);
+      }
+      DisconnectMessage::Disconnect(guild_id, forced) => self.disconnect(guild_id, forced).await,
+      DisconnectMessage::Listeners(guild_id, count) => {
+        self.guilds.entry(guild_id).or_default().listeners = Some(count);
+        self.check(guild_id, Instant::now()).await;
+      }
+      DisconnectMessage::Check(guild_id) => self.check(guild_id, Instant::now()).await,
+    }
+  }
+}
+
+impl DisconnectActor {
+  pub fn new(receiver: Receiver<DisconnectMessage>, timeout: Duration) -> Self {
+    Self {
+      receiver,
+      timeout,
+      guilds: HashMap::new(),
+    }
+  }
+
+  /// Leaves once the guild has gone `timeout` with nobody listening or nothing
+  /// to play. Anything worth staying for in between starts the clock over.
+  #[instrument(name = "DisconnectActor", level = "DEBUG", skip(self))]
+  async fn check(&mut self, guild_id: GuildId, now: Instant) {
+    let Some(guild) = self.guilds.get_mut(&guild_id) else {
+      return;
+    };
+    let Some(call) = guild.call.as_ref() else {
+      return;
+    };
+    if !call.is_connected().await {
+      guild.idle_since = None;
+      return;
+    }
+    let idle = call.is_idle().await && guild.in_progress_count == 0;
+    if !guild.alone() && !idle {
+      if guild.idle_since.take().is_some() {
+        info!("Needed again, no longer counting down");
+      }
+      return;
+    }
+    match guild.idle_since {
+      None => {
+        info!("Alone or idle, leaving in {:?}", self.timeout);
+        guild.idle_since = Some(now);
+      }
+      Some(since) if now.duration_since(since) >= self.timeout => {
+        self.disconnect(guild_id, false).await;
+      }
+      Some(_) => {}
+    }
+  }
+
+  #[instrument(name = "DisconnectActor", level = "INFO", skip(self))]
+  async fn disconnect(&mut self, guild_id: GuildId, force: bool) {
+    let Some(guild) = self.guilds.get_mut(&guild_id) else {
+      info!("Never joined this guild, nothing to disconnect");
+      return;
+    };
+    let Some(call) = guild.call.as_ref() else {
+      // Nothing to disconnect from silly
+      info!("No disconnect details present, nothing to disconnect");
+      return;
+    };
+
+    if !call.is_connected().await {
+      info!("Not in a channel, nothing to disconnect");
+      return;
+    }
+
+    if force || guild.alone() {
+      info!("Force stopping, forced: {}", force);
+      call.stop().await;
+    } else if !call.is_idle().await || guild.in_progress_count != 0 {
+      info!(
+        "Queue not empty or queuing ({}), will not disconnect",
+        guild.in_progress_count
+      );
+      return;
+    }
+
+    info!("Disconnecting client from voice");
+    call.leave().await;
+    info!("Disconnected");
+
+    // Don't reset the details since the call is still valid, and may reconnect
+    // we'll let it tell us when to. On the contrary, though, we should reset queuing
+    // progress and whatever we knew about who was listening
+    guild.in_progress_count = 0;
+    guild.listeners = None;
+    guild.idle_since = None;
+  }
+}
+
+/// Tells the disconnect actor how many people are left with the bot as they come and go
+#[derive(new, Clone)]
+pub struct ListenerWatch {
+  handle: ActorHandle<DisconnectMessage>,
+}
+
+impl ListenerWatch {
+  pub async fn voice_state_update(&self, ctx: &Context, guild_id: GuildId) {
+    let Some(manager) = songbird::get(ctx).await else {
+      return;
+    };
+    let Some(call) = manager.get(guild_id) else {
+      return;
+    };
+    let Some(channel) = call.lock().await.current_channel() else {
+      return;
+    };
+    let count = listeners(ctx, guild_id, ChannelId::from(channel.0));
+    let _ = self
+      .handle
+      .send(DisconnectMessage::Listeners(guild_id, count))
+      .await;
+  }
+}
+
+pub struct DisconnectEventHandler {
+  guild_id: GuildId,
+  handle: ActorHandle<DisconnectMessage>,
+}
+
+impl DisconnectEventHandler {
+  pub async fn register(
+    guild_id: GuildId,
+    handle: ActorHandle<DisconnectMessage>,
+    call: &Arc<Mutex<Call>>,
+  ) {
+    let mut call_lock = call.lock().await;
+    call_lock.add_global_event(
+      Event::Periodic(CHECK_INTERVAL, None),
+      Self { guild_id, handle },
+    );
+  }
+}
+
+#[async_trait]
+impl EventHandler for DisconnectEventHandler {
+  #[instrument(name = "VoiceTimeoutListener", level = "DEBUG", skip(self, _ctx))]
+  async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+    let _ = self
+      .handle
+      .send(DisconnectMessage::Check(self.guild_id))
+      .await;
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use kitchen_sink::shutdown::ShutdownCoordinator;
+  use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+  #[derive(Default)]
+  struct FakeCall {
+    queued: AtomicUsize,
+    left: AtomicBool,
+    stopped: AtomicBool,
+  }
+
+  impl FakeCall {
+    fn playing(tracks: usize) -> Arc<Self> {
+      let call = Self::default();
+      call.queued.store(tracks, Ordering::SeqCst);
+      Arc::new(call)
+    }
+
+    fn left(&self) -> bool {
+      self.left.load(Ordering::SeqCst)
+    }
+  }
+
+  #[async_trait]
+  impl GuildCall for FakeCall {
+    async fn is_connected(&self) -> bool {
+      !self.left()
+    }
+
+    async fn is_idle(&self) -> bool {
+      self.queued.load(Ordering::SeqCst) == 0
+    }
+
+    async fn stop(&self) {
+      self.stopped.store(true, Ordering::SeqCst);
+      self.queued.store(0, Ordering::SeqCst);
+    }
+
+    async fn leave(&self) {
+      self.left.store(true, Ordering::SeqCst);
+    }
+  }
+
+  fn actor() -> DisconnectActor {
+    let (_, receiver) = tokio::sync::mpsc::channel(1);
+    DisconnectActor::new(receiver, TIMEOUT)
+  }
+
+  const TIMEOUT: Duration = Duration::from_secs(60);
+
+  fn guild(id: u64) -> GuildId {
+    GuildId::new(id)
+  }
+
+  #[tokio::test]
+  async fn idle_guild_leaves_without_touching_busy_one() {
+    let mut actor = actor();
+    let (busy, idle) = (FakeCall::playing(3), FakeCall::playing(0));
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(1), busy.clone()))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(2), idle.clone()))
+      .await;
+
+    actor
+      .handle_msg(DisconnectMessage::Disconnect(guild(1), false))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Disconnect(guild(2), false))
+      .await;
+
+    assert!(!busy.left());
+    assert!(idle.left());
+  }
+
+  #[tokio::test]
+  async fn stop_only_stops_its_own_guild() {
+    let mut actor = actor();
+    let (first, second) = (FakeCall::playing(2), FakeCall::playing(2));
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(1), first.clone()))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(2), second.clone()))
+      .await;
+
+    actor
+      .handle_msg(DisconnectMessage::Disconnect(guild(2), true))
+      .await;
+
+    assert!(!first.left() && !first.stopped.load(Ordering::SeqCst));
+    assert!(second.left() && second.stopped.load(Ordering::SeqCst));
+  }
+
+  #[tokio::test]
+  async fn queuing_is_tracked_per_guild() {
+    let mut actor = actor();
+    let (first, second) = (FakeCall::playing(0), FakeCall::playing(0));
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(1), first.clone()))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(2), second.clone()))
+      .await;
+
+    // Both guilds start queuing, only the first finishes before the idle check
+    actor.handle_msg(DisconnectMessage::Enqueue(guild(1))).await;
+    actor.handle_msg(DisconnectMessage::Enqueue(guild(2))).await;
+    actor.handle_msg(DisconnectMessage::Dequeue(guild(1))).await;
+    actor
+      .handle_msg(DisconnectMessage::Disconnect(guild(1), false))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Disconnect(guild(2), false))
+      .await;
+
+    assert!(first.left());
+    assert!(!second.left());
+  }
+
+  #[tokio::test]
+  async fn leaves_once_alone_for_the_timeout() {
+    let mut actor = actor();
+    let call = FakeCall::playing(3);
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(1), call.clone()))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Listeners(guild(1), 0))
+      .await;
+
+    let start = Instant::now();
+    actor.check(guild(1), start + TIMEOUT / 2).await;
+    assert!(!call.left());
+    actor.check(guild(1), start + TIMEOUT * 2).await;
+    assert!(call.left() && call.stopped.load(Ordering::SeqCst));
+  }
+
+  #[tokio::test]
+  async fn rejoining_cancels_the_countdown() {
+    let mut actor = actor();
+    let call = FakeCall::playing(3);
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(1), call.clone()))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Listeners(guild(1), 0))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Listeners(guild(1), 1))
+      .await;
+
+    // Were the clock still running from being alone, this would be long enough
+    actor.check(guild(1), Instant::now() + TIMEOUT * 2).await;
+    assert!(!call.left());
+  }
+
+  #[tokio::test]
+  async fn idle_with_company_still_counts_down() {
+    let mut actor = actor();
+    let call = FakeCall::playing(0);
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(1), call.clone()))
+      .await;
+    actor
+      .handle_msg(DisconnectMessage::Listeners(guild(1), 2))
+      .await;
+
+    let start = Instant::now();
+    actor.check(guild(1), start).await;
+    actor.check(guild(1), start + TIMEOUT / 2).await;
+    assert!(!call.left());
+    actor.check(guild(1), start + TIMEOUT).await;
+    assert!(call.left() && !call.stopped.load(Ordering::SeqCst));
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+  async fn concurrent_guilds_through_the_handle() {
+    let mut shutdown = ShutdownCoordinator::new();
+    let handle = ActorHandle::<DisconnectMessage>::spawn(
+      |r, _| Box::new(DisconnectActor::new(r, TIMEOUT)),
+      &mut shutdown,
+    );
+    let (first, second, marker) = (
+      FakeCall::playing(1),
+      FakeCall::playing(0),
+      FakeCall::playing(0),
+    );
+    for (id, call) in [(1, &first), (2, &second), (3, &marker)] {
+      let call: Arc<dyn GuildCall> = call.clone();
+      let _ = handle
+        .send(DisconnectMessage::Details(guild(id), call))
+        .await;
+    }
+
+    // The first guild is stopped outright, the second is still queuing when it's
+    // asked to leave, and their messages land in whatever order they like
+    let send = |msg| {
+      let handle = handle.clone();
+      async move {
+        let _ = handle.send(msg).await;
+        tokio::task::yield_now().await;
+      }
+    };
+    tokio::join!(
+      async {
+        send(DisconnectMessage::Enqueue(guild(1))).await;
+        send(DisconnectMessage::Dequeue(guild(1))).await;
+        send(DisconnectMessage::Enqueue(guild(1))).await;
+        send(DisconnectMessage::Disconnect(guild(1), true)).await;
+      },
+      async {
+        send(DisconnectMessage::Enqueue(guild(2))).await;
+        send(DisconnectMessage::Enqueue(guild(2))).await;
+        send(DisconnectMessage::Dequeue(guild(2))).await;
+        send(DisconnectMessage::Disconnect(guild(2), false)).await;
+      },
+    );
+
+    // Messages are handled in order, so once the marker leaves the rest are done
+    let _ = handle
+      .send(DisconnectMessage::Disconnect(guild(3), true))
+      .await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+      while !marker.left() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+      }
+    })
+    .await
+    .expect("Actor never got to the marker");
+
+    assert!(first.left() && first.stopped.load(Ordering::SeqCst));
+    assert!(!second.left() && !second.stopped.load(Ordering::SeqCst));
+  }
+
+  #[tokio::test]
+  async fn unknown_guild_is_ignored() {
+    let mut actor = actor();
+    let call = FakeCall::playing(0);
+    actor
+      .handle_msg(DisconnectMessage::Details(guild(1), call.clone()))
+      .await;
+    actor.handle_msg(DisconnectMessage::Dequeue(guild(2))).await;
+    actor
+      .handle_msg(DisconnectMessage::Disconnect(guild(2), true))
+      .await;
+    assert!(!call.left());
+  }
+}
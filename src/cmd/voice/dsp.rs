@@ -0,0 +1,330 @@
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+/// Where normalized tracks get pulled to. R128 proper asks for -23, which is
+/// far too quiet next to everyone's voices in a call.
+pub const TARGET_LUFS: f64 = -16.0;
+/// Quiet tracks only get this much louder, past it we'd just be boosting noise
+const MAX_GAIN_DB: f64 = 10.0;
+const MIN_GAIN_DB: f64 = -20.0;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// Gating blocks are 400ms, taken every 100ms
+const HOPS_PER_BLOCK: usize = 4;
+/// Blocks are tallied into 0.1 LU wide bins from the absolute gate up to +30
+/// LUFS, so a stream can run for days without the meter growing
+const HISTOGRAM_STEP: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 1000;
+
+/// Second order IIR filter
+#[derive(Clone, Debug)]
+pub struct Biquad {
+  b: [f64; 3],
+  a: [f64; 2],
+  x: [f64; 2],
+  y: [f64; 2],
+}
+
+impl Biquad {
+  fn from_raw(b: [f64; 3], a: [f64; 3]) -> Self {
+    Self {
+      b: b.map(|v| v / a[0]),
+      a: [a[1] / a[0], a[2] / a[0]],
+      x: [0.0; 2],
+      y: [0.0; 2],
+    }
+  }
+
+  /// Shelf from the RBJ audio EQ cookbook
+  pub fn low_shelf(rate: f64, freq: f64, gain_db: f64, q: f64) -> Self {
+    let a = 10f64.powf(gain_db / 40.0);
+    let w0 = 2.0 * PI * freq / rate;
+    let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+    let sqrt_a = 2.0 * a.sqrt() * alpha;
+    Self::from_raw(
+      [
+        a * ((a + 1.0) - (a - 1.0) * cos + sqrt_a),
+        2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+        a * ((a + 1.0) - (a - 1.0) * cos - sqrt_a),
+      ],
+      [
+        (a + 1.0) + (a - 1.0) * cos + sqrt_a,
+        -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+        (a + 1.0) + (a - 1.0) * cos - sqrt_a,
+      ],
+    )
+  }
+
+  /// First stage of BS.1770's K-weighting, modelling the head. The spec only
+  /// gives coefficients for 48kHz, this derives them for any rate the way
+  /// libebur128 does.
+  pub fn k_shelf(rate: f64) -> Self {
+    let (freq, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * freq / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    Self::from_raw(
+      [
+        vh + vb * k / q + k * k,
+        2.0 * (k * k - vh),
+        vh - vb * k / q + k * k,
+      ],
+      [
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+      ],
+    )
+  }
+
+  /// Second stage of K-weighting, a low cut
+  pub fn k_high_pass(rate: f64) -> Self {
+    let (freq, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * freq / rate).tan();
+    Self::from_raw(
+      [1.0, -2.0, 1.0],
+      [
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+      ],
+    )
+  }
+
+  pub fn process(&mut self, x: f64) -> f64 {
+    let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+      - self.a[0] * self.y[0]
+      - self.a[1] * self.y[1];
+    self.x = [x, self.x[0]];
+    self.y = [y, self.y[0]];
+    y
+  }
+}
+
+/// Integrated loudness as ITU-R BS.1770 / EBU R128 measure it, worked out as
+/// the audio streams past rather than over the whole track up front
+pub struct LoudnessMeter {
+  channels: usize,
+  // Per channel K-weighting: a head shelf then a low cut
+  weighting: Vec<(Biquad, Biquad)>,
+  hop_len: usize,
+  hop_frames: usize,
+  hop_energy: f64,
+  recent_hops: Vec<f64>,
+  // How many blocks landed in each bin, and their summed mean squares
+  histogram: Vec<(u64, f64)>,
+}
+
+impl LoudnessMeter {
+  pub fn new(rate: u32, channels: usize) -> Self {
+    let rate = rate as f64;
+    Self {
+      channels,
+      weighting: (0..channels)
+        .map(|_| (Biquad::k_shelf(rate), Biquad::k_high_pass(rate)))
+        .collect(),
+      hop_len: (rate / 10.0) as usize,
+      hop_frames: 0,
+      hop_energy: 0.0,
+      recent_hops: Vec::with_capacity(HOPS_PER_BLOCK),
+      histogram: vec![(0, 0.0); HISTOGRAM_BINS],
+    }
+  }
+
+  /// Feeds interleaved samples through. Returns true whenever a new gating
+  /// block completed, which is when the integrated loudness may have moved.
+  pub fn push(&mut self, samples: &[f32]) -> bool {
+    let mut updated = false;
+    for frame in samples.chunks_exact(self.channels) {
+      for (sample, (shelf, pass)) in frame.iter().zip(self.weighting.iter_mut()) {
+        let weighted = pass.process(shelf.process(*sample as f64));
+        self.hop_energy += weighted * weighted;
+      }
+      self.hop_frames += 1;
+      if self.hop_frames == self.hop_len {
+        updated |= self.finish_hop();
+      }
+    }
+    updated
+  }
+
+  fn finish_hop(&mut self) -> bool {
+    if self.recent_hops.len() == HOPS_PER_BLOCK {
+      self.recent_hops.remove(0);
+    }
+    self.recent_hops.push(self.hop_energy / self.hop_len as f64);
+    self.hop_frames = 0;
+    self.hop_energy = 0.0;
+    if self.recent_hops.len() < HOPS_PER_BLOCK {
+      return false;
+    }
+    let block = self.recent_hops.iter().sum::<f64>() / HOPS_PER_BLOCK as f64;
+    self.add_block(block);
+    true
+  }
+
+  fn add_block(&mut self, mean_square: f64) {
+    let loudness = lufs(mean_square);
+    // Anything under the absolute gate never counts, so there's no need to keep it
+    if loudness <= ABSOLUTE_GATE_LUFS {
+      return;
+    }
+    let bin = &mut self.histogram[bin_of(loudness)];
+    bin.0 += 1;
+    bin.1 += mean_square;
+  }
+
+  /// LUFS so far, or None until there's been a block loud enough to count.
+  /// The relative gate is only as fine as the histogram's bins.
+  pub fn integrated(&self) -> Option<f64> {
+    let gated_mean = |from: usize| {
+      let (count, sum) = self.histogram[from..]
+        .iter()
+        .fold((0, 0.0), |(n, s), (count, sum)| (n + count, s + sum));
+      match count {
+        0 => None,
+        n => Some(sum / n as f64),
+      }
+    };
+    let absolute = gated_mean(0)?;
+    let relative_gate = lufs(absolute) + RELATIVE_GATE_LU;
+    gated_mean(bin_of(relative_gate.max(ABSOLUTE_GATE_LUFS))).map(lufs)
+  }
+}
+
+fn bin_of(loudness: f64) -> usize {
+  let bin = ((loudness - ABSOLUTE_GATE_LUFS) / HISTOGRAM_STEP) as usize;
+  bin.min(HISTOGRAM_BINS - 1)
+}
+
+fn lufs(mean_square: f64) -> f64 {
+  -0.691 + 10.0 * mean_square.log10()
+}
+
+/// Pulls a track towards [TARGET_LUFS], easing between gains so the changes
+/// don't click, and hard limiting whatever still pokes past full scale
+pub struct Normalizer {
+  meter: LoudnessMeter,
+  channels: usize,
+  gain: f64,
+  target_gain: f64,
+  // Per frame change while easing towards the target
+  step: f64,
+}
+
+impl Normalizer {
+  pub fn new(rate: u32, channels: usize) -> Self {
+    Self {
+      meter: LoudnessMeter::new(rate, channels),
+      channels,
+      gain: 1.0,
+      target_gain: 1.0,
+      step: 0.0,
+    }
+  }
+
+  pub fn process(&mut self, samples: &mut [f32]) {
+    if self.meter.push(samples) {
+      if let Some(loudness) = self.meter.integrated() {
+        let db = (TARGET_LUFS - loudness).clamp(MIN_GAIN_DB, MAX_GAIN_DB);
+        self.target_gain = 10f64.powf(db / 20.0);
+        self.step = (self.target_gain - self.gain) / self.meter.hop_len as f64;
+      }
+    }
+    for frame in samples.chunks_exact_mut(self.channels) {
+      if self.gain != self.target_gain {
+        self.gain += self.step;
+        let overshot = (self.step > 0.0 && self.gain > self.target_gain)
+          || (self.step < 0.0 && self.gain < self.target_gain);
+        if overshot {
+          self.gain = self.target_gain;
+        }
+      }
+      for sample in frame {
+        *sample = (*sample as f64 * self.gain).clamp(-1.0, 1.0) as f32;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RATE: u32 = 48_000;
+
+  fn sine(freq: f64, amplitude: f64, secs: f64, channels: usize) -> Vec<f32> {
+    let frames = (RATE as f64 * secs) as usize;
+    (0..frames)
+      .flat_map(|i| {
+        let v = amplitude * (2.0 * PI * freq * i as f64 / RATE as f64).sin();
+        std::iter::repeat_n(v as f32, channels)
+      })
+      .collect()
+  }
+
+  fn rms_db(samples: &[f32]) -> f64 {
+    let ms = samples.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / samples.len() as f64;
+    10.0 * ms.log10()
+  }
+
+  #[test]
+  fn full_scale_tone_reads_as_reference_loudness() {
+    // BS.1770's own sanity check: a 0 dBFS 997Hz tone in one channel is -3.01 LUFS
+    let mut meter = LoudnessMeter::new(RATE, 1);
+    meter.push(&sine(997.0, 1.0, 3.0, 1));
+    let loudness = meter.integrated().unwrap();
+    assert!((loudness + 3.01).abs() < 0.2, "measured {loudness}");
+  }
+
+  #[test]
+  fn silence_has_no_loudness() {
+    let mut meter = LoudnessMeter::new(RATE, 2);
+    meter.push(&vec![0.0; RATE as usize * 2]);
+    assert_eq!(None, meter.integrated());
+  }
+
+  #[test]
+  fn an_hour_of_blocks_is_gated_in_fixed_space() {
+    let mut meter = LoudnessMeter::new(RATE, 2);
+    let ms = |loudness: f64| 10f64.powf((loudness + 0.691) / 10.0);
+    // An hour of 100ms blocks, mostly -20 LUFS with quiet passages the relative
+    // gate throws out and silence the absolute gate does
+    for i in 0..36_000 {
+      meter.add_block(match i % 10 {
+        0 => ms(-40.0),
+        1 => 0.0,
+        _ => ms(-20.0),
+      });
+    }
+    assert_eq!(HISTOGRAM_BINS, meter.histogram.len());
+    let loudness = meter.integrated().unwrap();
+    assert!((loudness + 20.0).abs() < 0.1, "measured {loudness}");
+  }
+
+  #[test]
+  fn normalizer_tames_loud_tracks() {
+    let mut loud = sine(997.0, 1.0, 10.0, 2);
+    Normalizer::new(RATE, 2).process(&mut loud);
+    // A stereo tone reads about 3dB louder than its RMS, the two channels add up
+    let tail = &loud[loud.len() - RATE as usize * 2..];
+    let loudness = rms_db(tail) + 3.01;
+    assert!(
+      (loudness - TARGET_LUFS).abs() < 1.0,
+      "settled at {loudness}"
+    );
+  }
+
+  #[test]
+  fn low_shelf_boosts_bass_only() {
+    let measure = |freq: f64| {
+      let mut shelf = Biquad::low_shelf(RATE as f64, 100.0, 12.0, FRAC_1_SQRT_2);
+      let out: Vec<f32> = sine(freq, 0.1, 1.0, 1)
+        .into_iter()
+        .map(|s| shelf.process(s as f64) as f32)
+        .collect();
+      rms_db(&out[RATE as usize / 2..]) - rms_db(&sine(freq, 0.1, 0.5, 1))
+    };
+    assert!((measure(20.0) - 12.0).abs() < 1.0);
+    assert!(measure(5000.0).abs() < 0.5);
+  }
+}
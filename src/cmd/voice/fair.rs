@@ -0,0 +1,78 @@
+use super::play::track_metadata;
+use serenity::all::UserId;
+use songbird::tracks::TrackQueue;
+use std::collections::VecDeque;
+
+/// Reorders everything after the current track so requesters take turns,
+/// keeping each person's own tracks in the order they asked for them
+pub fn interleave(queue: &TrackQueue) {
+  queue.modify_queue(|queue| {
+    if queue.len() <= 2 {
+      return;
+    }
+    let requesters: Vec<Option<UserId>> = queue
+      .iter()
+      .map(|q| track_metadata(&q.handle()).and_then(|m| m.requested_by.as_ref().map(|u| u.0)))
+      .collect();
+    let mut upcoming: Vec<_> = queue.drain(1..).map(Some).collect();
+    for idx in fair_order(requesters[0], &requesters[1..]) {
+      if let Some(item) = upcoming[idx].take() {
+        queue.push_back(item);
+      }
+    }
+  });
+}
+
+/// Round robin over the requesters of the upcoming tracks, in the order they
+/// first show up. Whoever requested the current track waits for everyone else.
+/// Tracks nobody requested count as one more requester.
+fn fair_order(current: Option<UserId>, upcoming: &[Option<UserId>]) -> Vec<usize> {
+  let mut turns: Vec<(Option<UserId>, VecDeque<usize>)> = vec![];
+  for (idx, requester) in upcoming.iter().enumerate() {
+    match turns.iter_mut().find(|(r, _)| r == requester) {
+      Some((_, tracks)) => tracks.push_back(idx),
+      None => turns.push((*requester, VecDeque::from([idx]))),
+    }
+  }
+  if let Some(pos) = turns.iter().position(|(r, _)| *r == current) {
+    let theirs = turns.remove(pos);
+    turns.push(theirs);
+  }
+
+  let mut order = Vec::with_capacity(upcoming.len());
+  while order.len() < upcoming.len() {
+    for (_, tracks) in turns.iter_mut() {
+      if let Some(idx) = tracks.pop_front() {
+        order.push(idx);
+      }
+    }
+  }
+  order
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn user(id: u64) -> Option<UserId> {
+    Some(UserId::new(id))
+  }
+
+  #[test]
+  fn takes_turns() {
+    let upcoming = [user(1), user(1), user(1), user(2), user(3), user(2)];
+    assert_eq!(vec![0, 3, 4, 1, 5, 2], fair_order(user(9), &upcoming));
+  }
+
+  #[test]
+  fn current_requester_goes_last() {
+    let upcoming = [user(1), user(1), user(2)];
+    assert_eq!(vec![2, 0, 1], fair_order(user(1), &upcoming));
+  }
+
+  #[test]
+  fn unrequested_tracks_share_a_turn() {
+    let upcoming = [None, None, user(1)];
+    assert_eq!(vec![2, 0, 1], fair_order(None, &upcoming));
+  }
+}
@@ -0,0 +1,613 @@
+use super::{
+  dsp::{Biquad, Normalizer},
+  settings::VoiceSettings,
+  SubCommandHandler,
+};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore};
+use anyhow::anyhow;
+use bincode::{Decode, Encode};
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  utils::MessageBuilder,
+};
+use songbird::input::{
+  codecs::{get_codec_registry, get_probe},
+  AudioStream, AudioStreamError, AuxMetadata, Compose, Input, RawAdapter,
+};
+use std::{
+  f64::consts::FRAC_1_SQRT_2,
+  io::{Error as IoError, ErrorKind, Read, Seek, SeekFrom},
+  sync::{
+    mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TryRecvError},
+    Arc, Mutex,
+  },
+  thread,
+};
+use symphonia::core::{
+  audio::SampleBuffer,
+  codecs::{Decoder, DecoderOptions},
+  errors::Error as SymphError,
+  formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+  io::{MediaSource, MediaSourceStream},
+  meta::MetadataOptions,
+  units::{Time, TimeBase},
+};
+use tracing::{error, info};
+
+/// Decoded chunks allowed to pile up ahead of playback, each is one packet's worth
+const BUFFERED_PACKETS: usize = 64;
+const BASS_FREQ: f64 = 100.0;
+/// songbird's raw format header, which counts towards the positions it seeks to
+const RAW_HEADER_LEN: u64 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum BassBoost {
+  Off,
+  Low,
+  High,
+}
+
+impl BassBoost {
+  pub fn parse(raw: &str) -> Result<Self, anyhow::Error> {
+    match raw {
+      "off" => Ok(BassBoost::Off),
+      "low" => Ok(BassBoost::Low),
+      "high" => Ok(BassBoost::High),
+      b => Err(anyhow!("Unknown bass boost {b}")),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      BassBoost::Off => "off",
+      BassBoost::Low => "low",
+      BassBoost::High => "high",
+    }
+  }
+
+  fn gain_db(&self) -> f64 {
+    match self {
+      BassBoost::Off => 0.0,
+      BassBoost::Low => 6.0,
+      BassBoost::High => 12.0,
+    }
+  }
+}
+
+/// Processing applied to tracks as they're decoded, before songbird sees them
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct AudioFilters {
+  /// Pull every track to the same loudness
+  pub normalize: bool,
+  pub bass_boost: BassBoost,
+  /// Percent, 100 being the source's own speed. Pitch moves with it.
+  pub speed: u32,
+}
+
+impl Default for AudioFilters {
+  fn default() -> Self {
+    Self {
+      normalize: false,
+      bass_boost: BassBoost::Off,
+      speed: 100,
+    }
+  }
+}
+
+impl AudioFilters {
+  /// Wraps the source so it plays through these filters, if there's anything to do
+  pub fn apply(&self, source: Box<dyn Compose>) -> Input {
+    if *self == Self::default() {
+      return Input::Lazy(source);
+    }
+    Input::Lazy(Box::new(Filtered {
+      inner: source,
+      filters: self.clone(),
+    }))
+  }
+
+  pub fn describe(&self) -> String {
+    MessageBuilder::new()
+      .push_bold_line("Audio filters:")
+      .push_line(format!(
+        "Normalize: {}",
+        if self.normalize { "on" } else { "off" }
+      ))
+      .push_line(format!("Bass boost: {}", self.bass_boost.name()))
+      .push(format!("Speed: {}%", self.speed))
+      .build()
+  }
+}
+
+/// A source decoded and run through the guild's filters on our side, handed to
+/// songbird as raw PCM
+struct Filtered {
+  inner: Box<dyn Compose>,
+  filters: AudioFilters,
+}
+
+#[async_trait]
+impl Compose for Filtered {
+  fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+    Err(AudioStreamError::Unsupported)
+  }
+
+  async fn create_async(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+    let stream = self.inner.create_async().await?;
+    let filters = self.filters.clone();
+    // Probing reads from the stream, which blocks
+    tokio::task::spawn_blocking(move || start_decoding(stream, filters))
+      .await
+      .map_err(|e| AudioStreamError::Fail(Box::new(e)))?
+      .map_err(|e| AudioStreamError::Fail(e.into()))
+  }
+
+  fn should_create_async(&self) -> bool {
+    true
+  }
+
+  async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+    self.inner.aux_metadata().await
+  }
+}
+
+fn start_decoding(
+  stream: AudioStream<Box<dyn MediaSource>>,
+  filters: AudioFilters,
+) -> Result<AudioStream<Box<dyn MediaSource>>, anyhow::Error> {
+  let seekable = stream.input.is_seekable();
+  let probed = get_probe().format(
+    &stream.hint.unwrap_or_default(),
+    MediaSourceStream::new(stream.input, Default::default()),
+    &FormatOptions::default(),
+    &MetadataOptions::default(),
+  )?;
+  let format = probed.format;
+  let track = format
+    .default_track()
+    .ok_or_else(|| anyhow!("Source has no audio track"))?;
+  let rate = track
+    .codec_params
+    .sample_rate
+    .ok_or_else(|| anyhow!("Source has no sample rate"))?;
+  let channels = track
+    .codec_params
+    .channels
+    .ok_or_else(|| anyhow!("Source has no channel layout"))?
+    .count();
+  let decoder = get_codec_registry().make(&track.codec_params, &DecoderOptions::default())?;
+  let chain = FilterChain::new(&filters, rate, channels);
+  let source = Decoding {
+    track_id: track.id,
+    time_base: track.codec_params.time_base,
+    rate,
+    format,
+    decoder,
+  };
+
+  let (tx, rx) = sync_channel(BUFFERED_PACKETS);
+  let (seek_tx, seek_rx) = channel();
+  thread::spawn(move || decode(source, chain, tx, seek_rx));
+  // Claiming a different rate than we decoded at is all it takes to change speed,
+  // songbird resamples it back down to what Discord wants
+  let claimed_rate = rate * filters.speed / 100;
+  info!("Filtering {}Hz x{} source, {:?}", rate, channels, filters);
+  Ok(AudioStream {
+    input: Box::new(RawAdapter::new(
+      PcmReceiver::new(rx, seek_tx, channels, seekable),
+      claimed_rate,
+      channels as u32,
+    )),
+    hint: None,
+  })
+}
+
+/// What the decode thread hands over to playback
+enum Decoded {
+  Pcm(Vec<u8>),
+  /// Everything after this is from the position asked for
+  Seeked(Result<(), String>),
+  End,
+}
+
+/// The source as the decode thread reads it
+struct Decoding {
+  format: Box<dyn FormatReader>,
+  decoder: Box<dyn Decoder>,
+  track_id: u32,
+  time_base: Option<TimeBase>,
+  rate: u32,
+}
+
+impl Decoding {
+  /// Moves to the given frame, saying how many frames the decoder will produce
+  /// before getting there since formats can only land on packet boundaries
+  fn seek(&mut self, frame: u64) -> Result<usize, SymphError> {
+    let seeked = self.format.seek(
+      SeekMode::Accurate,
+      SeekTo::Time {
+        time: Time::from(frame as f64 / self.rate as f64),
+        track_id: Some(self.track_id),
+      },
+    )?;
+    self.decoder.reset();
+    let early = seeked.required_ts.saturating_sub(seeked.actual_ts);
+    Ok(match self.time_base {
+      Some(tb) => {
+        let t = tb.calc_time(early);
+        ((t.seconds as f64 + t.frac) * self.rate as f64).round() as usize
+      }
+      None => early as usize,
+    })
+  }
+}
+
+/// Pumps packets through the filters until the track is dropped. Reaching the
+/// end of the source just waits around in case playback seeks back.
+fn decode(
+  mut source: Decoding,
+  mut chain: FilterChain,
+  tx: SyncSender<Decoded>,
+  seeks: Receiver<u64>,
+) {
+  let mut buf: Option<SampleBuffer<f32>> = None;
+  let mut ended = false;
+  // Frames still to throw away after a seek landed short
+  let mut skip = 0;
+  loop {
+    let seek = match ended {
+      true => match seeks.recv() {
+        Ok(frame) => Some(frame),
+        Err(_) => return,
+      },
+      false => match seeks.try_recv() {
+        Ok(frame) => Some(frame),
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => return,
+      },
+    };
+    if let Some(frame) = seek {
+      let res = source.seek(frame).map(|early| {
+        skip = early;
+        ended = false;
+      });
+      if tx
+        .send(Decoded::Seeked(res.map_err(|e| e.to_string())))
+        .is_err()
+      {
+        return;
+      }
+      continue;
+    }
+
+    let packet = match source.format.next_packet() {
+      Ok(p) => p,
+      Err(e) => {
+        if !matches!(&e, SymphError::IoError(io) if io.kind() == ErrorKind::UnexpectedEof) {
+          error!("Stopped reading filtered source: {}", e);
+        }
+        ended = true;
+        if tx.send(Decoded::End).is_err() {
+          return;
+        }
+        continue;
+      }
+    };
+    if packet.track_id() != source.track_id {
+      continue;
+    }
+    let decoded = match source.decoder.decode(&packet) {
+      Ok(d) => d,
+      // A bad packet is a blip, not the end of the track
+      Err(SymphError::DecodeError(_)) => continue,
+      Err(e) => {
+        error!("Stopped decoding filtered source: {}", e);
+        ended = true;
+        if tx.send(Decoded::End).is_err() {
+          return;
+        }
+        continue;
+      }
+    };
+    let samples =
+      buf.get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+    if samples.capacity() < decoded.capacity() * decoded.spec().channels.count() {
+      *samples = SampleBuffer::new(decoded.capacity() as u64, *decoded.spec());
+    }
+    samples.copy_interleaved_ref(decoded);
+    let skipped = skip.min(samples.samples().len() / chain.channels);
+    skip -= skipped;
+    let mut pcm = samples.samples()[skipped * chain.channels..].to_vec();
+    if pcm.is_empty() {
+      continue;
+    }
+    chain.process(&mut pcm);
+    let bytes = pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+    if tx.send(Decoded::Pcm(bytes)).is_err() {
+      // Track was skipped or stopped
+      return;
+    }
+  }
+}
+
+/// Per track filter state, built from the guild's settings when the track starts
+struct FilterChain {
+  channels: usize,
+  bass: Option<Vec<Biquad>>,
+  normalizer: Option<Normalizer>,
+}
+
+impl FilterChain {
+  fn new(filters: &AudioFilters, rate: u32, channels: usize) -> Self {
+    let bass = match filters.bass_boost {
+      BassBoost::Off => None,
+      b => Some(
+        (0..channels)
+          .map(|_| Biquad::low_shelf(rate as f64, BASS_FREQ, b.gain_db(), FRAC_1_SQRT_2))
+          .collect(),
+      ),
+    };
+    Self {
+      channels,
+      bass,
+      normalizer: filters.normalize.then(|| Normalizer::new(rate, channels)),
+    }
+  }
+
+  fn process(&mut self, samples: &mut [f32]) {
+    if let Some(bass) = &mut self.bass {
+      for frame in samples.chunks_exact_mut(self.channels) {
+        for (sample, shelf) in frame.iter_mut().zip(bass.iter_mut()) {
+          *sample = shelf.process(*sample as f64) as f32;
+        }
+      }
+    }
+    if let Some(normalizer) = &mut self.normalizer {
+      normalizer.process(samples);
+    }
+  }
+}
+
+/// Reading end of the decode thread, blocking until it has more for us much like
+/// songbird's own adapters do for network streams. Seeking asks the thread to
+/// seek the source, so it works whenever the source itself can.
+struct PcmReceiver {
+  rx: Mutex<Receiver<Decoded>>,
+  seeks: Sender<u64>,
+  frame_bytes: u64,
+  seekable: bool,
+  pending: Vec<u8>,
+  offset: usize,
+  ended: bool,
+}
+
+impl PcmReceiver {
+  fn new(rx: Receiver<Decoded>, seeks: Sender<u64>, channels: usize, seekable: bool) -> Self {
+    Self {
+      rx: Mutex::new(rx),
+      seeks,
+      frame_bytes: (channels * size_of::<f32>()) as u64,
+      seekable,
+      pending: vec![],
+      offset: 0,
+      ended: false,
+    }
+  }
+}
+
+impl Read for PcmReceiver {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    while self.offset == self.pending.len() {
+      if self.ended {
+        return Ok(0);
+      }
+      let rx = self.rx.get_mut().map_err(|_| ErrorKind::Other)?;
+      match rx.recv() {
+        Ok(Decoded::Pcm(chunk)) => {
+          self.pending = chunk;
+          self.offset = 0;
+        }
+        Ok(Decoded::Seeked(_)) => {}
+        // Decoder finished, that's the end of the track unless we seek back
+        Ok(Decoded::End) | Err(_) => self.ended = true,
+      }
+    }
+    let n = buf.len().min(self.pending.len() - self.offset);
+    buf[..n].copy_from_slice(&self.pending[self.offset..self.offset + n]);
+    self.offset += n;
+    Ok(n)
+  }
+}
+
+impl Seek for PcmReceiver {
+  fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+    let SeekFrom::Start(pos) = pos else {
+      return Err(ErrorKind::Unsupported.into());
+    };
+    if !self.seekable {
+      return Err(ErrorKind::Unsupported.into());
+    }
+    // Rewinding asks for 0, anything else comes with the raw header counted in
+    let frame = pos.saturating_sub(RAW_HEADER_LEN) / self.frame_bytes;
+    self
+      .seeks
+      .send(frame)
+      .map_err(|_| IoError::from(ErrorKind::BrokenPipe))?;
+    // Whatever was decoded before the thread got to the seek is stale
+    let rx = self.rx.get_mut().map_err(|_| ErrorKind::Other)?;
+    loop {
+      match rx.recv() {
+        Ok(Decoded::Seeked(Ok(()))) => break,
+        Ok(Decoded::Seeked(Err(e))) => return Err(IoError::other(e)),
+        Ok(_) => continue,
+        Err(_) => return Err(ErrorKind::BrokenPipe.into()),
+      }
+    }
+    self.pending.clear();
+    self.offset = 0;
+    self.ended = false;
+    Ok(frame * self.frame_bytes)
+  }
+}
+
+impl MediaSource for PcmReceiver {
+  fn is_seekable(&self) -> bool {
+    self.seekable
+  }
+
+  fn byte_len(&self) -> Option<u64> {
+    None
+  }
+}
+
+/// Tunes the guild's filters. They kick in from the next track that starts.
+#[derive(new)]
+pub struct Filter {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Filter {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    let mut settings = VoiceSettings::load(&self.persistence, guild_id);
+    let filters = &mut settings.filters;
+    let (name, args) = args.subcommand()?;
+    match name {
+      // 1 arg: enabled. Boolean
+      "normalize" => filters.normalize = args.opt_bool("enabled")?.unwrap_or(true),
+      // 1 arg: level. String choice, off|low|high
+      "bass" => filters.bass_boost = BassBoost::parse(args.str("level")?)?,
+      // 1 arg: percent. Integer, 50-200
+      "speed" => filters.speed = (*args.i64("percent")?).clamp(50, 200) as u32,
+      "reset" => *filters = AudioFilters::default(),
+      n => return Err(anyhow!("Unknown filter {n}")),
+    }
+    settings.save(&self.persistence, guild_id)?;
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(format!(
+          "{}\n*Kicks in from the next tune*",
+          settings.filters.describe()
+        )),
+      )
+      .await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pcm_receiver_reads_across_chunks() {
+    let (tx, rx) = sync_channel(4);
+    tx.send(Decoded::Pcm(vec![1, 2, 3])).unwrap();
+    tx.send(Decoded::Pcm(vec![4, 5])).unwrap();
+    tx.send(Decoded::End).unwrap();
+
+    let mut out = vec![];
+    PcmReceiver::new(rx, channel().0, 1, false)
+      .read_to_end(&mut out)
+      .unwrap();
+    assert_eq!(vec![1, 2, 3, 4, 5], out);
+  }
+
+  const RATE: u32 = 8_000;
+
+  /// A mono 16 bit wav whose every sample is its own frame number
+  fn ramp_wav(frames: u32) -> Vec<u8> {
+    let data_len = frames * 2;
+    let mut wav = vec![];
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&RATE.to_le_bytes());
+    wav.extend_from_slice(&(RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for i in 0..frames {
+      wav.extend_from_slice(&(i as i16).to_le_bytes());
+    }
+    wav
+  }
+
+  fn next_frame(stream: &mut Box<dyn MediaSource>) -> u32 {
+    let mut sample = [0u8; 4];
+    stream.read_exact(&mut sample).unwrap();
+    (f32::from_le_bytes(sample) * 32768.0).round() as u32
+  }
+
+  #[tokio::test]
+  async fn filtered_tracks_can_seek() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ramp.wav");
+    std::fs::write(&path, ramp_wav(RATE * 2)).unwrap();
+    let source = || Box::new(songbird::input::File::new(path.clone())) as Box<dyn Compose>;
+
+    // Nothing to do leaves the source to songbird
+    let Input::Lazy(mut untouched) = AudioFilters::default().apply(source()) else {
+      panic!("Sources should stay lazy");
+    };
+    let stream = untouched.create_async().await.unwrap();
+    assert!(stream.input.byte_len().is_some());
+
+    // Speed leaves the samples be, so they can be checked as they come out
+    let filters = AudioFilters {
+      speed: 150,
+      ..Default::default()
+    };
+    let Input::Lazy(mut filtered) = filters.apply(source()) else {
+      panic!("Sources should stay lazy");
+    };
+    let mut stream = filtered.create_async().await.unwrap().input;
+    assert!(stream.is_seekable());
+    let mut header = [0u8; RAW_HEADER_LEN as usize];
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(0, next_frame(&mut stream));
+    assert_eq!(1, next_frame(&mut stream));
+
+    // Same as songbird's raw reader asks for, with the header counted in
+    let target = RAW_HEADER_LEN + 4 * 12_000;
+    let seeked = tokio::task::spawn_blocking(move || {
+      let pos = stream.seek(SeekFrom::Start(target)).unwrap();
+      (pos, next_frame(&mut stream), stream)
+    })
+    .await
+    .unwrap();
+    assert_eq!((target, 12_000), (seeked.0, seeked.1));
+
+    // Past the end and back again
+    let mut stream = seeked.2;
+    let mut rest = vec![];
+    stream.read_to_end(&mut rest).unwrap();
+    assert_eq!(4 * (RATE as usize * 2 - 12_001), rest.len());
+    stream.rewind().unwrap();
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(0, next_frame(&mut stream));
+  }
+
+  #[test]
+  fn untouched_filters_pass_straight_through() {
+    let mut samples = vec![0.5, -0.5, 0.25, -0.25];
+    FilterChain::new(&AudioFilters::default(), 48_000, 2).process(&mut samples);
+    assert_eq!(vec![0.5, -0.5, 0.25, -0.25], samples);
+  }
+}
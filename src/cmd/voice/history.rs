@@ -0,0 +1,453 @@
+use super::{
+  connect_util::VoiceConnector,
+  disconnect::DisconnectMessage,
+  now_playing::format_time,
+  play::{track_metadata, ListMetadata},
+  sources::SourceKind,
+  SubCommandHandler,
+};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore, types::Usr};
+use anyhow::anyhow;
+use bincode::{Decode, Encode};
+use derive_new::new;
+use kitchen_sink::actor::ActorHandle;
+use serenity::{
+  all::{ButtonStyle, CommandInteraction, ComponentInteraction, GuildId},
+  async_trait,
+  builder::{
+    CreateActionRow, CreateAllowedMentions, CreateButton, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse,
+  },
+  client::Context,
+  prelude::Mutex,
+  utils::MessageBuilder,
+};
+use songbird::{events::TrackEvent, Call, Event, EventContext, EventHandler};
+use std::{
+  collections::HashMap,
+  sync::Arc,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+/// Oldest plays fall off past this, enough for a good few sessions
+const HISTORY_LIMIT: usize = 500;
+const BUTTON_PREFIX: &str = "history-page:";
+const PAGE_SIZE: usize = 10;
+const TOP_COUNT: usize = 10;
+const TOP_WINDOW: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct PlayedTrack {
+  pub title: String,
+  pub url: String,
+  pub duration: Option<Duration>,
+  pub requested_by: Option<Usr>,
+  pub played_at: SystemTime,
+  pub source: SourceKind,
+}
+
+/// Everything a guild has played lately, oldest first
+#[derive(Clone, Debug, Default, Encode, Decode)]
+pub struct TrackHistory {
+  pub tracks: Vec<PlayedTrack>,
+}
+
+impl TrackHistory {
+  /// Loads the guild's history, starting afresh if there is none or it's unreadable
+  pub fn load(persistence: &PersistentStore, guild_id: GuildId) -> Self {
+    match persistence.voice_history().load(&guild_id) {
+      Ok(v) => v.unwrap_or_default(),
+      Err(e) => {
+        error!("Failed to load voice history for {}: {}", guild_id, e);
+        Self::default()
+      }
+    }
+  }
+
+  fn record(&mut self, track: PlayedTrack) {
+    self.tracks.push(track);
+    if self.tracks.len() > HISTORY_LIMIT {
+      let excess = self.tracks.len() - HISTORY_LIMIT;
+      self.tracks.drain(..excess);
+    }
+  }
+
+  /// The nth most recent play, 1 being the latest
+  fn recent(&self, n: usize) -> Option<&PlayedTrack> {
+    n.checked_sub(1)
+      .and_then(|idx| self.tracks.iter().rev().nth(idx))
+  }
+
+  /// Most played tracks since the given time, with how often each played.
+  /// Ties go to whichever played most recently.
+  fn top(&self, since: SystemTime, limit: usize) -> Vec<(&PlayedTrack, usize)> {
+    let mut counts: HashMap<&str, (&PlayedTrack, usize)> = HashMap::new();
+    for track in self.tracks.iter().filter(|t| t.played_at >= since) {
+      let entry = counts.entry(&track.url).or_insert((track, 0));
+      entry.0 = track;
+      entry.1 += 1;
+    }
+    let mut top: Vec<_> = counts.into_values().collect();
+    top.sort_by(|(a, a_plays), (b, b_plays)| {
+      b_plays
+        .cmp(a_plays)
+        .then_with(|| b.played_at.cmp(&a.played_at))
+    });
+    top.truncate(limit);
+    top
+  }
+}
+
+/// Notes down each track as it starts playing
+pub struct HistoryRecorder {
+  guild_id: GuildId,
+  persistence: Arc<PersistentStore>,
+  // Resuming from a pause plays the same track again, that's not a new play
+  last: Mutex<Option<Uuid>>,
+}
+
+impl HistoryRecorder {
+  pub async fn register(
+    guild_id: GuildId,
+    persistence: Arc<PersistentStore>,
+    call: &Arc<Mutex<Call>>,
+  ) {
+    call.lock().await.add_global_event(
+      Event::Track(TrackEvent::Play),
+      Self {
+        guild_id,
+        persistence,
+        last: Mutex::new(None),
+      },
+    );
+  }
+}
+
+#[async_trait]
+impl EventHandler for HistoryRecorder {
+  #[instrument(name = "HistoryRecorder", level = "DEBUG", skip(self, ctx))]
+  async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+    let EventContext::Track(tracks) = ctx else {
+      return None;
+    };
+    let mut last = self.last.lock().await;
+    let mut history = TrackHistory::load(&self.persistence, self.guild_id);
+    let mut changed = false;
+    for (_, trk) in tracks.iter() {
+      // Soundboard clips don't count
+      let Some(meta) = track_metadata(trk) else {
+        continue;
+      };
+      if *last == Some(trk.uuid()) {
+        continue;
+      }
+      *last = Some(trk.uuid());
+      history.record(PlayedTrack {
+        title: meta.title.clone(),
+        url: meta.url.clone(),
+        duration: meta.duration,
+        requested_by: meta.requested_by.clone(),
+        played_at: SystemTime::now(),
+        source: meta.source,
+      });
+      changed = true;
+    }
+    if changed {
+      if let Err(e) = self
+        .persistence
+        .voice_history()
+        .save(&self.guild_id, &history)
+      {
+        error!("Failed to save voice history for {}: {}", self.guild_id, e);
+      }
+    }
+    None
+  }
+}
+
+/// Pages back through what the guild has played
+#[derive(new)]
+pub struct History {
+  persistence: Arc<PersistentStore>,
+}
+
+impl History {
+  /// Flicks through the pages of the history. Returns Ok(false) if the
+  /// interaction isn't one of ours.
+  pub async fn msg_interact(
+    &self,
+    ctx: &Context,
+    itx: &ComponentInteraction,
+  ) -> Result<bool, anyhow::Error> {
+    let Some(page) = itx.data.custom_id.strip_prefix(BUTTON_PREFIX) else {
+      return Ok(false);
+    };
+    let page: usize = page.parse()?;
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    info!("Showing history page {}", page);
+
+    let history = TrackHistory::load(&self.persistence, guild_id);
+    let (content, components) = render_page(&history, page);
+    itx
+      .create_response(
+        &ctx.http,
+        CreateInteractionResponse::UpdateMessage(
+          CreateInteractionResponseMessage::new()
+            .content(content)
+            .components(components)
+            .allowed_mentions(CreateAllowedMentions::new()),
+        ),
+      )
+      .await?;
+    Ok(true)
+  }
+}
+
+#[async_trait]
+impl SubCommandHandler for History {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    _: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    let history = TrackHistory::load(&self.persistence, guild_id);
+    if history.tracks.is_empty() {
+      return Err(anyhow!("Nothing's been played yet"));
+    }
+    let (content, components) = render_page(&history, 0);
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new()
+          .content(content)
+          .components(components)
+          // Listing who asked for what shouldn't ping them all
+          .allowed_mentions(CreateAllowedMentions::new()),
+      )
+      .await?;
+    Ok(())
+  }
+}
+
+fn render_page(history: &TrackHistory, page: usize) -> (String, Vec<CreateActionRow>) {
+  let pages = history.tracks.len().div_ceil(PAGE_SIZE).max(1);
+  let page = page.min(pages - 1);
+
+  let mut bld = MessageBuilder::new();
+  bld.push_bold_line(format!("Recently Played ({}/{}):", page + 1, pages));
+  for (idx, track) in history
+    .tracks
+    .iter()
+    .rev()
+    .enumerate()
+    .skip(page * PAGE_SIZE)
+    .take(PAGE_SIZE)
+  {
+    bld
+      .push(format!("{}. ", idx + 1))
+      .push_safe(track.title.as_str());
+    if let Some(duration) = track.duration {
+      bld.push(" ").push_mono(format_time(duration));
+    }
+    if let Some(user) = &track.requested_by {
+      bld.push(format!(" · {user}"));
+    }
+    if let Ok(at) = track.played_at.duration_since(UNIX_EPOCH) {
+      bld.push(format!(" · <t:{}:R>", at.as_secs()));
+    }
+    bld.push_line("");
+  }
+  bld.push_italic("Use /play again with a number to hear one again");
+
+  let components = match pages {
+    1 => vec![],
+    _ => vec![CreateActionRow::Buttons(vec![
+      CreateButton::new(format!("{BUTTON_PREFIX}{}", page.saturating_sub(1)))
+        .label("Newer")
+        .style(ButtonStyle::Secondary)
+        .disabled(page == 0),
+      CreateButton::new(format!("{BUTTON_PREFIX}{}", page + 1))
+        .label("Older")
+        .style(ButtonStyle::Secondary)
+        .disabled(page + 1 >= pages),
+    ])],
+  };
+  (bld.build(), components)
+}
+
+/// Queues up something from the history again
+#[derive(new)]
+pub struct Again {
+  connector: VoiceConnector,
+  persistence: Arc<PersistentStore>,
+  disconnect: ActorHandle<DisconnectMessage>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Again {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    // 1 optional arg: n. Integer, min value 1, defaults to the latest
+    let n = args.opt_i64("n")?.unwrap_or(1).max(1) as usize;
+    let history = TrackHistory::load(&self.persistence, guild_id);
+    let track = history
+      .recent(n)
+      .ok_or_else(|| anyhow!("Only {} tracks in the history", history.tracks.len()))?;
+    let meta = ListMetadata {
+      title: track.title.clone(),
+      url: track.url.clone(),
+      duration: track.duration,
+      requested_by: None,
+      requested_at: None,
+      source: track.source,
+    };
+
+    let _ = self
+      .disconnect
+      .send(DisconnectMessage::Enqueue(guild_id))
+      .await;
+    let res = self
+      .connector
+      .enqueue_known(ctx, guild_id, itx.user.id, None, itx.channel_id, meta)
+      .await;
+    let _ = self
+      .disconnect
+      .send(DisconnectMessage::Dequeue(guild_id))
+      .await;
+    let len = res?;
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(
+          MessageBuilder::new()
+            .push_bold("Queued")
+            .push(format!(" ({len}) "))
+            .push_mono(&track.title)
+            .push(" again")
+            .build(),
+        ),
+      )
+      .await?;
+    Ok(())
+  }
+}
+
+/// What's been on repeat over the last week
+#[derive(new)]
+pub struct Top {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Top {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    _: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    let history = TrackHistory::load(&self.persistence, guild_id);
+    let top = history.top(SystemTime::now() - TOP_WINDOW, TOP_COUNT);
+    if top.is_empty() {
+      return Err(anyhow!("Nothing's been played this week"));
+    }
+    let mut bld = MessageBuilder::new();
+    bld.push_bold_line("Top tracks this week:");
+    for (idx, (track, plays)) in top.iter().enumerate() {
+      bld
+        .push(format!("{}. ", idx + 1))
+        .push_safe(track.title.as_str())
+        .push_line(format!(
+          " · {plays} play{}",
+          if *plays == 1 { "" } else { "s" }
+        ));
+    }
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(bld.build()),
+      )
+      .await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn played(url: &str, days_ago: u64) -> PlayedTrack {
+    PlayedTrack {
+      title: url.to_uppercase(),
+      url: url.to_string(),
+      duration: None,
+      requested_by: None,
+      played_at: SystemTime::now() - Duration::from_secs(days_ago * 24 * 60 * 60),
+      source: SourceKind::YouTube,
+    }
+  }
+
+  #[test]
+  fn history_keeps_the_latest() {
+    let mut history = TrackHistory::default();
+    for i in 0..HISTORY_LIMIT + 5 {
+      history.record(played(&i.to_string(), 0));
+    }
+    assert_eq!(HISTORY_LIMIT, history.tracks.len());
+    assert_eq!("5", history.tracks[0].url);
+    assert_eq!(
+      Some((HISTORY_LIMIT + 4).to_string()),
+      history.recent(1).map(|t| t.url.clone())
+    );
+    assert_eq!(
+      Some((HISTORY_LIMIT + 3).to_string()),
+      history.recent(2).map(|t| t.url.clone())
+    );
+    assert!(history.recent(0).is_none());
+    assert!(history.recent(HISTORY_LIMIT + 1).is_none());
+  }
+
+  #[test]
+  fn top_counts_plays_within_the_window() {
+    let history = TrackHistory {
+      tracks: vec![
+        played("old", 10),
+        played("old", 10),
+        played("old", 10),
+        played("a", 3),
+        played("b", 2),
+        played("a", 1),
+        played("c", 0),
+      ],
+    };
+    let week_ago = SystemTime::now() - TOP_WINDOW;
+    let top: Vec<_> = history
+      .top(week_ago, 2)
+      .into_iter()
+      .map(|(t, plays)| (t.url.as_str(), plays))
+      .collect();
+    assert_eq!(vec![("a", 2), ("c", 1)], top);
+  }
+}
@@ -1,14 +1,72 @@
-use super::{play::ListMetadata, SubCommandHandler};
+use super::{
+  now_playing::format_time,
+  play::{track_metadata, ListMetadata},
+  SubCommandHandler,
+};
 use crate::cmd::arg_util::Args;
 use anyhow::anyhow;
 use serenity::{
-  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  all::{ButtonStyle, CommandInteraction, ComponentInteraction, GuildId},
+  async_trait,
+  builder::{
+    CreateActionRow, CreateAllowedMentions, CreateButton, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse,
+  },
+  client::Context,
   utils::MessageBuilder,
 };
+use std::{sync::Arc, time::Duration};
+use tracing::info;
+
+const BUTTON_PREFIX: &str = "queue-page:";
+const PAGE_SIZE: usize = 10;
+
+/// One rendered page of the queue, along with how to get to its neighbours
+struct Page {
+  content: String,
+  components: Vec<CreateActionRow>,
+}
 
 #[derive(Default)]
 pub struct List {}
 
+impl List {
+  /// Flicks through the pages of a listed queue. Returns Ok(false) if the
+  /// interaction isn't one of ours.
+  pub async fn msg_interact(
+    &self,
+    ctx: &Context,
+    itx: &ComponentInteraction,
+  ) -> Result<bool, anyhow::Error> {
+    let Some(page) = itx.data.custom_id.strip_prefix(BUTTON_PREFIX) else {
+      return Ok(false);
+    };
+    let page: usize = page.parse()?;
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    info!("Showing queue page {}", page);
+
+    let msg = match render(ctx, guild_id, page).await {
+      Ok(page) => CreateInteractionResponseMessage::new()
+        .content(page.content)
+        .components(page.components),
+      Err(e) => CreateInteractionResponseMessage::new()
+        .content(format!("{e}"))
+        .components(vec![]),
+    };
+    itx
+      .create_response(
+        &ctx.http,
+        CreateInteractionResponse::UpdateMessage(
+          msg.allowed_mentions(CreateAllowedMentions::new()),
+        ),
+      )
+      .await?;
+    Ok(true)
+  }
+}
+
 #[async_trait]
 impl SubCommandHandler for List {
   async fn handle(
@@ -21,38 +79,143 @@ impl SubCommandHandler for List {
       .guild_id
       .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
 
-    let manager = songbird::get(ctx)
-      .await
-      .expect("Songbird Voice client placed in at initialisation.")
-      .clone();
-
-    let handler_lock = manager
-      .get(guild_id)
-      .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
-    let handler = handler_lock.lock().await;
-
-    let mut bld = MessageBuilder::new();
-    bld.push_bold_line("Current Queue:");
-    let mut body = String::new();
-    for (idx, trk) in handler.queue().current_queue().iter().enumerate() {
-      // Retrieve custom metadata using songbird 0.5.0 API
-      // Note: data() will panic if type doesn't match, so we use a simple fallback approach
-      let title = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        trk.data::<ListMetadata>().title.clone()
-      })) {
-        Ok(title) => title,
-        Err(_) => "<UNKNOWN TITLE>".to_string(),
-      };
-
-      body.push_str(&format!("{}. {}\n", idx + 1, title));
-    }
-
+    let page = render(ctx, guild_id, 0).await?;
     itx
       .edit_response(
         &ctx.http,
-        EditInteractionResponse::new().content(bld.push_codeblock(body, None).build()),
+        EditInteractionResponse::new()
+          .content(page.content)
+          .components(page.components)
+          // Listing who asked for what shouldn't ping them all
+          .allowed_mentions(CreateAllowedMentions::new()),
       )
       .await?;
     Ok(())
   }
 }
+
+async fn render(ctx: &Context, guild_id: GuildId, page: usize) -> Result<Page, anyhow::Error> {
+  let manager = songbird::get(ctx)
+    .await
+    .expect("Songbird Voice client placed in at initialisation.");
+  let handler_lock = manager
+    .get(guild_id)
+    .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
+  let queue = handler_lock.lock().await.queue().clone();
+
+  let position = match queue.current() {
+    Some(current) => current
+      .get_info()
+      .await
+      .map(|i| i.position)
+      .unwrap_or_default(),
+    None => Duration::ZERO,
+  };
+  let tracks: Vec<Option<Arc<ListMetadata>>> =
+    queue.current_queue().iter().map(track_metadata).collect();
+  if tracks.is_empty() {
+    return Err(anyhow!("Nothing queued up"));
+  }
+  let pages = tracks.len().div_ceil(PAGE_SIZE);
+  let page = page.min(pages - 1);
+
+  let mut bld = MessageBuilder::new();
+  bld.push_bold_line(format!("Current Queue ({}/{}):", page + 1, pages));
+  for (idx, meta) in tracks
+    .iter()
+    .enumerate()
+    .skip(page * PAGE_SIZE)
+    .take(PAGE_SIZE)
+  {
+    bld.push(format!("{}. ", idx + 1));
+    match meta {
+      Some(meta) => {
+        bld.push_safe(meta.title.as_str());
+        if let Some(duration) = meta.duration {
+          bld.push(" ").push_mono(format_time(duration));
+        }
+        if let Some(user) = &meta.requested_by {
+          bld.push(format!(" · {user}"));
+        }
+      }
+      None => {
+        bld.push("<UNKNOWN TITLE>");
+      }
+    }
+    bld.push_line("");
+  }
+  let durations: Vec<_> = tracks
+    .iter()
+    .map(|m| m.as_ref().and_then(|m| m.duration))
+    .collect();
+  let (left, exact) = remaining(&durations, position);
+  bld.push_italic(format!(
+    "{}{} remaining across {} tracks",
+    if exact { "" } else { "At least " },
+    format_time(left),
+    tracks.len()
+  ));
+
+  Ok(Page {
+    content: bld.build(),
+    components: buttons(page, pages),
+  })
+}
+
+fn buttons(page: usize, pages: usize) -> Vec<CreateActionRow> {
+  if pages <= 1 {
+    return vec![];
+  }
+  vec![CreateActionRow::Buttons(vec![
+    CreateButton::new(format!("{BUTTON_PREFIX}{}", page.saturating_sub(1)))
+      .label("Previous")
+      .style(ButtonStyle::Secondary)
+      .disabled(page == 0),
+    CreateButton::new(format!("{BUTTON_PREFIX}{}", page + 1))
+      .label("Next")
+      .style(ButtonStyle::Secondary)
+      .disabled(page + 1 >= pages),
+  ])]
+}
+
+/// Time left in the queue given how far into the first track we are. The flag
+/// is false when some tracks don't know how long they are, e.g. livestreams.
+fn remaining(durations: &[Option<Duration>], position: Duration) -> (Duration, bool) {
+  let total: Duration = durations.iter().flatten().sum();
+  let played = match durations.first() {
+    Some(Some(first)) => position.min(*first),
+    _ => Duration::ZERO,
+  };
+  (total - played, durations.iter().all(Option::is_some))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn remaining_takes_off_what_has_played() {
+    let durations = [
+      Some(Duration::from_secs(100)),
+      Some(Duration::from_secs(60)),
+    ];
+    assert_eq!(
+      (Duration::from_secs(130), true),
+      remaining(&durations, Duration::from_secs(30))
+    );
+    // Looping tracks can report positions beyond their length
+    assert_eq!(
+      (Duration::from_secs(60), true),
+      remaining(&durations, Duration::from_secs(500))
+    );
+  }
+
+  #[test]
+  fn remaining_flags_unknown_durations() {
+    let durations = [None, Some(Duration::from_secs(60))];
+    assert_eq!(
+      (Duration::from_secs(60), false),
+      remaining(&durations, Duration::from_secs(30))
+    );
+  }
+}
@@ -0,0 +1,154 @@
+use super::{
+  play::{enqueue_lazy, track_metadata},
+  settings::{LoopMode, VoiceSettings},
+  sources::Sources,
+  SubCommandHandler,
+};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::{CommandInteraction, GuildId},
+  async_trait,
+  builder::EditInteractionResponse,
+  client::Context,
+  prelude::Mutex,
+};
+use songbird::{events::TrackEvent, tracks::PlayMode, Call, Event, EventContext, EventHandler};
+use std::sync::{Arc, Weak};
+use tracing::{info, instrument};
+
+#[derive(new)]
+pub struct Loop {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Loop {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    // 1 arg: mode. String choice, off|track|queue
+    let mode = match args.str("mode")? {
+      "off" => LoopMode::Off,
+      "track" => LoopMode::Track,
+      "queue" => LoopMode::Queue,
+      m => return Err(anyhow!("Unknown loop mode {m}")),
+    };
+
+    let mut settings = VoiceSettings::load(&self.persistence, guild_id);
+    settings.loop_mode = mode;
+    settings.save(&self.persistence, guild_id)?;
+
+    // Later tracks are picked up by the looper, the current one we do by hand
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+    if let Some(handler_lock) = manager.get(guild_id) {
+      if let Some(current) = handler_lock.lock().await.queue().current() {
+        let _ = match mode {
+          LoopMode::Track => current.enable_loop(),
+          _ => current.disable_loop(),
+        };
+      }
+    }
+
+    let content = match mode {
+      LoopMode::Off => "No more looping",
+      LoopMode::Track => "Looping this track",
+      LoopMode::Queue => "Looping the whole queue",
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+      .await?;
+    Ok(())
+  }
+}
+
+/// Applies the guild's loop mode as tracks come and go: looping each track as
+/// it starts, or sending finished tracks to the back of the queue
+pub struct QueueLooper {
+  guild_id: GuildId,
+  // Starts and ends both arrive as a list of tracks, so we can't tell them apart by context
+  on_end: bool,
+  // Weak since the call owns this handler
+  call: Weak<Mutex<Call>>,
+  sources: Sources,
+  persistence: Arc<PersistentStore>,
+}
+
+impl QueueLooper {
+  pub async fn register(
+    guild_id: GuildId,
+    sources: Sources,
+    persistence: Arc<PersistentStore>,
+    call: &Arc<Mutex<Call>>,
+  ) {
+    let mut call_lock = call.lock().await;
+    call_lock.add_global_event(
+      Event::Track(TrackEvent::Play),
+      Self {
+        guild_id,
+        on_end: false,
+        call: Arc::downgrade(call),
+        sources: sources.clone(),
+        persistence: persistence.clone(),
+      },
+    );
+    call_lock.add_global_event(
+      Event::Track(TrackEvent::End),
+      Self {
+        guild_id,
+        on_end: true,
+        call: Arc::downgrade(call),
+        sources,
+        persistence,
+      },
+    );
+  }
+}
+
+#[async_trait]
+impl EventHandler for QueueLooper {
+  #[instrument(name = "QueueLooper", level = "DEBUG", skip(self, ctx))]
+  async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+    let EventContext::Track(tracks) = ctx else {
+      return None;
+    };
+    let mode = VoiceSettings::load(&self.persistence, self.guild_id).loop_mode;
+    match (mode, self.on_end) {
+      (LoopMode::Track, false) => {
+        // Soundboard clips play over the top of the queue, leave those be
+        for (_, trk) in tracks.iter().filter(|(_, t)| track_metadata(t).is_some()) {
+          let _ = trk.enable_loop();
+        }
+      }
+      (LoopMode::Queue, true) => {
+        let call = self.call.upgrade()?;
+        let mut handler = call.lock().await;
+        let settings = VoiceSettings::load(&self.persistence, self.guild_id);
+        // Only tracks that played out, skipping or stopping means they're done with it
+        for (state, trk) in tracks.iter() {
+          if !matches!(state.playing, PlayMode::End) {
+            continue;
+          }
+          let Some(meta) = track_metadata(trk) else {
+            continue;
+          };
+          info!("Looping {} back onto the queue", meta.title);
+          let input = settings.filters.apply(self.sources.source(&meta));
+          enqueue_lazy(&mut handler, input, (*meta).clone(), settings.gain());
+        }
+      }
+      _ => {}
+    }
+    None
+  }
+}
@@ -1,53 +1,198 @@
+mod clear;
 mod connect_util;
+mod default_channel;
+mod disconnect;
+mod dsp;
+mod fair;
+mod filters;
+mod history;
 mod list;
+mod looping;
+mod moving;
+mod now_playing;
+mod pause;
+mod picker;
 mod play;
+mod playlist;
+mod playnext;
+mod remove;
 mod reorder;
+mod resume;
+mod rules;
+mod seek;
+mod settings;
 mod shuffle;
 mod skip;
+mod soundboard;
+mod sources;
 mod stop;
+mod volume;
+mod vote_skip;
 
-use self::connect_util::{DisconnectActor, DisconnectMessage};
+use self::{
+  connect_util::VoiceConnector,
+  disconnect::{DisconnectActor, DisconnectMessage},
+};
 use super::arg_util::Args;
 use super::{AppInteractor, SubCommandHandler};
-use crate::{config::Config, emoji::EmojiLookup};
+use crate::{config::Config, emoji::EmojiLookup, persistence::PersistentStore};
+use clear::Clear;
+use default_channel::DefaultChannel;
+pub use disconnect::ListenerWatch;
+pub use filters::BassBoost;
+use filters::Filter;
+pub use history::TrackHistory;
+use history::{Again, History, Top};
 use kitchen_sink::{actor::ActorHandle, shutdown::ShutdownCoordinator};
 use list::*;
+use looping::Loop;
+use moving::Move;
+use now_playing::NowPlaying;
+use pause::Pause;
+use picker::Picker;
+pub use play::ListMetadata;
 use play::*;
+use playnext::PlayNext;
+use remove::Remove;
 use reorder::*;
-use serenity::all::{CommandOptionType, CommandType, CreateCommand, CreateCommandOption};
+use reqwest::Client;
+pub use resume::{Resumer, SavedQueue};
+use rules::Rules;
+use seek::Seek;
+use serenity::all::{
+  ChannelType, CommandOptionType, CommandType, CreateCommand, CreateCommandOption,
+};
 use serenity::builder::{
   CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
 };
-use serenity::{all::CommandInteraction, async_trait, client::Context};
+use serenity::{
+  all::{CommandInteraction, ComponentInteraction},
+  async_trait,
+  client::Context,
+};
+pub use settings::VoiceSettings;
 use shuffle::*;
 use skip::*;
+pub use soundboard::{SoundLibrary, Soundboard};
+use sources::Sources;
+use std::{path::PathBuf, sync::Arc};
 use stop::*;
 use tracing::{error, instrument};
+use volume::Volume;
+use vote_skip::SkipVotes;
 
 const NAME: &str = "play";
 
 pub struct Voice {
+  connector: VoiceConnector,
+  listener_watch: ListenerWatch,
+  resumer: Arc<Resumer>,
+  now_playing: Arc<NowPlaying>,
+  picker: Arc<Picker>,
   play: Play,
   stop: Stop,
   skip: Skip,
   shuffle: Shuffle,
   list: List,
   reorder: Reorder,
+  remove: Remove,
+  clear: Clear,
+  playnext: PlayNext,
+  volume: Volume,
+  pause: Pause,
+  resume: Pause,
+  seek: Seek,
+  looping: Loop,
+  rules: Rules,
+  filter: Filter,
+  history: History,
+  again: Again,
+  top: Top,
+  moving: Move,
+  default_channel: DefaultChannel,
 }
 
 impl Voice {
-  pub fn new(config: Config, emoji: EmojiLookup, shutdown: &mut ShutdownCoordinator) -> Self {
-    let disconnect =
-      ActorHandle::<DisconnectMessage>::spawn(|r, _| Box::new(DisconnectActor::new(r)), shutdown);
+  pub fn new(
+    config: Config,
+    emoji: EmojiLookup,
+    http: Client,
+    persistence: Arc<PersistentStore>,
+    shutdown: &mut ShutdownCoordinator,
+  ) -> Self {
+    let timeout = config.voice_channel_timeout;
+    let disconnect = ActorHandle::<DisconnectMessage>::spawn(
+      |r, _| Box::new(DisconnectActor::new(r, timeout)),
+      shutdown,
+    );
+    let votes = Arc::new(SkipVotes::new(persistence.clone()));
+    let now_playing = Arc::new(NowPlaying::new(
+      persistence.clone(),
+      disconnect.clone(),
+      votes.clone(),
+    ));
+    let connector = VoiceConnector::new(
+      emoji.clone(),
+      persistence.clone(),
+      now_playing.clone(),
+      disconnect.clone(),
+      Sources::new(http, PathBuf::from(&config.music_dir)),
+    );
+    let resumer = Arc::new(Resumer::new(persistence.clone(), connector.clone()));
+    let picker = Arc::new(Picker::new(connector.clone(), disconnect.clone()));
     Self {
-      play: Play::new(config, emoji.clone(), disconnect.clone()),
+      connector: connector.clone(),
+      listener_watch: ListenerWatch::new(disconnect.clone()),
+      resumer: resumer.clone(),
+      now_playing,
+      picker: picker.clone(),
+      history: History::new(persistence.clone()),
+      again: Again::new(connector.clone(), persistence.clone(), disconnect.clone()),
+      top: Top::new(persistence.clone()),
+      moving: Move::new(connector.clone()),
+      default_channel: DefaultChannel::new(persistence.clone()),
+      play: Play::new(
+        config.clone(),
+        emoji.clone(),
+        connector,
+        resumer,
+        picker,
+        disconnect.clone(),
+      ),
       stop: Stop::new(disconnect),
-      skip: Skip::new(emoji.clone()),
+      skip: Skip::new(emoji.clone(), votes),
       shuffle: Shuffle::default(),
       list: List::default(),
       reorder: Reorder::new(emoji),
+      remove: Remove::new(persistence.clone()),
+      clear: Clear::new(persistence.clone()),
+      playnext: PlayNext::new(persistence.clone()),
+      volume: Volume::new(persistence.clone()),
+      pause: Pause::new(false),
+      resume: Pause::new(true),
+      seek: Seek::default(),
+      looping: Loop::new(persistence.clone()),
+      rules: Rules::new(persistence.clone()),
+      filter: Filter::new(persistence),
     }
   }
+
+  pub fn connector(&self) -> VoiceConnector {
+    self.connector.clone()
+  }
+
+  pub fn resumer(&self) -> Arc<Resumer> {
+    self.resumer.clone()
+  }
+
+  pub fn listener_watch(&self) -> ListenerWatch {
+    self.listener_watch.clone()
+  }
+}
+
+fn voice_channel_option(description: &str) -> CreateCommandOption {
+  CreateCommandOption::new(CommandOptionType::Channel, "channel", description)
+    .channel_types(vec![ChannelType::Voice, ChannelType::Stage])
 }
 
 #[async_trait]
@@ -66,7 +211,24 @@ impl AppInteractor for Voice {
               "Link or search on YT",
             )
             .required(true),
-          ),
+          )
+          .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "pick",
+            "Choose from the top search results",
+          ))
+          .add_sub_option(
+            CreateCommandOption::new(
+              CommandOptionType::String,
+              "source",
+              "Where it's from, if not obvious from the link",
+            )
+            .add_string_choice("youtube", "youtube")
+            .add_string_choice("http", "http")
+            .add_string_choice("file", "file")
+            .add_string_choice("radio", "radio"),
+          )
+          .add_sub_option(voice_channel_option("Where to play, if not where you are")),
       )
       .add_option(CreateCommandOption::new(
         CommandOptionType::SubCommand,
@@ -104,6 +266,194 @@ impl AppInteractor for Voice {
             .required(true)
             .min_int_value(1),
         ),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "remove",
+          "Binkies will forget about some tunes",
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::String,
+            "range",
+            "Position to remove, or a range like 3-5",
+          )
+          .required(true),
+        ),
+      )
+      .add_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "clear",
+        "Binkies will forget everything but the current tune",
+      ))
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "playnext",
+          "Move the given item to play next",
+        )
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::Integer, "position", "Item to move")
+            .required(true)
+            .min_int_value(2),
+        ),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "volume",
+          "Tell Binkies how loud to scream",
+        )
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::Integer, "volume", "Percent, 0-200")
+            .required(true)
+            .min_int_value(0)
+            .max_int_value(200),
+        ),
+      )
+      .add_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "pause",
+        "Binkies holds his breath",
+      ))
+      .add_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "resume",
+        "Binkies lets it all out again",
+      ))
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "seek",
+          "Jump to a point in the current tune",
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::String,
+            "timestamp",
+            "Where to jump to, like 1:23",
+          )
+          .required(true),
+        ),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "loop",
+          "Binkies will scream it again",
+        )
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::String, "mode", "What to loop")
+            .required(true)
+            .add_string_choice("off", "off")
+            .add_string_choice("track", "track")
+            .add_string_choice("queue", "queue"),
+        ),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "rules",
+          "Keep the queue civil",
+        )
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::Boolean,
+          "fair_queue",
+          "Take turns by who asked",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::Boolean,
+          "vote_skip",
+          "Skipping someone else's tune takes a majority",
+        )),
+      )
+      .add_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "history",
+        "Binkies will recall what he's screamed lately",
+      ))
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "again",
+          "Binkies will scream an old favourite",
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::Integer,
+            "n",
+            "How far back, 1 being the last tune",
+          )
+          .min_int_value(1),
+        ),
+      )
+      .add_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "top",
+        "Binkies' greatest hits this week",
+      ))
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "move",
+          "Drag Binkies somewhere else, tunes and all",
+        )
+        .add_sub_option(voice_channel_option("Where to, if not where you are")),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "channel",
+          "Where Binkies screams when you're not in voice",
+        )
+        .add_sub_option(voice_channel_option("Leave out to forget the default")),
+      )
+      .add_option(
+        CreateCommandOption::new(
+          CommandOptionType::SubCommandGroup,
+          "filter",
+          "Binkies will fiddle with the knobs",
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "normalize",
+            "Even out loud and quiet tunes",
+          )
+          .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "On or off")
+              .required(true),
+          ),
+        )
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::SubCommand, "bass", "Boost the bass")
+            .add_sub_option(
+              CreateCommandOption::new(CommandOptionType::String, "level", "How much")
+                .required(true)
+                .add_string_choice("off", "off")
+                .add_string_choice("low", "low")
+                .add_string_choice("high", "high"),
+            ),
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "speed",
+            "Play faster or slower, pitch and all",
+          )
+          .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "percent", "Percent, 50-200")
+              .required(true)
+              .min_int_value(50)
+              .max_int_value(200),
+          ),
+        )
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "reset",
+          "Back to how the tunes came",
+        )),
       )]
   }
 
@@ -133,6 +483,8 @@ impl AppInteractor for Voice {
     let subopt = top_args.first().expect("Discord did not pass sub-opt");
     let args = match &subopt.value {
       serenity::all::ResolvedValue::SubCommand(c) => Args::from(c),
+      // Groups nest one level deeper, their handlers pick out the subcommand
+      serenity::all::ResolvedValue::SubCommandGroup(c) => Args::from(c),
       _ => unreachable!("Dev error - subopt was not subcommand"),
     };
 
@@ -142,7 +494,22 @@ impl AppInteractor for Voice {
       "skip" => self.skip.handle(ctx, itx, &args).await,
       "reorder" => self.reorder.handle(ctx, itx, &args).await,
       "list" => self.list.handle(ctx, itx, &args).await,
+      "remove" => self.remove.handle(ctx, itx, &args).await,
+      "clear" => self.clear.handle(ctx, itx, &args).await,
+      "playnext" => self.playnext.handle(ctx, itx, &args).await,
       "shuffle" => self.shuffle.handle(ctx, itx, &args).await,
+      "volume" => self.volume.handle(ctx, itx, &args).await,
+      "pause" => self.pause.handle(ctx, itx, &args).await,
+      "resume" => self.resume.handle(ctx, itx, &args).await,
+      "seek" => self.seek.handle(ctx, itx, &args).await,
+      "loop" => self.looping.handle(ctx, itx, &args).await,
+      "rules" => self.rules.handle(ctx, itx, &args).await,
+      "filter" => self.filter.handle(ctx, itx, &args).await,
+      "history" => self.history.handle(ctx, itx, &args).await,
+      "again" => self.again.handle(ctx, itx, &args).await,
+      "top" => self.top.handle(ctx, itx, &args).await,
+      "move" => self.moving.handle(ctx, itx, &args).await,
+      "channel" => self.default_channel.handle(ctx, itx, &args).await,
       _ => unreachable!(),
     } {
       error!("{:?}", e);
@@ -154,4 +521,24 @@ impl AppInteractor for Voice {
         .await;
     }
   }
+
+  #[instrument(name = "Voice", level = "INFO", skip(self, ctx, itx))]
+  async fn msg_interact(&self, ctx: &Context, itx: &ComponentInteraction) {
+    let mut res = self.resumer.msg_interact(ctx, itx).await;
+    if let Ok(false) = res {
+      res = self.now_playing.msg_interact(ctx, itx).await;
+    }
+    if let Ok(false) = res {
+      res = self.picker.msg_interact(ctx, itx).await;
+    }
+    if let Ok(false) = res {
+      res = self.list.msg_interact(ctx, itx).await;
+    }
+    if let Ok(false) = res {
+      res = self.history.msg_interact(ctx, itx).await;
+    }
+    if let Err(e) = res {
+      error!("{:?}", e);
+    }
+  }
 }
@@ -0,0 +1,61 @@
+use super::{connect_util::VoiceConnector, SubCommandHandler};
+use crate::{cmd::arg_util::Args, types::Chan};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  utils::MessageBuilder,
+};
+use tracing::info;
+
+/// Drags the bot, queue and all, over to another voice channel
+#[derive(new)]
+pub struct Move {
+  connector: VoiceConnector,
+}
+
+#[async_trait]
+impl SubCommandHandler for Move {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    let manager = songbird::get(ctx)
+      .await
+      .ok_or_else(|| anyhow!("Songbird Voice client not initialized"))?;
+    let in_voice = match manager.get(guild_id) {
+      Some(call) => call.lock().await.current_channel().is_some(),
+      None => false,
+    };
+    if !in_voice {
+      return Err(anyhow!("Not in voice, nothing to move"));
+    }
+
+    // 1 optional arg: channel. Defaults to wherever the caller is
+    let requested = args.opt_channel("channel")?.map(|c| c.id);
+    let channel_id = self
+      .connector
+      .target_channel(ctx, guild_id, itx.user.id, requested)?;
+    info!("Moving to {}", channel_id);
+    self.connector.move_to(ctx, guild_id, channel_id).await?;
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(
+          MessageBuilder::new()
+            .push_bold("Moved")
+            .push(format!(" to {}", Chan(channel_id)))
+            .build(),
+        ),
+      )
+      .await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,386 @@
+use super::{
+  disconnect::DisconnectMessage,
+  play::{track_metadata, ListMetadata},
+  shuffle::shuffle_queue,
+  vote_skip::{SkipOutcome, SkipVotes},
+};
+use crate::{cmd::component_util::respond, persistence::PersistentStore};
+use anyhow::anyhow;
+use kitchen_sink::actor::ActorHandle;
+use serenity::{
+  all::{ButtonStyle, ChannelId, ComponentInteraction, GuildId, MessageId},
+  async_trait,
+  builder::{
+    CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, EditMessage,
+  },
+  client::Context,
+  http::Http,
+  prelude::Mutex,
+  utils::MessageBuilder,
+};
+use songbird::{
+  events::TrackEvent,
+  tracks::{PlayMode, TrackQueue},
+  Call, Event, EventContext, EventHandler,
+};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+const BUTTON_PREFIX: &str = "np:";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const BAR_WIDTH: usize = 20;
+
+/// Everything needed to draw the now playing message
+struct View {
+  metadata: Arc<ListMetadata>,
+  position: Duration,
+  paused: bool,
+}
+
+/// Keeps a single "now playing" message per guild up to date as the queue moves along
+pub struct NowPlaying {
+  persistence: Arc<PersistentStore>,
+  disconnect: ActorHandle<DisconnectMessage>,
+  votes: Arc<SkipVotes>,
+  messages: Mutex<HashMap<GuildId, (ChannelId, MessageId)>>,
+}
+
+impl NowPlaying {
+  pub fn new(
+    persistence: Arc<PersistentStore>,
+    disconnect: ActorHandle<DisconnectMessage>,
+    votes: Arc<SkipVotes>,
+  ) -> Self {
+    Self {
+      persistence,
+      disconnect,
+      votes,
+      messages: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Posts a fresh message for the current track, clearing out the last one
+  async fn repost(&self, http: &Http, guild_id: GuildId, queue: &TrackQueue) {
+    let mut messages = self.messages.lock().await;
+    if let Some((channel, message)) = messages.remove(&guild_id) {
+      let _ = channel.delete_message(http, message).await;
+    }
+    let Some(view) = current_view(queue).await else {
+      return;
+    };
+    // The queue record knows where /play was last used, which is where people are looking
+    let channel = match self.persistence.voice_queues().load(&guild_id) {
+      Ok(Some(saved)) => *saved.text_channel,
+      Ok(None) => return,
+      Err(e) => {
+        error!("Failed to find now playing channel for {}: {}", guild_id, e);
+        return;
+      }
+    };
+    match channel
+      .send_message(
+        http,
+        CreateMessage::new()
+          .content(render(&view))
+          .components(controls(view.paused)),
+      )
+      .await
+    {
+      Ok(m) => {
+        messages.insert(guild_id, (channel, m.id));
+      }
+      Err(e) => error!("Failed to post now playing for {}: {}", guild_id, e),
+    }
+  }
+
+  /// Brings the existing message up to date, closing it out once nothing is playing
+  async fn refresh(&self, http: &Http, guild_id: GuildId, queue: &TrackQueue) {
+    let mut messages = self.messages.lock().await;
+    let Some((channel, message)) = messages.get(&guild_id).copied() else {
+      return;
+    };
+    let edit = match current_view(queue).await {
+      Some(view) => EditMessage::new()
+        .content(render(&view))
+        .components(controls(view.paused)),
+      None => {
+        messages.remove(&guild_id);
+        EditMessage::new()
+          .content("Nothing playing right now")
+          .components(vec![])
+      }
+    };
+    if let Err(e) = channel.edit_message(http, message, edit).await {
+      error!("Failed to update now playing for {}: {}", guild_id, e);
+    }
+  }
+
+  /// Handles the playback buttons, returning false if the interaction isn't ours
+  pub async fn msg_interact(
+    &self,
+    ctx: &Context,
+    itx: &ComponentInteraction,
+  ) -> Result<bool, anyhow::Error> {
+    let Some(action) = itx.data.custom_id.strip_prefix(BUTTON_PREFIX) else {
+      return Ok(false);
+    };
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+    let Some(call) = manager.get(guild_id) else {
+      respond(
+        ctx,
+        itx,
+        "I'm currently not in a voice channel".to_string(),
+        true,
+      )
+      .await?;
+      return Ok(true);
+    };
+    let queue = call.lock().await.queue().clone();
+
+    info!("Now playing button {} pressed", action);
+    match action {
+      "pause" => queue.pause()?,
+      "resume" => queue.resume()?,
+      "shuffle" => shuffle_queue(&queue),
+      "skip" => {
+        let outcome = self
+          .votes
+          .request_skip(ctx, guild_id, itx.user.id, &*call.lock().await)
+          .await?;
+        match outcome {
+          // The next track starting will post its own message
+          SkipOutcome::Skipped => itx.defer(&ctx.http).await?,
+          SkipOutcome::Pending { votes, needed } => {
+            let content = format!("Vote counted, {votes} of {needed} to skip");
+            respond(ctx, itx, content, false).await?
+          }
+          SkipOutcome::NotListening => {
+            let content = "Only people listening along get a vote".to_string();
+            respond(ctx, itx, content, false).await?
+          }
+        }
+        return Ok(true);
+      }
+      "stop" => {
+        self.messages.lock().await.remove(&guild_id);
+        let _ = self
+          .disconnect
+          .send(DisconnectMessage::Disconnect(guild_id, true))
+          .await;
+        let content = MessageBuilder::new()
+          .push("Stopped by ")
+          .mention(&itx.user)
+          .build();
+        respond(ctx, itx, content, true).await?;
+        return Ok(true);
+      }
+      _ => return Err(anyhow!("Unknown now playing action {action}")),
+    }
+
+    // The track state lags a little behind the command, so pause is drawn from the button
+    let Some(mut view) = current_view(&queue).await else {
+      respond(ctx, itx, "Nothing playing right now".to_string(), true).await?;
+      return Ok(true);
+    };
+    match action {
+      "pause" => view.paused = true,
+      "resume" => view.paused = false,
+      _ => {}
+    }
+    itx
+      .create_response(
+        &ctx.http,
+        CreateInteractionResponse::UpdateMessage(
+          CreateInteractionResponseMessage::new()
+            .content(render(&view))
+            .components(controls(view.paused)),
+        ),
+      )
+      .await?;
+    Ok(true)
+  }
+}
+
+async fn current_view(queue: &TrackQueue) -> Option<View> {
+  let trk = queue.current()?;
+  let metadata = track_metadata(&trk)?;
+  let state = trk.get_info().await.ok()?;
+  Some(View {
+    metadata,
+    position: state.position,
+    paused: matches!(state.playing, PlayMode::Pause),
+  })
+}
+
+fn render(view: &View) -> String {
+  let elapsed = match view.metadata.duration {
+    Some(total) => format!("{} / {}", format_time(view.position), format_time(total)),
+    None => format_time(view.position),
+  };
+  MessageBuilder::new()
+    .push_bold("Now Playing ")
+    .push_mono_line(&view.metadata.title)
+    .push_line(&view.metadata.url)
+    .push(if view.paused { "⏸️ " } else { "▶️ " })
+    .push_line(elapsed)
+    .push_mono(progress_bar(
+      view.position,
+      view.metadata.duration,
+      BAR_WIDTH,
+    ))
+    .build()
+}
+
+fn controls(paused: bool) -> Vec<CreateActionRow> {
+  let toggle = match paused {
+    true => CreateButton::new(format!("{BUTTON_PREFIX}resume"))
+      .label("Resume")
+      .style(ButtonStyle::Success),
+    false => CreateButton::new(format!("{BUTTON_PREFIX}pause"))
+      .label("Pause")
+      .style(ButtonStyle::Secondary),
+  };
+  vec![CreateActionRow::Buttons(vec![
+    toggle,
+    CreateButton::new(format!("{BUTTON_PREFIX}skip"))
+      .label("Skip")
+      .style(ButtonStyle::Primary),
+    CreateButton::new(format!("{BUTTON_PREFIX}shuffle"))
+      .label("Shuffle")
+      .style(ButtonStyle::Secondary),
+    CreateButton::new(format!("{BUTTON_PREFIX}stop"))
+      .label("Stop")
+      .style(ButtonStyle::Danger),
+  ])]
+}
+
+/// Formats as m:ss, or h:mm:ss for anything an hour or longer
+pub fn format_time(d: Duration) -> String {
+  let secs = d.as_secs();
+  let (h, m, s) = (secs / 3600, (secs / 60) % 60, secs % 60);
+  match h {
+    0 => format!("{m}:{s:02}"),
+    _ => format!("{h}:{m:02}:{s:02}"),
+  }
+}
+
+/// Text progress bar with a marker at the current position. Without a total
+/// (e.g. streams) the marker just sits at the start.
+fn progress_bar(position: Duration, total: Option<Duration>, width: usize) -> String {
+  let filled = match total {
+    Some(t) if !t.is_zero() => {
+      let frac = (position.as_secs_f64() / t.as_secs_f64()).min(1.0);
+      ((frac * width as f64) as usize).min(width - 1)
+    }
+    _ => 0,
+  };
+  let mut bar = "━".repeat(filled);
+  bar.push('●');
+  bar.push_str(&"─".repeat(width - filled - 1));
+  bar
+}
+
+/// Drives the now playing message from the call's events
+pub struct NowPlayingHandler {
+  guild_id: GuildId,
+  // Periodic events also carry every track in the call, so we can't tell them apart by context
+  on_play: bool,
+  queue: TrackQueue,
+  http: Arc<Http>,
+  now_playing: Arc<NowPlaying>,
+  // Resuming from a pause fires Play for the same track again, which shouldn't repost
+  announced: Mutex<Option<Uuid>>,
+}
+
+impl NowPlayingHandler {
+  pub async fn register(
+    guild_id: GuildId,
+    http: Arc<Http>,
+    now_playing: Arc<NowPlaying>,
+    call: &Arc<Mutex<Call>>,
+  ) {
+    let mut call_lock = call.lock().await;
+    let queue = call_lock.queue().clone();
+    call_lock.add_global_event(
+      Event::Track(TrackEvent::Play),
+      Self {
+        guild_id,
+        on_play: true,
+        queue: queue.clone(),
+        http: http.clone(),
+        now_playing: now_playing.clone(),
+        announced: Mutex::new(None),
+      },
+    );
+    call_lock.add_global_event(
+      Event::Periodic(REFRESH_INTERVAL, None),
+      Self {
+        guild_id,
+        on_play: false,
+        queue,
+        http,
+        now_playing,
+        announced: Mutex::new(None),
+      },
+    );
+  }
+}
+
+#[async_trait]
+impl EventHandler for NowPlayingHandler {
+  #[instrument(name = "NowPlaying", level = "DEBUG", skip(self, ctx))]
+  async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+    match ctx {
+      EventContext::Track(started) if self.on_play => {
+        // Soundboard clips play over the top of the queue, only queue tracks count here
+        let current = self.queue.current()?.uuid();
+        if !started.iter().any(|(_, h)| h.uuid() == current) {
+          return None;
+        }
+        let mut announced = self.announced.lock().await;
+        if announced.replace(current) != Some(current) {
+          self
+            .now_playing
+            .repost(&self.http, self.guild_id, &self.queue)
+            .await;
+        }
+      }
+      _ => {
+        self
+          .now_playing
+          .refresh(&self.http, self.guild_id, &self.queue)
+          .await
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn times_are_formatted() {
+    assert_eq!("0:07", format_time(Duration::from_secs(7)));
+    assert_eq!("4:56", format_time(Duration::from_secs(296)));
+    assert_eq!("1:02:03", format_time(Duration::from_secs(3723)));
+  }
+
+  #[test]
+  fn progress_bar_tracks_position() {
+    let total = Some(Duration::from_secs(100));
+    assert_eq!("●────", progress_bar(Duration::ZERO, total, 5));
+    assert_eq!("━━●──", progress_bar(Duration::from_secs(50), total, 5));
+    // Marker never runs off the end, even if we overshoot the reported length
+    assert_eq!("━━━━●", progress_bar(Duration::from_secs(120), total, 5));
+    assert_eq!("●────", progress_bar(Duration::from_secs(50), None, 5));
+  }
+}
@@ -0,0 +1,56 @@
+use super::SubCommandHandler;
+use crate::cmd::arg_util::Args;
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+};
+
+/// Pauses or resumes whatever is playing, depending on `resume`
+#[derive(new)]
+pub struct Pause {
+  resume: bool,
+}
+
+#[async_trait]
+impl SubCommandHandler for Pause {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    _: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+
+    let handler_lock = manager
+      .get(guild_id)
+      .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
+    let handler = handler_lock.lock().await;
+
+    let queue = handler.queue();
+    if queue.is_empty() {
+      return Err(anyhow!("Nothing is playing"));
+    }
+    let content = match self.resume {
+      true => {
+        queue.resume()?;
+        "Back at it"
+      }
+      false => {
+        queue.pause()?;
+        "Paused"
+      }
+    };
+
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+      .await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,298 @@
+use super::{
+  connect_util::VoiceConnector, disconnect::DisconnectMessage, now_playing::format_time,
+  play::ListMetadata, sources::SourceKind,
+};
+use crate::{cmd::component_util::respond, HttpClient};
+use anyhow::anyhow;
+use derive_new::new;
+use kitchen_sink::actor::ActorHandle;
+use serenity::{
+  all::{
+    ChannelId, CommandInteraction, ComponentInteraction, ComponentInteractionDataKind, GuildId,
+    UserId,
+  },
+  builder::{
+    CreateActionRow, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+    EditInteractionResponse,
+  },
+  client::Context,
+  prelude::Mutex,
+  utils::MessageBuilder,
+};
+use songbird::input::{AuxMetadata, YoutubeDl};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const MENU_PREFIX: &str = "pick:";
+const RESULT_COUNT: usize = 5;
+const PICK_TIMEOUT: Duration = Duration::from_secs(60);
+// Discord caps select option labels and descriptions at 100 chars
+const OPTION_TEXT_MAX: usize = 100;
+
+struct PendingPick {
+  guild_id: GuildId,
+  user_id: UserId,
+  requested: Option<ChannelId>,
+  choices: Vec<ListMetadata>,
+}
+
+/// Lets someone choose between the top search results instead of trusting the first
+#[derive(new)]
+pub struct Picker {
+  connector: VoiceConnector,
+  disconnect: ActorHandle<DisconnectMessage>,
+  #[new(default)]
+  pending: Mutex<HashMap<Uuid, PendingPick>>,
+}
+
+impl Picker {
+  /// Replaces the command's response with a menu of search results, which
+  /// quietly expires if nobody picks anything
+  pub async fn offer(
+    self: &Arc<Self>,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    guild_id: GuildId,
+    requested: Option<ChannelId>,
+    searchterm: String,
+  ) -> Result<(), anyhow::Error> {
+    let http_client = http_client(ctx).await?;
+    let choices: Vec<(ListMetadata, Option<String>)> =
+      YoutubeDl::new_search(http_client, searchterm.clone())
+        .search(Some(RESULT_COUNT))
+        .await
+        .map_err(|e| anyhow!("Search failed").context(e))?
+        .filter_map(to_choice)
+        .collect();
+    if choices.is_empty() {
+      return Err(anyhow!("Nothing found for {searchterm}"));
+    }
+
+    let id = Uuid::new_v4();
+    let options = choices
+      .iter()
+      .enumerate()
+      .map(|(idx, (meta, channel))| {
+        CreateSelectMenuOption::new(truncate(&meta.title), idx.to_string())
+          .description(truncate(&describe(meta, channel.as_deref())))
+      })
+      .collect();
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new()
+          .content(
+            MessageBuilder::new()
+              .push("Results for ")
+              .push_mono(&searchterm)
+              .build(),
+          )
+          .components(vec![CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+              format!("{MENU_PREFIX}{id}"),
+              CreateSelectMenuKind::String { options },
+            )
+            .placeholder("Pick a track"),
+          )]),
+      )
+      .await?;
+
+    self.pending.lock().await.insert(
+      id,
+      PendingPick {
+        guild_id,
+        user_id: itx.user.id,
+        requested,
+        choices: choices.into_iter().map(|(m, _)| m).collect(),
+      },
+    );
+
+    let picker = self.clone();
+    let (ctx, itx) = (ctx.clone(), itx.clone());
+    tokio::spawn(async move {
+      tokio::time::sleep(PICK_TIMEOUT).await;
+      if picker.pending.lock().await.remove(&id).is_none() {
+        return;
+      }
+      info!("Pick {} timed out", id);
+      let _ = itx
+        .edit_response(
+          &ctx.http,
+          EditInteractionResponse::new()
+            .content("Too slow, the picker has gone home")
+            .components(vec![]),
+        )
+        .await;
+    });
+    Ok(())
+  }
+
+  /// Handles selections from the menu, returning false if the interaction isn't ours
+  pub async fn msg_interact(
+    &self,
+    ctx: &Context,
+    itx: &ComponentInteraction,
+  ) -> Result<bool, anyhow::Error> {
+    let Some(id) = itx.data.custom_id.strip_prefix(MENU_PREFIX) else {
+      return Ok(false);
+    };
+    let id: Uuid = id
+      .parse()
+      .map_err(|e| anyhow!("Bad id on picker {id}").context(e))?;
+    let idx = match &itx.data.kind {
+      ComponentInteractionDataKind::StringSelect { values } => values
+        .first()
+        .and_then(|v| v.parse::<usize>().ok())
+        .ok_or_else(|| anyhow!("Nothing selected on picker {id}"))?,
+      _ => return Err(anyhow!("Wrong interaction kind on picker {id}")),
+    };
+
+    let pick = {
+      let mut pending = self.pending.lock().await;
+      match pending.get(&id) {
+        None => {
+          respond(ctx, itx, "This picker has expired".to_string(), true).await?;
+          return Ok(true);
+        }
+        Some(p) if p.user_id != itx.user.id => {
+          respond(ctx, itx, "Not your pick to make".to_string(), false).await?;
+          return Ok(true);
+        }
+        Some(_) => pending.remove(&id).expect("Checked present under lock"),
+      }
+    };
+    let meta = pick
+      .choices
+      .into_iter()
+      .nth(idx)
+      .ok_or_else(|| anyhow!("Picked an option that doesn't exist {idx}"))?;
+
+    let title = meta.title.clone();
+    respond(
+      ctx,
+      itx,
+      MessageBuilder::new()
+        .push("Queuing ")
+        .push_mono(&title)
+        .build(),
+      true,
+    )
+    .await?;
+
+    let guild_id = pick.guild_id;
+    let _ = self
+      .disconnect
+      .send(DisconnectMessage::Enqueue(guild_id))
+      .await;
+    let res = self
+      .connector
+      .enqueue_known(
+        ctx,
+        guild_id,
+        itx.user.id,
+        pick.requested,
+        itx.channel_id,
+        meta,
+      )
+      .await;
+    let _ = self
+      .disconnect
+      .send(DisconnectMessage::Dequeue(guild_id))
+      .await;
+    let content = match res {
+      Ok(len) => MessageBuilder::new()
+        .push_bold("Queued")
+        .push(format!(" ({len}) "))
+        .push_mono(title)
+        .build(),
+      Err(e) => format!("{e}"),
+    };
+    if let Err(e) = itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+      .await
+    {
+      warn!("Failed to report pick result {:?}", e);
+    }
+    Ok(true)
+  }
+}
+
+async fn http_client(ctx: &Context) -> Result<reqwest::Client, anyhow::Error> {
+  let data = ctx.data.read().await;
+  data
+    .get::<HttpClient>()
+    .cloned()
+    .ok_or_else(|| anyhow!("HttpClient not found in typemap"))
+}
+
+fn to_choice(m: AuxMetadata) -> Option<(ListMetadata, Option<String>)> {
+  Some((
+    ListMetadata {
+      url: m.source_url?,
+      title: m
+        .track
+        .or(m.title)
+        .unwrap_or_else(|| "<UNKNOWN>".to_string()),
+      duration: m.duration,
+      requested_by: None,
+      requested_at: None,
+      source: SourceKind::YouTube,
+    },
+    m.channel.or(m.artist),
+  ))
+}
+
+fn describe(meta: &ListMetadata, channel: Option<&str>) -> String {
+  let duration = meta
+    .duration
+    .map(format_time)
+    .unwrap_or_else(|| "live".to_string());
+  match channel {
+    Some(c) => format!("{c} · {duration}"),
+    None => duration,
+  }
+}
+
+fn truncate(s: &str) -> String {
+  match s.chars().count() > OPTION_TEXT_MAX {
+    true => {
+      let mut t: String = s.chars().take(OPTION_TEXT_MAX - 1).collect();
+      t.push('…');
+      t
+    }
+    false => s.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn options_fit_discord_limits() {
+    let long = "a".repeat(150);
+    let t = truncate(&long);
+    assert_eq!(OPTION_TEXT_MAX, t.chars().count());
+    assert!(t.ends_with('…'));
+    assert_eq!("short", truncate("short"));
+  }
+
+  #[test]
+  fn descriptions_show_channel_and_length() {
+    let meta = ListMetadata {
+      title: "Song".to_string(),
+      url: "https://www.youtube.com/watch?v=a".to_string(),
+      duration: Some(Duration::from_secs(225)),
+      requested_by: None,
+      requested_at: None,
+      source: SourceKind::YouTube,
+    };
+    assert_eq!("Band · 3:45", describe(&meta, Some("Band")));
+    let live = ListMetadata {
+      duration: None,
+      ..meta
+    };
+    assert_eq!("live", describe(&live, None));
+  }
+}
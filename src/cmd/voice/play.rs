@@ -1,38 +1,107 @@
-use super::{connect_util::DisconnectMessage, SubCommandHandler};
+use super::{
+  connect_util::VoiceConnector,
+  disconnect::DisconnectMessage,
+  fair::interleave,
+  picker::Picker,
+  playlist::{expand, is_playlist},
+  resume::{save_queue, Resumer},
+  settings::VoiceSettings,
+  sources::{Resolved, SourceKind},
+  SubCommandHandler,
+};
 use crate::{
-  cmd::{
-    arg_util::Args,
-    voice::connect_util::{DisconnectDetails, DisconnectEventHandler},
-  },
+  cmd::arg_util::Args,
   config::Config,
   emoji::EmojiLookup,
-  HttpClient,
+  types::{Chan, Usr},
 };
 use anyhow::anyhow;
+use bincode::{Decode, Encode};
 use derive_new::new;
 use kitchen_sink::actor::ActorHandle;
 use serenity::{
-  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  all::{CommandInteraction, GuildId, UserId},
+  async_trait,
+  builder::EditInteractionResponse,
+  client::Context,
   utils::MessageBuilder,
 };
 use songbird::{
   driver::Bitrate,
-  input::{Input, YoutubeDl},
-  tracks::Track,
+  input::Input,
+  tracks::{Track, TrackHandle},
+  Call,
+};
+use std::{
+  sync::Arc,
+  time::{Duration, SystemTime},
 };
-use std::sync::Arc;
-use tracing::info;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Encode, Decode)]
 pub struct ListMetadata {
   pub title: String,
   pub url: String,
+  pub duration: Option<Duration>,
+  pub requested_by: Option<Usr>,
+  pub requested_at: Option<SystemTime>,
+  pub source: SourceKind,
+}
+
+impl ListMetadata {
+  /// Stamps who asked for the track, and when
+  pub fn requested(mut self, user_id: UserId) -> Self {
+    self.requested_by = Some(Usr(user_id));
+    self.requested_at = Some(SystemTime::now());
+    self
+  }
+}
+
+/// Retrieves the metadata attached to a queued track, if it has any
+pub fn track_metadata(trk: &TrackHandle) -> Option<Arc<ListMetadata>> {
+  // Note: data() will panic if type doesn't match, so we use a simple fallback approach
+  std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| trk.data::<ListMetadata>())).ok()
+}
+
+/// Enqueues the input onto the call with the metadata attached for later listing
+pub async fn enqueue(
+  handler: &mut Call,
+  input: Input,
+  metadata: ListMetadata,
+  volume: f32,
+) -> TrackHandle {
+  handler.set_bitrate(Bitrate::Max);
+  handler
+    .enqueue(with_metadata(input, metadata).volume(volume))
+    .await
+}
+
+/// Like [enqueue], but skips probing the source for its duration. Much quicker
+/// when queuing many tracks at once at the cost of preloading the next track.
+pub fn enqueue_lazy(
+  handler: &mut Call,
+  input: Input,
+  metadata: ListMetadata,
+  volume: f32,
+) -> TrackHandle {
+  handler.set_bitrate(Bitrate::Max);
+  handler.enqueue_with_preload(with_metadata(input, metadata).volume(volume), None)
+}
+
+fn with_metadata(input: Input, metadata: ListMetadata) -> Track {
+  // Create track with custom metadata using songbird 0.5.0 API
+  Track::new_with_data(
+    input,
+    Arc::new(metadata) as Arc<dyn std::any::Any + Send + Sync>,
+  )
 }
 
 #[derive(new)]
 pub struct Play {
   config: Config,
   emoji: EmojiLookup,
+  connector: VoiceConnector,
+  resumer: Arc<Resumer>,
+  picker: Arc<Picker>,
   disconnect: ActorHandle<DisconnectMessage>,
 }
 
@@ -44,9 +113,18 @@ impl SubCommandHandler for Play {
     itx: &CommandInteraction,
     args: &Args,
   ) -> Result<(), anyhow::Error> {
-    let _ = self.disconnect.send(DisconnectMessage::Enqueue).await;
-    let res = wrapped_handle(self, ctx, itx, args).await;
-    let _ = self.disconnect.send(DisconnectMessage::Dequeue).await;
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let _ = self
+      .disconnect
+      .send(DisconnectMessage::Enqueue(guild_id))
+      .await;
+    let res = wrapped_handle(self, ctx, itx, guild_id, args).await;
+    let _ = self
+      .disconnect
+      .send(DisconnectMessage::Dequeue(guild_id))
+      .await;
     res
   }
 }
@@ -55,123 +133,116 @@ async fn wrapped_handle(
   play: &Play,
   ctx: &Context,
   itx: &CommandInteraction,
+  guild_id: GuildId,
   args: &Args<'_>,
 ) -> Result<(), anyhow::Error> {
-  let guild_id = itx
-    .guild_id
-    .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
-  let channel_id = ctx
-    .cache
-    .guild(guild_id)
-    .and_then(|g| {
-      g.voice_states
-        .get(&itx.user.id)
-        .and_then(|vs| vs.channel_id)
-    })
-    .ok_or_else(|| anyhow!("Not in a voice channel"))?;
+  // 1 optional arg: channel. Voice channel to play in, moving there if we're elsewhere
+  let requested = args.opt_channel("channel")?.map(|c| c.id);
+  let channel_id = play
+    .connector
+    .target_channel(ctx, guild_id, itx.user.id, requested)?;
 
   // 1 arg: link. String.
   let searchterm = args
     .str("link_or_search")
     .map_err(|e| anyhow!("Must provide a url|search string").context(e))?
     .to_string();
-
-  // Fetch the Songbird mgr & join channel
-  let manager = songbird::get(ctx)
-    .await
-    .ok_or_else(|| anyhow!("Songbird Voice client not initialized"))?;
-
-  // Check if we're already in the channel or not, connecting if not
-  let handler_lock = match manager.get(guild_id) {
-    None => {
-      info!("Joining voice for first time...");
-      let handler_lock = manager
-        .join(guild_id, channel_id)
-        .await
-        .map_err(|e| anyhow!("Error joining voice channel").context(format!("{e:?}")))?;
-
-      // Register an event handler to listen for the duration of the call
-      DisconnectEventHandler::register(
-        play.config.voice_channel_timeout.as_secs(),
-        play.disconnect.clone(),
-        &handler_lock,
-      )
+  // 1 optional arg: source. Otherwise it's worked out from the link
+  let kind = args.opt_str("source")?.map(SourceKind::parse).transpose()?;
+  let sources = play.connector.sources();
+  let kind = kind.unwrap_or_else(|| sources.kind_for(&searchterm));
+
+  // Let them know we've still got what was playing before the last restart
+  play
+    .resumer
+    .offer_on_play(ctx, guild_id, itx.channel_id)
+    .await;
+
+  // Let them choose from the results instead, we'll queue it once they have
+  let is_url = searchterm.starts_with("http");
+  let is_youtube = kind == SourceKind::YouTube;
+  if !is_url && is_youtube && args.opt_bool("pick")?.unwrap_or(false) {
+    return play
+      .picker
+      .offer(ctx, itx, guild_id, requested, searchterm)
       .await;
+  }
 
-      // Inform disconnect of where to disconnect from
-      play
-        .disconnect
-        .send(DisconnectMessage::Details(DisconnectDetails::new(
-          handler_lock.clone(),
-          ctx.http.clone(),
-          play.emoji.get(&ctx.http, guild_id).await?,
-        )))
-        .await;
-
-      handler_lock
-    }
-    Some(l) => {
-      {
-        // Rejoin the channel if we're not in it already, but we previously were
-        let mut lock = l.lock().await;
-        if lock.current_channel().is_none() {
-          let _ = lock.join(channel_id).await;
-        }
-      }
-      l
-    }
+  // Fetch the call for this guild, joining the channel if needed
+  let handler_lock = match requested {
+    Some(_) => play.connector.move_to(ctx, guild_id, channel_id).await?,
+    None => play.connector.connect(ctx, guild_id, channel_id).await?,
   };
 
   // Queue up the source
-  let http_client = {
-    let data = ctx.data.read().await;
-    data
-      .get::<HttpClient>()
-      .cloned()
-      .ok_or_else(|| anyhow!("HttpClient not found in typemap"))?
-  };
-  let is_url = searchterm.starts_with("http");
-  let resolved_src = match is_url {
-    false => YoutubeDl::new_search(http_client, searchterm),
-    true => YoutubeDl::new(http_client, searchterm),
-  };
-  let mut input = Input::from(resolved_src);
+  let emoji = play.emoji.get(&ctx.http, guild_id).await?;
+  let settings = VoiceSettings::load(play.resumer.persistence(), guild_id);
+  let mut build = MessageBuilder::new();
 
-  let list_metadata = input
-    .aux_metadata()
-    .await
-    .map(|m| ListMetadata {
-      title: m
-        .track
-        .or(m.title)
-        .unwrap_or_else(|| "<UNKNOWN>".to_string()),
-      url: m.source_url.unwrap_or_else(|| "<UNKNOWN>".to_string()),
-    })
-    .unwrap_or_else(|_| ListMetadata {
-      title: "<UNKNOWN>".to_string(),
-      url: "<UNKNOWN>".to_string(),
-    });
-
-  let mut handler = handler_lock.lock().await;
-  handler.set_bitrate(Bitrate::Max);
+  let handler = if is_url && is_youtube && is_playlist(&searchterm) {
+    let max = play.config.playlist_max_tracks;
+    let playlist = expand(&searchterm, max).await?;
+    let count = playlist.tracks.len();
+
+    let mut handler = handler_lock.lock().await;
+    for meta in playlist.tracks {
+      let input = settings.filters.apply(sources.source(&meta));
+      enqueue_lazy(
+        &mut handler,
+        input,
+        meta.requested(itx.user.id),
+        settings.gain(),
+      );
+    }
+    if settings.fair_queue {
+      interleave(handler.queue());
+    }
 
-  // Create track with custom metadata using songbird 0.5.0 API
-  let track_with_data = Track::new_with_data(
-    input,
-    Arc::new(list_metadata.clone()) as Arc<dyn std::any::Any + Send + Sync>,
+    build
+      .push_bold("Queued")
+      .push(format!(" {count} tracks from "))
+      .push_mono(playlist.title);
+    if playlist.total > count {
+      build.push(format!(" (only the first {count} of {})", playlist.total));
+    }
+    build.emoji(&emoji);
+    handler
+  } else {
+    let Resolved { source, meta } = sources.resolve(&searchterm, Some(kind)).await?;
+    let input = settings.filters.apply(source);
+    let list_metadata = meta.requested(itx.user.id);
+
+    let mut handler = handler_lock.lock().await;
+    let th = enqueue(&mut handler, input, list_metadata.clone(), settings.gain()).await;
+    if settings.fair_queue {
+      interleave(handler.queue());
+    }
+    let position = handler
+      .queue()
+      .current_queue()
+      .iter()
+      .position(|t| t.uuid() == th.uuid())
+      .map_or(handler.queue().len(), |p| p + 1);
+
+    build
+      .push_bold("Queued")
+      .push(format!(" ({position}) "))
+      .push_mono(list_metadata.title)
+      .emoji(&emoji);
+    // Searches might not land where they expected, show them what we found
+    if !is_url && is_youtube {
+      build.push_line("").push(list_metadata.url);
+    }
+    handler
+  };
+  save_queue(
+    play.resumer.persistence(),
+    guild_id,
+    handler.queue(),
+    Chan(channel_id),
+    Chan(itx.channel_id),
   );
-  let _th = handler.enqueue(track_with_data).await;
 
-  let emoji = play.emoji.get(&ctx.http, guild_id).await?;
-  let mut build = MessageBuilder::new();
-  build
-    .push_bold("Queued")
-    .push(format!(" ({}) ", handler.queue().len()))
-    .push_mono(list_metadata.title)
-    .emoji(&emoji);
-  if !is_url {
-    build.push_line("").push(list_metadata.url);
-  }
   itx
     .edit_response(
       &ctx.http,
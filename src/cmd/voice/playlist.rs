@@ -0,0 +1,146 @@
+use super::{play::ListMetadata, sources::SourceKind};
+use anyhow::anyhow;
+use reqwest::Url;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{info, instrument};
+
+/// Placeholders yt-dlp lists for entries we couldn't play anyway
+const UNAVAILABLE: [&str; 2] = ["[Private video]", "[Deleted video]"];
+
+#[derive(Deserialize)]
+struct FlatPlaylist {
+  title: Option<String>,
+  playlist_count: Option<usize>,
+  #[serde(default)]
+  entries: Vec<FlatEntry>,
+}
+
+#[derive(Deserialize)]
+struct FlatEntry {
+  url: Option<String>,
+  title: Option<String>,
+  duration: Option<f64>,
+}
+
+/// The entries of a playlist, already trimmed down to what we're willing to queue
+pub struct Playlist {
+  pub title: String,
+  pub tracks: Vec<ListMetadata>,
+  /// How many entries the playlist had in total, before capping
+  pub total: usize,
+}
+
+/// Whether the link points at a whole playlist rather than a single video. Videos
+/// opened from within a playlist carry a `list` too, but they mean the video.
+pub fn is_playlist(link: &str) -> bool {
+  let Ok(url) = Url::parse(link) else {
+    return false;
+  };
+  let youtube = url
+    .host_str()
+    .is_some_and(|h| h == "youtube.com" || h.ends_with(".youtube.com"));
+  let has = |key: &str| url.query_pairs().any(|(k, _)| k == key);
+  youtube && has("list") && (url.path() == "/playlist" || !has("v"))
+}
+
+/// Lists the playlist's entries through yt-dlp without resolving each of them,
+/// which is what keeps this fast enough to do while someone waits
+#[instrument(level = "INFO")]
+pub async fn expand(link: &str, max: usize) -> Result<Playlist, anyhow::Error> {
+  let output = Command::new("yt-dlp")
+    .args(["--flat-playlist", "-J", "--playlist-end"])
+    .arg(max.to_string())
+    .arg(link)
+    .output()
+    .await
+    .map_err(|e| anyhow!("Failed to run yt-dlp").context(e))?;
+  if !output.status.success() {
+    return Err(anyhow!(
+      "Couldn't read that playlist: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  let playlist = parse(&output.stdout, max)?;
+  info!(
+    "Expanded playlist {} to {} of {} tracks",
+    playlist.title,
+    playlist.tracks.len(),
+    playlist.total
+  );
+  Ok(playlist)
+}
+
+fn parse(json: &[u8], max: usize) -> Result<Playlist, anyhow::Error> {
+  let raw: FlatPlaylist =
+    serde_json::from_slice(json).map_err(|e| anyhow!("Unexpected yt-dlp output").context(e))?;
+  let total = raw.playlist_count.unwrap_or(raw.entries.len());
+  let tracks: Vec<ListMetadata> = raw
+    .entries
+    .into_iter()
+    .filter(|e| !e.title.as_deref().is_some_and(|t| UNAVAILABLE.contains(&t)))
+    .filter_map(|e| {
+      Some(ListMetadata {
+        url: e.url?,
+        title: e.title.unwrap_or_else(|| "<UNKNOWN>".to_string()),
+        duration: e.duration.map(Duration::from_secs_f64),
+        requested_by: None,
+        requested_at: None,
+        source: SourceKind::YouTube,
+      })
+    })
+    .take(max)
+    .collect();
+  if tracks.is_empty() {
+    return Err(anyhow!("Nothing playable in that playlist"));
+  }
+  Ok(Playlist {
+    title: raw.title.unwrap_or_else(|| "<UNKNOWN>".to_string()),
+    tracks,
+    total,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  #[test_case("https://www.youtube.com/playlist?list=PL123", true ; "Playlist page")]
+  #[test_case("https://music.youtube.com/playlist?list=PL123", true ; "Music playlist")]
+  #[test_case("https://www.youtube.com/watch?v=abc&list=PL123", false ; "Video in playlist")]
+  #[test_case("https://www.youtube.com/watch?v=abc", false ; "Video")]
+  #[test_case("https://youtu.be/abc", false ; "Short link")]
+  #[test_case("https://example.com/playlist?list=PL123", false ; "Not YouTube")]
+  #[test_case("never gonna give you up", false ; "Search")]
+  fn detects_playlists(link: &str, expected: bool) {
+    assert_eq!(expected, is_playlist(link));
+  }
+
+  #[test]
+  fn parses_flat_playlist() {
+    let json = br#"{
+      "_type": "playlist",
+      "title": "Bangers",
+      "playlist_count": 120,
+      "entries": [
+        {"_type": "url", "url": "https://www.youtube.com/watch?v=a", "title": "A", "duration": 61.0},
+        {"_type": "url", "url": "https://www.youtube.com/watch?v=b", "title": "[Private video]", "duration": null},
+        {"_type": "url", "url": "https://www.youtube.com/watch?v=c", "title": "C", "duration": null},
+        {"_type": "url", "url": "https://www.youtube.com/watch?v=d", "title": "D", "duration": 5.0}
+      ]
+    }"#;
+    let playlist = parse(json, 2).unwrap();
+    assert_eq!("Bangers", playlist.title);
+    assert_eq!(120, playlist.total);
+    let titles: Vec<_> = playlist.tracks.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(vec!["A", "C"], titles);
+    assert_eq!(Some(Duration::from_secs(61)), playlist.tracks[0].duration);
+  }
+
+  #[test]
+  fn rejects_empty_playlist() {
+    assert!(parse(br#"{"title": "Empty", "entries": []}"#, 10).is_err());
+  }
+}
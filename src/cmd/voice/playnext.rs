@@ -0,0 +1,87 @@
+use super::{play::track_metadata, resume::save_queue, SubCommandHandler};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore, types::Chan};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::{ChannelId, CommandInteraction},
+  async_trait,
+  builder::EditInteractionResponse,
+  client::Context,
+  utils::MessageBuilder,
+};
+use std::sync::Arc;
+
+/// Bumps a queued track up to play straight after the current one
+#[derive(new)]
+pub struct PlayNext {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for PlayNext {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+    let handler_lock = manager
+      .get(guild_id)
+      .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
+    let handler = handler_lock.lock().await;
+
+    // 1 arg: position. Integer, min value 2
+    let pos = *args
+      .i64("position")
+      .map_err(|e| anyhow!("Must provide a numeric position").context(e))?;
+    let queue_size = handler.queue().len();
+    if pos <= 1 {
+      return Err(anyhow!("That one's already playing"));
+    }
+    let pos = pos as usize;
+    if pos > queue_size {
+      return Err(anyhow!("There are only {} items in the queue", queue_size));
+    }
+
+    let moved = handler.queue().modify_queue(|queue| {
+      let item = queue.remove(pos - 1)?;
+      let handle = item.handle();
+      queue.insert(1, item);
+      Some(handle)
+    });
+    if let Some(voice) = handler.current_channel() {
+      save_queue(
+        &self.persistence,
+        guild_id,
+        handler.queue(),
+        Chan(ChannelId::from(voice.0)),
+        Chan(itx.channel_id),
+      );
+    }
+
+    let title = moved
+      .as_ref()
+      .and_then(track_metadata)
+      .map(|m| m.title.clone())
+      .unwrap_or_else(|| "<UNKNOWN TITLE>".to_string());
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(
+          MessageBuilder::new()
+            .push_bold_safe(title)
+            .push(" is up next")
+            .build(),
+        ),
+      )
+      .await?;
+    Ok(())
+  }
+}
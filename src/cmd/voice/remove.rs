@@ -0,0 +1,129 @@
+use super::{play::track_metadata, resume::save_queue, SubCommandHandler};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore, types::Chan};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::{ChannelId, CommandInteraction},
+  async_trait,
+  builder::EditInteractionResponse,
+  client::Context,
+  utils::MessageBuilder,
+};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+#[derive(new)]
+pub struct Remove {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Remove {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+    let handler_lock = manager
+      .get(guild_id)
+      .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
+    let handler = handler_lock.lock().await;
+
+    // 1 arg: range. String, either a position or from-to
+    let range = parse_range(args.str("range")?, handler.queue().len())?;
+    let removed = handler.queue().modify_queue(|queue| {
+      queue
+        .drain(range.start() - 1..*range.end())
+        .collect::<Vec<_>>()
+    });
+    // Out of the queue isn't out of the driver, they'd sit there holding their streams
+    let mut titles = Vec::with_capacity(removed.len());
+    for queued in removed {
+      titles.push(
+        track_metadata(&queued.handle())
+          .map(|m| m.title.clone())
+          .unwrap_or_else(|| "<UNKNOWN TITLE>".to_string()),
+      );
+      let _ = queued.stop();
+    }
+
+    if let Some(voice) = handler.current_channel() {
+      save_queue(
+        &self.persistence,
+        guild_id,
+        handler.queue(),
+        Chan(ChannelId::from(voice.0)),
+        Chan(itx.channel_id),
+      );
+    }
+
+    let content = match titles.as_slice() {
+      [title] => MessageBuilder::new()
+        .push("Removed ")
+        .push_bold_safe(title.as_str())
+        .build(),
+      _ => format!("Removed {} tracks", titles.len()),
+    };
+    itx
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+      .await?;
+    Ok(())
+  }
+}
+
+/// Reads a 1 based queue position like `3`, or an inclusive range like `3-5`.
+/// The first item is what's playing, that's what skip is for.
+fn parse_range(raw: &str, queue_size: usize) -> Result<RangeInclusive<usize>, anyhow::Error> {
+  let parse = |s: &str| {
+    s.trim()
+      .parse::<usize>()
+      .map_err(|_| anyhow!("{} isn't a queue position", s.trim()))
+  };
+  let (from, to) = match raw.split_once('-') {
+    Some((from, to)) => (parse(from)?, parse(to)?),
+    None => {
+      let pos = parse(raw)?;
+      (pos, pos)
+    }
+  };
+  if from > to {
+    return Err(anyhow!("That range is backwards"));
+  }
+  if from <= 1 {
+    return Err(anyhow!("Cannot remove what's playing, skip it instead"));
+  }
+  if to > queue_size {
+    return Err(anyhow!("There are only {} items in the queue", queue_size));
+  }
+  Ok(from..=to)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  #[test_case("3", 5, 3..=3 ; "Single")]
+  #[test_case("2-4", 5, 2..=4 ; "Range")]
+  #[test_case(" 2 - 5 ", 5, 2..=5 ; "Spaces")]
+  fn parses_ranges(raw: &str, size: usize, expected: RangeInclusive<usize>) {
+    assert_eq!(expected, parse_range(raw, size).unwrap());
+  }
+
+  #[test_case("1", 5 ; "Current track")]
+  #[test_case("1-3", 5 ; "Range over current track")]
+  #[test_case("6", 5 ; "Past the end")]
+  #[test_case("4-2", 5 ; "Backwards")]
+  #[test_case("two", 5 ; "Not a number")]
+  #[test_case("2-", 5 ; "Open ended")]
+  fn rejects_bad_ranges(raw: &str, size: usize) {
+    assert!(parse_range(raw, size).is_err());
+  }
+}
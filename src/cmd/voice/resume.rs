@@ -0,0 +1,396 @@
+use super::{
+  connect_util::VoiceConnector,
+  play::{enqueue_lazy, track_metadata, ListMetadata},
+  settings::VoiceSettings,
+};
+use crate::{cmd::component_util::respond, persistence::PersistentStore, types::Chan};
+use anyhow::anyhow;
+use bincode::{Decode, Encode};
+use serenity::{
+  all::{ButtonStyle, ChannelId, ComponentInteraction, GuildId},
+  async_trait,
+  builder::{CreateActionRow, CreateButton, CreateMessage},
+  client::Context,
+  http::Http,
+  prelude::Mutex,
+  utils::MessageBuilder,
+};
+use songbird::{events::TrackEvent, tracks::TrackQueue, Call, Event, EventContext, EventHandler};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+const RESUME_PREFIX: &str = "voice-resume:";
+const DISCARD_PREFIX: &str = "voice-discard:";
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// What a guild was listening to, kept around so a restart doesn't lose it
+#[derive(Clone, Encode, Decode)]
+pub struct SavedQueue {
+  pub voice_channel: Chan,
+  pub text_channel: Chan,
+  pub tracks: Vec<ListMetadata>,
+  pub position: Duration,
+}
+
+/// Persists the current state of the queue, dropping the record once it's empty.
+/// Channels are only known at enqueue time, so this is what creates the record.
+pub fn save_queue(
+  persistence: &PersistentStore,
+  guild_id: GuildId,
+  queue: &TrackQueue,
+  voice_channel: Chan,
+  text_channel: Chan,
+) {
+  let tracks = snapshot(queue, None);
+  let res = match tracks.is_empty() {
+    true => persistence.voice_queues().remove(&guild_id),
+    false => {
+      let position = persistence
+        .voice_queues()
+        .load(&guild_id)
+        .ok()
+        .flatten()
+        .map(|s| s.position)
+        .unwrap_or_default();
+      persistence.voice_queues().save(
+        &guild_id,
+        &SavedQueue {
+          voice_channel,
+          text_channel,
+          tracks,
+          position,
+        },
+      )
+    }
+  };
+  if let Err(e) = res {
+    error!("Failed to persist queue for guild {}: {}", guild_id, e);
+  }
+}
+
+fn snapshot(queue: &TrackQueue, skip: Option<Uuid>) -> Vec<ListMetadata> {
+  queue
+    .current_queue()
+    .iter()
+    .filter(|trk| Some(trk.uuid()) != skip)
+    .filter_map(track_metadata)
+    .map(|m| (*m).clone())
+    .collect()
+}
+
+/// Keeps the persisted queue in step with playback, both as tracks finish
+/// and periodically so we know roughly how far into the current track we were
+pub struct QueuePersister {
+  guild_id: GuildId,
+  // Periodic events also carry every track in the call, so we can't tell them apart by context
+  on_end: bool,
+  queue: TrackQueue,
+  persistence: Arc<PersistentStore>,
+}
+
+impl QueuePersister {
+  pub async fn register(
+    guild_id: GuildId,
+    persistence: Arc<PersistentStore>,
+    call: &Arc<Mutex<Call>>,
+  ) {
+    let mut call_lock = call.lock().await;
+    let queue = call_lock.queue().clone();
+    call_lock.add_global_event(
+      Event::Track(TrackEvent::End),
+      Self {
+        guild_id,
+        on_end: true,
+        queue: queue.clone(),
+        persistence: persistence.clone(),
+      },
+    );
+    call_lock.add_global_event(
+      Event::Periodic(SNAPSHOT_INTERVAL, None),
+      Self {
+        guild_id,
+        on_end: false,
+        queue,
+        persistence,
+      },
+    );
+  }
+}
+
+#[async_trait]
+impl EventHandler for QueuePersister {
+  #[instrument(name = "QueuePersister", level = "DEBUG", skip(self, ctx))]
+  async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+    let store = self.persistence.voice_queues();
+    let Ok(Some(mut saved)) = store.load(&self.guild_id) else {
+      // Nothing was queued through /play, so nothing to keep up to date
+      return None;
+    };
+
+    let (skip, position) = match ctx {
+      EventContext::Track(ended) if self.on_end => {
+        (ended.first().map(|(_, h)| h.uuid()), Duration::ZERO)
+      }
+      _ => {
+        let position = match self.queue.current() {
+          Some(trk) => trk.get_info().await.map(|s| s.position).unwrap_or_default(),
+          None => Duration::ZERO,
+        };
+        (None, position)
+      }
+    };
+
+    saved.tracks = snapshot(&self.queue, skip);
+    saved.position = position;
+    let res = match saved.tracks.is_empty() {
+      true => store.remove(&self.guild_id),
+      false => store.save(&self.guild_id, &saved),
+    };
+    if let Err(e) = res {
+      error!("Failed to persist queue for guild {}: {}", self.guild_id, e);
+    }
+    None
+  }
+}
+
+struct Pending {
+  queue: SavedQueue,
+  offered_on_ready: bool,
+  offered_on_play: bool,
+}
+
+/// Holds onto whatever queues were left over from the last run until someone
+/// decides to resume or forget them
+pub struct Resumer {
+  persistence: Arc<PersistentStore>,
+  connector: VoiceConnector,
+  pending: Mutex<HashMap<GuildId, Pending>>,
+}
+
+impl Resumer {
+  pub fn new(persistence: Arc<PersistentStore>, connector: VoiceConnector) -> Self {
+    // Anything on disk at startup is from a previous run
+    let pending = load_pending(&persistence);
+    Self {
+      persistence,
+      connector,
+      pending: Mutex::new(pending),
+    }
+  }
+
+  pub fn persistence(&self) -> &PersistentStore {
+    &self.persistence
+  }
+
+  /// Posts a resume offer for each saved queue in the channel it was queued from
+  pub async fn offer_on_ready(&self, http: &Http) {
+    let mut pending = self.pending.lock().await;
+    for (guild_id, p) in pending.iter_mut() {
+      if p.offered_on_ready {
+        continue;
+      }
+      p.offered_on_ready = true;
+      info!("Offering to resume queue for guild {}", guild_id);
+      if let Err(e) = send_offer(http, *guild_id, &p.queue, *p.queue.text_channel).await {
+        error!("Failed to offer resume to guild {}: {}", guild_id, e);
+      }
+    }
+  }
+
+  /// Reminds the guild about their saved queue the first time they /play
+  pub async fn offer_on_play(&self, ctx: &Context, guild_id: GuildId, channel: ChannelId) {
+    let mut pending = self.pending.lock().await;
+    let Some(p) = pending.get_mut(&guild_id) else {
+      return;
+    };
+    if p.offered_on_play {
+      return;
+    }
+    p.offered_on_play = true;
+    if let Err(e) = send_offer(&ctx.http, guild_id, &p.queue, channel).await {
+      error!("Failed to offer resume to guild {}: {}", guild_id, e);
+    }
+  }
+
+  /// Handles the resume/discard buttons, returning false if the interaction isn't ours
+  pub async fn msg_interact(
+    &self,
+    ctx: &Context,
+    itx: &ComponentInteraction,
+  ) -> Result<bool, anyhow::Error> {
+    let id = &itx.data.custom_id;
+    let (resume, guild) = if let Some(g) = id.strip_prefix(RESUME_PREFIX) {
+      (true, g)
+    } else if let Some(g) = id.strip_prefix(DISCARD_PREFIX) {
+      (false, g)
+    } else {
+      return Ok(false);
+    };
+    let guild_id: GuildId = guild
+      .parse()
+      .map_err(|e| anyhow!("Bad guild on resume button {guild}").context(e))?;
+
+    let Some(p) = self.pending.lock().await.remove(&guild_id) else {
+      respond(ctx, itx, "Already taken care of".to_string(), true).await?;
+      return Ok(true);
+    };
+
+    if !resume {
+      forget(&self.persistence, guild_id);
+      respond(
+        ctx,
+        itx,
+        "Fine, I'll forget it ever happened".to_string(),
+        true,
+      )
+      .await?;
+      return Ok(true);
+    }
+
+    let count = p.queue.tracks.len();
+    respond(
+      ctx,
+      itx,
+      format!("Picking back up with {count} tracks"),
+      true,
+    )
+    .await?;
+    if let Err(e) = self.resume(ctx, guild_id, itx.channel_id, p.queue).await {
+      let _ = itx
+        .channel_id
+        .say(&ctx.http, format!("Couldn't resume: {e}"))
+        .await;
+      return Err(e);
+    }
+    Ok(true)
+  }
+
+  async fn resume(
+    &self,
+    ctx: &Context,
+    guild_id: GuildId,
+    text_channel: ChannelId,
+    saved: SavedQueue,
+  ) -> Result<(), anyhow::Error> {
+    let handler_lock = self
+      .connector
+      .connect(ctx, guild_id, *saved.voice_channel)
+      .await?;
+    let mut handler = handler_lock.lock().await;
+
+    let was_empty = handler.queue().is_empty();
+    let settings = VoiceSettings::load(&self.persistence, guild_id);
+    for (idx, meta) in saved.tracks.into_iter().enumerate() {
+      let source = self.connector.sources().source(&meta);
+      let trk = enqueue_lazy(
+        &mut handler,
+        settings.filters.apply(source),
+        meta,
+        settings.gain(),
+      );
+      // Only jump ahead if the track we stopped partway through is what's playing
+      if idx == 0 && was_empty && !saved.position.is_zero() && trk.seek(saved.position).is_hung_up()
+      {
+        warn!("Failed to seek resumed track");
+      }
+    }
+    save_queue(
+      &self.persistence,
+      guild_id,
+      handler.queue(),
+      saved.voice_channel,
+      Chan(text_channel),
+    );
+    Ok(())
+  }
+}
+
+fn load_pending(persistence: &PersistentStore) -> HashMap<GuildId, Pending> {
+  match persistence.voice_queues().load_all() {
+    Ok(v) => v
+      .into_iter()
+      .map(|(guild_id, queue)| {
+        (
+          guild_id,
+          Pending {
+            queue,
+            offered_on_ready: false,
+            offered_on_play: false,
+          },
+        )
+      })
+      .collect(),
+    Err(e) => {
+      error!("Failed to load saved voice queues: {}", e);
+      HashMap::new()
+    }
+  }
+}
+
+/// Drops the saved queue for good, so it isn't offered again on the next start
+fn forget(persistence: &PersistentStore, guild_id: GuildId) {
+  if let Err(e) = persistence.voice_queues().remove(&guild_id) {
+    error!("Failed to forget saved queue for guild {}: {}", guild_id, e);
+  }
+}
+
+async fn send_offer(
+  http: &Http,
+  guild_id: GuildId,
+  queue: &SavedQueue,
+  channel: ChannelId,
+) -> Result<(), anyhow::Error> {
+  let mut content = MessageBuilder::new();
+  content
+    .push("I was playing ")
+    .push_bold(format!("{} tracks", queue.tracks.len()))
+    .push(format!(" in {} before my nap", queue.voice_channel));
+  if let Some(first) = queue.tracks.first() {
+    content.push(", starting with ").push_mono(&first.title);
+  }
+  content.push(". Pick up where we left off?");
+
+  channel
+    .send_message(
+      http,
+      CreateMessage::new()
+        .content(content.build())
+        .components(vec![CreateActionRow::Buttons(vec![
+          CreateButton::new(format!("{RESUME_PREFIX}{guild_id}"))
+            .label("Resume")
+            .style(ButtonStyle::Success),
+          CreateButton::new(format!("{DISCARD_PREFIX}{guild_id}"))
+            .label("Forget it")
+            .style(ButtonStyle::Danger),
+        ])]),
+    )
+    .await?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn forgotten_queues_arent_offered_again() {
+    let temp_dir = tempdir().unwrap();
+    let store = PersistentStore::new(temp_dir.path().join("test.db")).unwrap();
+    let queue = SavedQueue {
+      voice_channel: Chan(ChannelId::new(1)),
+      text_channel: Chan(ChannelId::new(2)),
+      tracks: vec![],
+      position: Duration::ZERO,
+    };
+    let (kept, forgotten) = (GuildId::new(1), GuildId::new(2));
+    store.voice_queues().save(&kept, &queue).unwrap();
+    store.voice_queues().save(&forgotten, &queue).unwrap();
+
+    forget(&store, forgotten);
+    let pending = load_pending(&store);
+    assert!(pending.contains_key(&kept));
+    assert!(!pending.contains_key(&forgotten));
+  }
+}
@@ -0,0 +1,67 @@
+use super::{settings::VoiceSettings, SubCommandHandler};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore};
+use anyhow::{anyhow, bail};
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+  utils::MessageBuilder,
+};
+use std::sync::Arc;
+
+/// Toggles the guild's queue etiquette. Leaving an option out keeps it as is, so
+/// anyone can see the rules but only server managers can change them.
+#[derive(new)]
+pub struct Rules {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Rules {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    // 2 optional args: fair_queue, vote_skip. Booleans
+    let fair_queue = args.opt_bool("fair_queue")?;
+    let vote_skip = args.opt_bool("vote_skip")?;
+    let mut settings = VoiceSettings::load(&self.persistence, guild_id);
+    if fair_queue.is_some() || vote_skip.is_some() {
+      let is_admin = itx
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_guild());
+      if !is_admin {
+        bail!("Only server managers can change the queue rules");
+      }
+      if let Some(fair_queue) = fair_queue {
+        settings.fair_queue = fair_queue;
+      }
+      if let Some(vote_skip) = vote_skip {
+        settings.vote_skip = vote_skip;
+      }
+      settings.save(&self.persistence, guild_id)?;
+    }
+
+    let on_off = |b: bool| if b { "on" } else { "off" };
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(
+          MessageBuilder::new()
+            .push_bold_line("Queue rules:")
+            .push_line(format!("Fair queue: {}", on_off(settings.fair_queue)))
+            .push(format!("Vote skip: {}", on_off(settings.vote_skip)))
+            .build(),
+        ),
+      )
+      .await?;
+    Ok(())
+  }
+}
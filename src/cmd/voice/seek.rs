@@ -0,0 +1,95 @@
+use super::{now_playing::format_time, SubCommandHandler};
+use crate::cmd::arg_util::Args;
+use anyhow::anyhow;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+};
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct Seek {}
+
+#[async_trait]
+impl SubCommandHandler for Seek {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    // 1 arg: timestamp. String, [[h:]m:]s
+    let position = parse_timestamp(args.str("timestamp")?)?;
+
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+
+    let handler_lock = manager
+      .get(guild_id)
+      .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
+    let current = handler_lock
+      .lock()
+      .await
+      .queue()
+      .current()
+      .ok_or_else(|| anyhow!("Nothing is playing"))?;
+
+    // Seeking may have to re-read the source up to the position, don't hold the call meanwhile
+    let landed = current
+      .seek_async(position)
+      .await
+      .map_err(|e| anyhow!("Couldn't seek there").context(e))?;
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(format!("Skipped to {}", format_time(landed))),
+      )
+      .await?;
+    Ok(())
+  }
+}
+
+/// Parses `s`, `m:ss` or `h:mm:ss` into a duration
+fn parse_timestamp(raw: &str) -> Result<Duration, anyhow::Error> {
+  let bad = || anyhow!("{raw} isn't a timestamp, try something like 1:23");
+  let parts = raw
+    .trim()
+    .split(':')
+    .map(|p| p.parse::<u64>().map_err(|_| bad()))
+    .collect::<Result<Vec<_>, _>>()?;
+  // Only the leading unit may overflow into the next, e.g. 90 or 90:00
+  if parts.len() > 3 || parts.iter().skip(1).any(|p| *p >= 60) {
+    return Err(bad());
+  }
+  let secs = parts.iter().fold(0, |acc, p| acc * 60 + p);
+  Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  #[test_case("45", 45 ; "Seconds")]
+  #[test_case("90", 90 ; "Overflowing seconds")]
+  #[test_case("1:23", 83 ; "Minutes")]
+  #[test_case("1:02:03", 3723 ; "Hours")]
+  #[test_case(" 0:05 ", 5 ; "Padded")]
+  fn parses_timestamps(raw: &str, secs: u64) {
+    assert_eq!(Duration::from_secs(secs), parse_timestamp(raw).unwrap());
+  }
+
+  #[test_case("" ; "Empty")]
+  #[test_case("1:60" ; "Seconds overflow")]
+  #[test_case("1:2:3:4" ; "Too many parts")]
+  #[test_case("1m30s" ; "Units")]
+  #[test_case("-5" ; "Negative")]
+  fn rejects_bad_timestamps(raw: &str) {
+    assert!(parse_timestamp(raw).is_err());
+  }
+}
@@ -0,0 +1,97 @@
+use super::filters::AudioFilters;
+use crate::{persistence::PersistentStore, types::Chan};
+use bincode::{Decode, Encode};
+use serenity::all::GuildId;
+use tracing::error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum LoopMode {
+  Off,
+  Track,
+  Queue,
+}
+
+/// Per guild playback preferences that outlive the call
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct VoiceSettings {
+  /// Percent, 100 being the source's own volume
+  pub volume: u32,
+  pub loop_mode: LoopMode,
+  /// Interleave the queue by requester instead of first come first served
+  pub fair_queue: bool,
+  /// Skipping someone else's track takes a majority of the listeners
+  pub vote_skip: bool,
+  pub filters: AudioFilters,
+  /// Where to play when whoever asked isn't in voice themselves
+  pub default_channel: Option<Chan>,
+}
+
+impl Default for VoiceSettings {
+  fn default() -> Self {
+    Self {
+      volume: 100,
+      loop_mode: LoopMode::Off,
+      fair_queue: false,
+      vote_skip: false,
+      filters: AudioFilters::default(),
+      default_channel: None,
+    }
+  }
+}
+
+impl VoiceSettings {
+  /// Loads the guild's settings, falling back to defaults if there are none or they're unreadable
+  pub fn load(persistence: &PersistentStore, guild_id: GuildId) -> Self {
+    match persistence.voice_settings().load(&guild_id) {
+      Ok(v) => v.unwrap_or_default(),
+      Err(e) => {
+        error!("Failed to load voice settings for {}: {}", guild_id, e);
+        Self::default()
+      }
+    }
+  }
+
+  pub fn save(
+    &self,
+    persistence: &PersistentStore,
+    guild_id: GuildId,
+  ) -> Result<(), anyhow::Error> {
+    persistence.voice_settings().save(&guild_id, self)
+  }
+
+  /// Volume as songbird wants it, where 1.0 is unchanged
+  pub fn gain(&self) -> f32 {
+    self.volume as f32 / 100.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn settings_default_until_saved() {
+    let temp_dir = tempdir().unwrap();
+    let store = PersistentStore::new(temp_dir.path().join("test.db")).unwrap();
+    let guild_id = GuildId::new(111111111);
+
+    let settings = VoiceSettings::load(&store, guild_id);
+    assert_eq!(100, settings.volume);
+    assert_eq!(1.0, settings.gain());
+
+    let saved = VoiceSettings {
+      volume: 150,
+      loop_mode: LoopMode::Queue,
+      fair_queue: true,
+      vote_skip: false,
+      filters: AudioFilters::default(),
+      default_channel: None,
+    };
+    saved.save(&store, guild_id).unwrap();
+    let loaded = VoiceSettings::load(&store, guild_id);
+    assert_eq!(150, loaded.volume);
+    assert_eq!(LoopMode::Queue, loaded.loop_mode);
+    assert!(loaded.fair_queue);
+  }
+}
@@ -5,6 +5,18 @@ use rand::seq::SliceRandom;
 use serenity::{
   all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
 };
+use songbird::tracks::TrackQueue;
+
+/// Shuffles everything after the currently playing track
+pub fn shuffle_queue(queue: &TrackQueue) {
+  queue.modify_queue(|f| {
+    let front = f.pop_front();
+    f.make_contiguous().shuffle(&mut rand::rng());
+    if let Some(v) = front {
+      f.push_front(v);
+    }
+  });
+}
 
 #[derive(Default)]
 pub struct Shuffle {}
@@ -30,13 +42,7 @@ impl SubCommandHandler for Shuffle {
       .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
     let handler = handler_lock.lock().await;
 
-    handler.queue().modify_queue(|f| {
-      let front = f.pop_front();
-      f.make_contiguous().shuffle(&mut rand::rng());
-      if let Some(v) = front {
-        f.push_front(v);
-      }
-    });
+    shuffle_queue(handler.queue());
 
     itx
       .edit_response(
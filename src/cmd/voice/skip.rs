@@ -1,4 +1,7 @@
-use super::SubCommandHandler;
+use super::{
+  vote_skip::{SkipOutcome, SkipVotes},
+  SubCommandHandler,
+};
 use crate::{cmd::arg_util::Args, emoji::EmojiLookup};
 use anyhow::anyhow;
 use derive_new::new;
@@ -6,10 +9,12 @@ use serenity::{
   all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
   utils::MessageBuilder,
 };
+use std::sync::Arc;
 
 #[derive(new)]
 pub struct Skip {
   emoji: EmojiLookup,
+  votes: Arc<SkipVotes>,
 }
 
 #[async_trait]
@@ -36,18 +41,22 @@ impl SubCommandHandler for Skip {
       .ok_or_else(|| anyhow!("Not in a voice channel to play in"))?;
     let handler = handler_lock.lock().await;
 
-    let queue = handler.queue();
-    let _ = queue.skip();
+    let content = match self
+      .votes
+      .request_skip(ctx, guild_id, itx.user.id, &handler)
+      .await?
+    {
+      SkipOutcome::Skipped => MessageBuilder::new()
+        .push("I didn't like that song either ")
+        .emoji(&emoji)
+        .build(),
+      SkipOutcome::Pending { votes, needed } => {
+        format!("That's not your tune. {votes} of {needed} votes to skip it")
+      }
+      SkipOutcome::NotListening => "Only people listening along get a vote".to_string(),
+    };
     itx
-      .edit_response(
-        &ctx.http,
-        EditInteractionResponse::new().content(
-          MessageBuilder::new()
-            .push("I didn't like that song either ")
-            .emoji(&emoji)
-            .build(),
-        ),
-      )
+      .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
       .await?;
 
     Ok(())
@@ -0,0 +1,538 @@
+use super::connect_util::{caller_channel, VoiceConnector};
+use crate::{
+  cmd::{arg_util::Args, AppInteractor},
+  config::Config,
+  persistence::PersistentStore,
+};
+use anyhow::{anyhow, bail};
+use bincode::{Decode, Encode};
+use derive_new::new;
+use serenity::{
+  all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, CommandType, ComponentInteraction, GuildId,
+    UserId,
+  },
+  async_trait,
+  builder::{
+    CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse,
+  },
+  client::Context,
+  utils::MessageBuilder,
+};
+use songbird::{
+  input::{File, Input},
+  tracks::Track,
+};
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::SystemTime,
+};
+use tracing::{error, info, instrument, warn};
+
+const PLAY_NAME: &str = "sound";
+const BOARD_NAME: &str = "soundboard";
+const BUTTON_PREFIX: &str = "sound:";
+const MAX_CLIP_BYTES: u32 = 1024 * 1024;
+const ALLOWED_EXTENSIONS: &[&str] = &["mp3", "wav", "ogg", "flac", "m4a"];
+// Discord allows 5 rows of 5 buttons on a single message
+const MAX_BUTTONS: usize = 25;
+
+#[derive(Clone, Encode, Decode)]
+pub struct SoundClip {
+  pub name: String,
+  pub file: String,
+  pub volume: f32,
+  pub uploaded_by: u64,
+  pub created_at: SystemTime,
+}
+
+#[derive(Clone, Default, Encode, Decode)]
+pub struct SoundLibrary {
+  pub clips: BTreeMap<String, SoundClip>,
+}
+
+impl SoundLibrary {
+  /// Clips are keyed by their normalized name, so look them up the same way
+  fn clip_mut(&mut self, name: &str) -> Result<&mut SoundClip, anyhow::Error> {
+    self
+      .clips
+      .get_mut(&name.trim().to_lowercase())
+      .ok_or_else(|| anyhow!("No clip named {name}"))
+  }
+
+  fn take_clip(&mut self, name: &str) -> Result<SoundClip, anyhow::Error> {
+    self
+      .clips
+      .remove(&name.trim().to_lowercase())
+      .ok_or_else(|| anyhow!("No clip named {name}"))
+  }
+}
+
+#[derive(new)]
+pub struct Soundboard {
+  config: Config,
+  connector: VoiceConnector,
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl AppInteractor for Soundboard {
+  #[instrument(name = "Soundboard", level = "INFO", skip(self))]
+  fn commands(&self) -> Vec<CreateCommand> {
+    let clip_name = || {
+      CreateCommandOption::new(CommandOptionType::String, "name", "Name of the clip")
+        .required(true)
+        .max_length(32)
+    };
+    vec![
+      CreateCommand::new(PLAY_NAME)
+        .description("Binkies makes a noise, right now")
+        .kind(CommandType::ChatInput)
+        .add_option(clip_name()),
+      CreateCommand::new(BOARD_NAME)
+        .description("Binkies' bag of noises")
+        .kind(CommandType::ChatInput)
+        .add_option(CreateCommandOption::new(
+          CommandOptionType::SubCommand,
+          "show",
+          "Post a button for every clip",
+        ))
+        .add_option(
+          CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "upload",
+            "Add a clip to the board (admins only)",
+          )
+          .add_sub_option(clip_name())
+          .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Attachment, "clip", "Short audio file")
+              .required(true),
+          )
+          .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "volume", "Percent, 0-200")
+              .min_int_value(0)
+              .max_int_value(200),
+          ),
+        )
+        .add_option(
+          CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "remove",
+            "Drop a clip from the board (admins only)",
+          )
+          .add_sub_option(clip_name()),
+        )
+        .add_option(
+          CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "volume",
+            "Change how loud a clip plays (admins only)",
+          )
+          .add_sub_option(clip_name())
+          .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "volume", "Percent, 0-200")
+              .required(true)
+              .min_int_value(0)
+              .max_int_value(200),
+          ),
+        ),
+    ]
+  }
+
+  #[instrument(name = "Soundboard", level = "INFO", skip(self, ctx, itx))]
+  async fn app_interact(&self, ctx: &Context, itx: &CommandInteraction) {
+    let name = itx.data.name.as_str();
+    if name != PLAY_NAME && name != BOARD_NAME {
+      return;
+    }
+
+    if let Err(e) = itx
+      .create_response(
+        &ctx.http,
+        CreateInteractionResponse::Message(
+          CreateInteractionResponseMessage::new().content("Loading..."),
+        ),
+      )
+      .await
+    {
+      error!("{:?}", e);
+      return;
+    }
+
+    let top_args = itx.data.options();
+    let res = match name {
+      PLAY_NAME => self.play(ctx, itx, &Args::from(&top_args)).await,
+      _ => {
+        let subopt = top_args.first().expect("Discord did not pass sub-opt");
+        let args = match &subopt.value {
+          serenity::all::ResolvedValue::SubCommand(c) => Args::from(c),
+          _ => unreachable!("Dev error - subopt was not subcommand"),
+        };
+        match subopt.name {
+          "show" => self.show(ctx, itx).await,
+          "upload" => self.upload(ctx, itx, &args).await,
+          "remove" => self.remove(ctx, itx, &args).await,
+          "volume" => self.volume(ctx, itx, &args).await,
+          _ => unreachable!(),
+        }
+      }
+    };
+
+    if let Err(e) = res {
+      error!("{:?}", e);
+      let _ = itx
+        .edit_response(
+          &ctx.http,
+          EditInteractionResponse::new().content(format!("{e}")),
+        )
+        .await;
+    }
+  }
+
+  #[instrument(name = "Soundboard", level = "INFO", skip(self, ctx, itx))]
+  async fn msg_interact(&self, ctx: &Context, itx: &ComponentInteraction) {
+    let Some(clip) = itx.data.custom_id.strip_prefix(BUTTON_PREFIX) else {
+      return;
+    };
+    let res = match itx.guild_id {
+      Some(guild_id) => self.play_clip(ctx, guild_id, itx.user.id, clip).await,
+      None => Err(anyhow!("No Guild Id on Interaction")),
+    };
+    let resp = match res {
+      Ok(_) => itx.defer(&ctx.http).await,
+      Err(e) => {
+        itx
+          .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+              CreateInteractionResponseMessage::new()
+                .content(format!("{e}"))
+                .ephemeral(true),
+            ),
+          )
+          .await
+      }
+    };
+    if let Err(e) = resp {
+      error!("Failed to respond to soundboard press {:?}", e);
+    }
+  }
+}
+
+impl Soundboard {
+  async fn play(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args<'_>,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let name = args
+      .str("name")
+      .map_err(|e| anyhow!("Must provide a clip name").context(e))?;
+    self.play_clip(ctx, guild_id, itx.user.id, name).await?;
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(MessageBuilder::new().push_mono(name).build()),
+      )
+      .await?;
+    Ok(())
+  }
+
+  async fn play_clip(
+    &self,
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    name: &str,
+  ) -> Result<(), anyhow::Error> {
+    let clip = self.library(guild_id)?.take_clip(name)?;
+    let channel_id = caller_channel(ctx, guild_id, user_id)?;
+    let handler_lock = self.connector.connect(ctx, guild_id, channel_id).await?;
+
+    // Clips are played alongside the queue rather than through it, so they
+    // land immediately and the mixer lays them over whatever is playing
+    info!("Playing clip {}", clip.name);
+    let input = Input::from(File::new(PathBuf::from(&clip.file)));
+    let mut handler = handler_lock.lock().await;
+    handler.play(Track::from(input).volume(clip.volume));
+    Ok(())
+  }
+
+  async fn show(&self, ctx: &Context, itx: &CommandInteraction) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+    let library = self.library(guild_id)?;
+    if library.clips.is_empty() {
+      bail!("No clips yet, have an admin upload some");
+    }
+
+    let buttons: Vec<CreateButton> = library
+      .clips
+      .keys()
+      .take(MAX_BUTTONS)
+      .map(|name| {
+        CreateButton::new(format!("{BUTTON_PREFIX}{name}"))
+          .label(name)
+          .style(ButtonStyle::Secondary)
+      })
+      .collect();
+    let rows = buttons
+      .chunks(5)
+      .map(|row| CreateActionRow::Buttons(row.to_vec()))
+      .collect();
+
+    let mut content = MessageBuilder::new();
+    content.push_bold("Soundboard");
+    if library.clips.len() > MAX_BUTTONS {
+      content.push_italic(format!(
+        " (showing {MAX_BUTTONS} of {}, use /{PLAY_NAME} for the rest)",
+        library.clips.len()
+      ));
+    }
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new()
+          .content(content.build())
+          .components(rows),
+      )
+      .await?;
+    Ok(())
+  }
+
+  async fn upload(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args<'_>,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = require_admin(itx)?;
+    let name = validate_name(
+      args
+        .str("name")
+        .map_err(|e| anyhow!("Must provide a clip name").context(e))?,
+    )?;
+    let attachment = args
+      .attachment("clip")
+      .map_err(|e| anyhow!("Must attach an audio clip").context(e))?;
+    let volume = match args.i64("volume") {
+      Ok(v) => percent_to_volume(*v),
+      Err(_) => 1.0,
+    };
+
+    if attachment.size > MAX_CLIP_BYTES {
+      bail!(
+        "Clips must be under {}KiB, that one's {}KiB",
+        MAX_CLIP_BYTES / 1024,
+        attachment.size / 1024
+      );
+    }
+    let ext = Path::new(&attachment.filename)
+      .extension()
+      .and_then(|e| e.to_str())
+      .map(|e| e.to_lowercase())
+      .filter(|e| ALLOWED_EXTENSIONS.contains(&e.as_str()))
+      .ok_or_else(|| anyhow!("Clip must be one of: {}", ALLOWED_EXTENSIONS.join(", ")))?;
+
+    let dir = Path::new(&self.config.soundboard_dir).join(guild_id.to_string());
+    tokio::fs::create_dir_all(&dir).await?;
+    let file = dir.join(format!("{name}.{ext}"));
+    let bytes = attachment.download().await?;
+    tokio::fs::write(&file, bytes).await?;
+
+    let mut library = self.library(guild_id)?;
+    if let Some(old) = library.clips.get(&name) {
+      // Replacing with a different format leaves the old file behind
+      if Path::new(&old.file) != file {
+        remove_clip_file(old);
+      }
+    }
+    library.clips.insert(
+      name.clone(),
+      SoundClip {
+        name: name.clone(),
+        file: file.to_string_lossy().to_string(),
+        volume,
+        uploaded_by: itx.user.id.get(),
+        created_at: SystemTime::now(),
+      },
+    );
+    self.persistence.soundboards().save(&guild_id, &library)?;
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(
+          MessageBuilder::new()
+            .push_bold("Added ")
+            .push_mono(&name)
+            .push(" to the board")
+            .build(),
+        ),
+      )
+      .await?;
+    Ok(())
+  }
+
+  async fn remove(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args<'_>,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = require_admin(itx)?;
+    let name = args
+      .str("name")
+      .map_err(|e| anyhow!("Must provide a clip name").context(e))?;
+    let mut library = self.library(guild_id)?;
+    let clip = library.take_clip(name)?;
+    self.persistence.soundboards().save(&guild_id, &library)?;
+    remove_clip_file(&clip);
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(
+          MessageBuilder::new()
+            .push_bold("Removed ")
+            .push_mono(name)
+            .build(),
+        ),
+      )
+      .await?;
+    Ok(())
+  }
+
+  async fn volume(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args<'_>,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = require_admin(itx)?;
+    let name = args
+      .str("name")
+      .map_err(|e| anyhow!("Must provide a clip name").context(e))?;
+    let percent = args
+      .i64("volume")
+      .map_err(|e| anyhow!("Must provide a volume").context(e))?;
+    let mut library = self.library(guild_id)?;
+    let clip = library.clip_mut(name)?;
+    clip.volume = percent_to_volume(*percent);
+    self.persistence.soundboards().save(&guild_id, &library)?;
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(
+          MessageBuilder::new()
+            .push_mono(name)
+            .push(format!(" now plays at {percent}%"))
+            .build(),
+        ),
+      )
+      .await?;
+    Ok(())
+  }
+
+  fn library(&self, guild_id: GuildId) -> Result<SoundLibrary, anyhow::Error> {
+    Ok(
+      self
+        .persistence
+        .soundboards()
+        .load(&guild_id)?
+        .unwrap_or_default(),
+    )
+  }
+}
+
+fn require_admin(itx: &CommandInteraction) -> Result<GuildId, anyhow::Error> {
+  let guild_id = itx
+    .guild_id
+    .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+  let is_admin = itx
+    .member
+    .as_ref()
+    .and_then(|m| m.permissions)
+    .is_some_and(|p| p.manage_guild());
+  if !is_admin {
+    bail!("Only server managers can change the soundboard");
+  }
+  Ok(guild_id)
+}
+
+fn remove_clip_file(clip: &SoundClip) {
+  if let Err(e) = std::fs::remove_file(&clip.file) {
+    warn!("Failed to remove clip file {}: {}", clip.file, e);
+  }
+}
+
+fn percent_to_volume(percent: i64) -> f32 {
+  percent.clamp(0, 200) as f32 / 100.0
+}
+
+fn validate_name(name: &str) -> Result<String, anyhow::Error> {
+  let name = name.trim().to_lowercase();
+  if name.is_empty()
+    || !name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+  {
+    bail!("Clip names can only contain letters, numbers, underscores and dashes");
+  }
+  Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn clip_names_are_normalized() {
+    assert_eq!("airhorn", validate_name(" AirHorn ").unwrap());
+    assert_eq!("bruh_2-electric", validate_name("bruh_2-electric").unwrap());
+  }
+
+  #[test]
+  fn clip_names_reject_unsafe_chars() {
+    assert!(validate_name("").is_err());
+    assert!(validate_name("../etc").is_err());
+    assert!(validate_name("two words").is_err());
+  }
+
+  #[test]
+  fn clips_found_whatever_the_case() {
+    let name = validate_name("Airhorn").unwrap();
+    let mut library = SoundLibrary::default();
+    library.clips.insert(
+      name.clone(),
+      SoundClip {
+        name,
+        file: "sounds/1/airhorn.mp3".to_string(),
+        volume: 1.0,
+        uploaded_by: 1,
+        created_at: SystemTime::UNIX_EPOCH,
+      },
+    );
+
+    library.clip_mut("AIRHORN").unwrap().volume = 0.5;
+    assert!(library.clip_mut("horn").is_err());
+    assert_eq!(0.5, library.take_clip("Airhorn ").unwrap().volume);
+    assert!(library.clips.is_empty());
+  }
+
+  #[test]
+  fn volume_is_clamped() {
+    assert_eq!(1.0, percent_to_volume(100));
+    assert_eq!(2.0, percent_to_volume(500));
+    assert_eq!(0.0, percent_to_volume(-5));
+  }
+}
@@ -0,0 +1,614 @@
+use super::play::ListMetadata;
+use anyhow::anyhow;
+use bincode::{Decode, Encode};
+use reqwest::{Client, Url};
+use serenity::async_trait;
+use songbird::input::{
+  codecs::get_probe, AudioStream, AudioStreamError, Compose, File, HttpRequest, YoutubeDl,
+};
+use std::{
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Duration,
+};
+use symphonia::core::{
+  formats::FormatOptions,
+  io::{MediaSource, MediaSourceStream},
+  meta::MetadataOptions,
+};
+use tracing::{info, warn};
+
+/// Links ending in these are plain audio files we can fetch ourselves
+const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "ogg", "opus", "flac", "wav", "m4a", "aac", "webm"];
+/// Links ending in these list a radio station's stream
+const PLAYLIST_EXTENSIONS: [&str; 2] = ["m3u", "pls"];
+/// Queries naming a file in the music folder start with this
+const FILE_PREFIX: &str = "file:";
+
+/// Where a track's audio comes from, which decides how it's fetched
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Encode, Decode)]
+pub enum SourceKind {
+  #[default]
+  YouTube,
+  Http,
+  File,
+  Radio,
+}
+
+impl SourceKind {
+  pub fn parse(raw: &str) -> Result<Self, anyhow::Error> {
+    match raw {
+      "youtube" => Ok(SourceKind::YouTube),
+      "http" => Ok(SourceKind::Http),
+      "file" => Ok(SourceKind::File),
+      "radio" => Ok(SourceKind::Radio),
+      s => Err(anyhow!("Unknown source {s}")),
+    }
+  }
+}
+
+/// A query turned into something playable, along with what to list it as
+pub struct Resolved {
+  pub source: Box<dyn Compose>,
+  pub meta: ListMetadata,
+}
+
+/// Turns what someone typed into /play into audio
+#[async_trait]
+pub trait Resolver: Send + Sync {
+  fn kind(&self) -> SourceKind;
+
+  /// Whether the query looks like one of ours, for when no source was asked for
+  fn matches(&self, query: &str) -> bool;
+
+  /// Looks the query up, fetching whatever details it can for the listing
+  async fn resolve(&self, query: &str) -> Result<Resolved, anyhow::Error>;
+
+  /// Recreates the source for a track resolved earlier, from the url it was listed with
+  fn source(&self, url: &str) -> Box<dyn Compose>;
+}
+
+/// Every resolver we have, tried most particular first
+#[derive(Clone)]
+pub struct Sources {
+  resolvers: Vec<Arc<dyn Resolver>>,
+}
+
+impl Sources {
+  pub fn new(http_client: Client, music_dir: PathBuf) -> Self {
+    Self {
+      resolvers: vec![
+        Arc::new(LocalFile { root: music_dir }),
+        Arc::new(Radio {
+          client: http_client.clone(),
+        }),
+        Arc::new(HttpAudio {
+          client: http_client.clone(),
+        }),
+        // Anything else yt-dlp can likely make sense of, searches included
+        Arc::new(YtDlp {
+          client: http_client,
+        }),
+      ],
+    }
+  }
+
+  fn get(&self, kind: SourceKind) -> &dyn Resolver {
+    self
+      .resolvers
+      .iter()
+      .find(|r| r.kind() == kind)
+      .expect("Dev error - every source kind has a resolver")
+      .as_ref()
+  }
+
+  /// Which source the query would go to were none asked for
+  pub fn kind_for(&self, query: &str) -> SourceKind {
+    self
+      .resolvers
+      .iter()
+      .find(|r| r.matches(query))
+      .map_or(SourceKind::YouTube, |r| r.kind())
+  }
+
+  /// Resolves the query with the given source, or whichever recognises it
+  pub async fn resolve(
+    &self,
+    query: &str,
+    kind: Option<SourceKind>,
+  ) -> Result<Resolved, anyhow::Error> {
+    let kind = kind.unwrap_or_else(|| self.kind_for(query));
+    info!("Resolving {} as {:?}", query, kind);
+    self.get(kind).resolve(query).await
+  }
+
+  /// Recreates the source for a track we've queued before
+  pub fn source(&self, meta: &ListMetadata) -> Box<dyn Compose> {
+    self.get(meta.source).source(&meta.url)
+  }
+}
+
+fn metadata(
+  title: String,
+  url: String,
+  duration: Option<Duration>,
+  source: SourceKind,
+) -> ListMetadata {
+  ListMetadata {
+    title,
+    url,
+    duration,
+    requested_by: None,
+    requested_at: None,
+    source,
+  }
+}
+
+fn http_url(query: &str) -> Option<Url> {
+  Url::parse(query)
+    .ok()
+    .filter(|u| matches!(u.scheme(), "http" | "https"))
+}
+
+fn has_extension(path: &str, extensions: &[&str]) -> bool {
+  Path::new(path)
+    .extension()
+    .and_then(|e| e.to_str())
+    .is_some_and(|e| extensions.contains(&e.to_lowercase().as_str()))
+}
+
+/// Last part of the path, so the listing shows more than a bare host
+fn file_name(url: &Url) -> String {
+  url
+    .path_segments()
+    .and_then(|mut s| s.next_back())
+    .filter(|s| !s.is_empty())
+    .or(url.host_str())
+    .unwrap_or("<UNKNOWN>")
+    .to_string()
+}
+
+/// Opens the source just far enough to find out how long it runs. Errors if
+/// it can't be opened at all, since then it won't play either.
+async fn probe_duration(source: &mut dyn Compose) -> Result<Option<Duration>, anyhow::Error> {
+  let stream = source
+    .create_async()
+    .await
+    .map_err(|e| anyhow!("Couldn't open source: {e}"))?;
+  // Probing reads from the stream, which blocks
+  tokio::task::spawn_blocking(move || duration_of(stream)).await?
+}
+
+fn duration_of(
+  stream: AudioStream<Box<dyn MediaSource>>,
+) -> Result<Option<Duration>, anyhow::Error> {
+  let probed = get_probe().format(
+    &stream.hint.unwrap_or_default(),
+    MediaSourceStream::new(stream.input, Default::default()),
+    &FormatOptions::default(),
+    &MetadataOptions::default(),
+  )?;
+  let params = &probed
+    .format
+    .default_track()
+    .ok_or_else(|| anyhow!("Source has no audio track"))?
+    .codec_params;
+  Ok(match (params.time_base, params.n_frames) {
+    (Some(base), Some(frames)) => {
+      let time = base.calc_time(frames);
+      Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+    }
+    _ => None,
+  })
+}
+
+/// Anything yt-dlp understands, or a search on YT if it's not a link
+struct YtDlp {
+  client: Client,
+}
+
+#[async_trait]
+impl Resolver for YtDlp {
+  fn kind(&self) -> SourceKind {
+    SourceKind::YouTube
+  }
+
+  fn matches(&self, _: &str) -> bool {
+    true
+  }
+
+  async fn resolve(&self, query: &str) -> Result<Resolved, anyhow::Error> {
+    let mut source = match query.starts_with("http") {
+      false => YoutubeDl::new_search(self.client.clone(), query.to_string()),
+      true => YoutubeDl::new(self.client.clone(), query.to_string()),
+    };
+    let meta = source
+      .aux_metadata()
+      .await
+      .map(|m| {
+        metadata(
+          m.track
+            .or(m.title)
+            .unwrap_or_else(|| "<UNKNOWN>".to_string()),
+          m.source_url.unwrap_or_else(|| "<UNKNOWN>".to_string()),
+          m.duration,
+          SourceKind::YouTube,
+        )
+      })
+      .unwrap_or_else(|_| {
+        metadata(
+          "<UNKNOWN>".to_string(),
+          "<UNKNOWN>".to_string(),
+          None,
+          SourceKind::YouTube,
+        )
+      });
+    Ok(Resolved {
+      source: Box::new(source),
+      meta,
+    })
+  }
+
+  fn source(&self, url: &str) -> Box<dyn Compose> {
+    Box::new(YoutubeDl::new(self.client.clone(), url.to_string()))
+  }
+}
+
+/// Links straight to an audio file
+struct HttpAudio {
+  client: Client,
+}
+
+#[async_trait]
+impl Resolver for HttpAudio {
+  fn kind(&self) -> SourceKind {
+    SourceKind::Http
+  }
+
+  fn matches(&self, query: &str) -> bool {
+    http_url(query).is_some_and(|u| has_extension(u.path(), &AUDIO_EXTENSIONS))
+  }
+
+  async fn resolve(&self, query: &str) -> Result<Resolved, anyhow::Error> {
+    let url = http_url(query).ok_or_else(|| anyhow!("Not a link: {query}"))?;
+    let mut source = self.source(query);
+    let duration = probe_duration(source.as_mut()).await?;
+    Ok(Resolved {
+      source,
+      meta: metadata(
+        file_name(&url),
+        query.to_string(),
+        duration,
+        SourceKind::Http,
+      ),
+    })
+  }
+
+  fn source(&self, url: &str) -> Box<dyn Compose> {
+    Box::new(HttpRequest::new(self.client.clone(), url.to_string()))
+  }
+}
+
+/// Files sitting in the music folder. Nothing outside it is reachable.
+struct LocalFile {
+  root: PathBuf,
+}
+
+impl LocalFile {
+  fn path(&self, query: &str) -> Result<PathBuf, anyhow::Error> {
+    let relative = query.strip_prefix(FILE_PREFIX).unwrap_or(query);
+    let root = self
+      .root
+      .canonicalize()
+      .map_err(|e| anyhow!("Music folder unavailable: {e}"))?;
+    let path = root
+      .join(relative)
+      .canonicalize()
+      .map_err(|_| anyhow!("No file called {relative}"))?;
+    match path.starts_with(&root) && path.is_file() {
+      true => Ok(path),
+      false => Err(anyhow!("No file called {relative}")),
+    }
+  }
+}
+
+#[async_trait]
+impl Resolver for LocalFile {
+  fn kind(&self) -> SourceKind {
+    SourceKind::File
+  }
+
+  fn matches(&self, query: &str) -> bool {
+    query.starts_with(FILE_PREFIX)
+  }
+
+  async fn resolve(&self, query: &str) -> Result<Resolved, anyhow::Error> {
+    let path = self.path(query)?;
+    let relative = path
+      .strip_prefix(self.root.canonicalize()?)?
+      .to_string_lossy()
+      .to_string();
+    let title = path
+      .file_stem()
+      .map_or_else(|| relative.clone(), |s| s.to_string_lossy().to_string());
+    let mut source: Box<dyn Compose> = Box::new(File::new(path));
+    let duration = probe_duration(source.as_mut()).await?;
+    Ok(Resolved {
+      source,
+      meta: metadata(
+        title,
+        format!("{FILE_PREFIX}{relative}"),
+        duration,
+        SourceKind::File,
+      ),
+    })
+  }
+
+  fn source(&self, url: &str) -> Box<dyn Compose> {
+    let relative = url.strip_prefix(FILE_PREFIX).unwrap_or(url);
+    // Only urls we resolved ourselves end up here, and those were checked
+    Box::new(File::new(self.root.join(relative)))
+  }
+}
+
+/// Internet radio, either the stream itself or an m3u/pls pointing at it
+struct Radio {
+  client: Client,
+}
+
+#[async_trait]
+impl Resolver for Radio {
+  fn kind(&self) -> SourceKind {
+    SourceKind::Radio
+  }
+
+  fn matches(&self, query: &str) -> bool {
+    http_url(query).is_some_and(|u| has_extension(u.path(), &PLAYLIST_EXTENSIONS))
+  }
+
+  async fn resolve(&self, query: &str) -> Result<Resolved, anyhow::Error> {
+    let url = http_url(query).ok_or_else(|| anyhow!("Not a link: {query}"))?;
+    let stream = stream_url(&self.client, query).await?;
+    // Icecast and shoutcast name the station in the headers
+    let resp = self.client.get(&stream).send().await?.error_for_status()?;
+    let title = resp
+      .headers()
+      .get("icy-name")
+      .and_then(|v| v.to_str().ok())
+      .map(str::to_string)
+      .unwrap_or_else(|| file_name(&url));
+    drop(resp);
+    Ok(Resolved {
+      source: self.source(query),
+      // Streams never end, so there's no duration to speak of
+      meta: metadata(title, query.to_string(), None, SourceKind::Radio),
+    })
+  }
+
+  fn source(&self, url: &str) -> Box<dyn Compose> {
+    Box::new(RadioStream {
+      client: self.client.clone(),
+      url: url.to_string(),
+    })
+  }
+}
+
+/// Reads the station's playlist afresh each time it's played, streams move about
+struct RadioStream {
+  client: Client,
+  url: String,
+}
+
+#[async_trait]
+impl Compose for RadioStream {
+  fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+    Err(AudioStreamError::Unsupported)
+  }
+
+  async fn create_async(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+    let stream = stream_url(&self.client, &self.url)
+      .await
+      .map_err(|e| AudioStreamError::Fail(e.into()))?;
+    HttpRequest::new(self.client.clone(), stream)
+      .create_async()
+      .await
+  }
+
+  fn should_create_async(&self) -> bool {
+    true
+  }
+}
+
+/// Follows a station playlist to its stream, or passes a bare stream through
+async fn stream_url(client: &Client, url: &str) -> Result<String, anyhow::Error> {
+  if !has_extension(url.split('?').next().unwrap_or(url), &PLAYLIST_EXTENSIONS) {
+    return Ok(url.to_string());
+  }
+  let body = client
+    .get(url)
+    .send()
+    .await?
+    .error_for_status()?
+    .text()
+    .await?;
+  first_stream(&body).ok_or_else(|| {
+    warn!("No streams in playlist {}", url);
+    anyhow!("That playlist has no streams in it")
+  })
+}
+
+/// First stream listed in an m3u or pls playlist
+fn first_stream(playlist: &str) -> Option<String> {
+  playlist
+    .lines()
+    .map(str::trim)
+    .find_map(|line| match line.split_once('=') {
+      // pls: File1=http://...
+      Some((key, value)) if key.to_lowercase().starts_with("file") => Some(value),
+      // m3u: anything that isn't a comment or directive
+      _ if line.starts_with("http") => Some(line),
+      _ => None,
+    })
+    .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+  use test_case::test_case;
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+  };
+
+  /// Mono 16 bit PCM wav of silence
+  fn wav(secs: u32) -> Vec<u8> {
+    let rate: u32 = 8000;
+    let data_len = rate * secs * 2;
+    let mut out = Vec::new();
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&rate.to_le_bytes());
+    out.extend_from_slice(&(rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    out.resize(out.len() + data_len as usize, 0);
+    out
+  }
+
+  /// Stands in for the web: a song, a radio station and its playlist.
+  /// Returns the address it's listening on.
+  async fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let playlist = format!("#EXTM3U\n#EXTINF:-1,Binkies FM\n{base}/live\n");
+    tokio::spawn(async move {
+      loop {
+        let Ok((mut conn, _)) = listener.accept().await else {
+          return;
+        };
+        let playlist = playlist.clone();
+        tokio::spawn(async move {
+          let mut buf = vec![0; 4096];
+          let n = conn.read(&mut buf).await.unwrap_or(0);
+          let request = String::from_utf8_lossy(&buf[..n]);
+          let path = request.split_whitespace().nth(1).unwrap_or("/");
+          let (status, headers, body) = match path {
+            "/music/song.wav" => ("200 OK", "Content-Type: audio/wav\r\n", wav(2)),
+            "/live" => (
+              "200 OK",
+              "Content-Type: audio/wav\r\nicy-name: Binkies FM\r\n",
+              wav(1),
+            ),
+            "/station.m3u" => ("200 OK", "", playlist.into_bytes()),
+            _ => ("404 Not Found", "", vec![]),
+          };
+          let head = format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+          );
+          let _ = conn.write_all(head.as_bytes()).await;
+          let _ = conn.write_all(&body).await;
+        });
+      }
+    });
+    base
+  }
+
+  fn sources(music_dir: &Path) -> Sources {
+    Sources::new(Client::new(), music_dir.to_path_buf())
+  }
+
+  #[test_case("https://example.com/music/song.mp3" => SourceKind::Http; "audio link")]
+  #[test_case("https://example.com/song.FLAC?x=1" => SourceKind::Http; "audio link with query")]
+  #[test_case("https://example.com/station.m3u" => SourceKind::Radio; "m3u playlist")]
+  #[test_case("http://example.com/listen.pls" => SourceKind::Radio; "pls playlist")]
+  #[test_case("file:albums/song.mp3" => SourceKind::File; "local file")]
+  #[test_case("https://www.youtube.com/watch?v=dQw4w9WgXcQ" => SourceKind::YouTube; "video link")]
+  #[test_case("never gonna give you up" => SourceKind::YouTube; "search")]
+  fn sources_picked_by_pattern(query: &str) -> SourceKind {
+    sources(Path::new("music")).kind_for(query)
+  }
+
+  #[test_case("#EXTM3U\n#EXTINF:-1,Station\nhttp://a/stream\n" => Some("http://a/stream".to_string()); "m3u")]
+  #[test_case("[playlist]\nNumberOfEntries=1\nFile1=http://b/stream\nTitle1=B\n" => Some("http://b/stream".to_string()); "pls")]
+  #[test_case("#EXTM3U\n" => None; "empty")]
+  fn playlists_lead_to_streams(playlist: &str) -> Option<String> {
+    first_stream(playlist)
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn http_audio_resolves_with_duration() {
+    let base = serve().await;
+    let sources = sources(Path::new("music"));
+    let url = format!("{base}/music/song.wav");
+
+    let resolved = sources.resolve(&url, None).await.unwrap();
+    assert_eq!(SourceKind::Http, resolved.meta.source);
+    assert_eq!("song.wav", resolved.meta.title);
+    assert_eq!(url, resolved.meta.url);
+    assert_eq!(Some(Duration::from_secs(2)), resolved.meta.duration);
+
+    // What comes back from a restart or loop should play just the same
+    let mut rebuilt = sources.source(&resolved.meta);
+    assert!(rebuilt.create_async().await.is_ok());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn http_audio_errors_when_missing() {
+    let base = serve().await;
+    let url = format!("{base}/music/gone.mp3");
+    assert!(sources(Path::new("music"))
+      .resolve(&url, None)
+      .await
+      .is_err());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn radio_follows_playlist_to_station() {
+    let base = serve().await;
+    let sources = sources(Path::new("music"));
+
+    let resolved = sources
+      .resolve(&format!("{base}/station.m3u"), None)
+      .await
+      .unwrap();
+    assert_eq!(SourceKind::Radio, resolved.meta.source);
+    assert_eq!("Binkies FM", resolved.meta.title);
+    assert_eq!(None, resolved.meta.duration);
+    let mut source = resolved.source;
+    assert!(source.create_async().await.is_ok());
+
+    // Asking for radio by name works on bare streams too
+    let resolved = sources
+      .resolve(&format!("{base}/live"), Some(SourceKind::Radio))
+      .await
+      .unwrap();
+    assert_eq!("Binkies FM", resolved.meta.title);
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn local_files_stay_in_music_folder() {
+    let temp_dir = tempdir().unwrap();
+    let music = temp_dir.path().join("music");
+    std::fs::create_dir_all(music.join("albums")).unwrap();
+    std::fs::write(music.join("albums/song.wav"), wav(1)).unwrap();
+    std::fs::write(temp_dir.path().join("secret.wav"), wav(1)).unwrap();
+    let sources = sources(&music);
+
+    let resolved = sources.resolve("file:albums/song.wav", None).await.unwrap();
+    assert_eq!("song", resolved.meta.title);
+    assert_eq!("file:albums/song.wav", resolved.meta.url);
+    assert_eq!(Some(Duration::from_secs(1)), resolved.meta.duration);
+
+    assert!(sources.resolve("file:../secret.wav", None).await.is_err());
+    assert!(sources.resolve("file:nope.wav", None).await.is_err());
+  }
+}
@@ -1,5 +1,6 @@
 use super::SubCommandHandler;
-use crate::cmd::{arg_util::Args, voice::connect_util::DisconnectMessage};
+use crate::cmd::{arg_util::Args, voice::disconnect::DisconnectMessage};
+use anyhow::anyhow;
 use derive_new::new;
 use kitchen_sink::actor::ActorHandle;
 use serenity::{all::CommandInteraction, async_trait, client::Context};
@@ -15,13 +16,16 @@ impl SubCommandHandler for Stop {
   async fn handle(
     &self,
     _ctx: &Context,
-    _itx: &CommandInteraction,
+    itx: &CommandInteraction,
     _: &Args,
   ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
     info!("Stopping voice playback");
     let _ = self
       .disconnect
-      .send(DisconnectMessage::Disconnect(true))
+      .send(DisconnectMessage::Disconnect(guild_id, true))
       .await;
     Ok(())
   }
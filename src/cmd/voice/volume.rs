@@ -0,0 +1,56 @@
+use super::{settings::VoiceSettings, SubCommandHandler};
+use crate::{cmd::arg_util::Args, persistence::PersistentStore};
+use anyhow::anyhow;
+use derive_new::new;
+use serenity::{
+  all::CommandInteraction, async_trait, builder::EditInteractionResponse, client::Context,
+};
+use std::sync::Arc;
+
+#[derive(new)]
+pub struct Volume {
+  persistence: Arc<PersistentStore>,
+}
+
+#[async_trait]
+impl SubCommandHandler for Volume {
+  async fn handle(
+    &self,
+    ctx: &Context,
+    itx: &CommandInteraction,
+    args: &Args,
+  ) -> Result<(), anyhow::Error> {
+    let guild_id = itx
+      .guild_id
+      .ok_or_else(|| anyhow!("No Guild Id on Interaction"))?;
+
+    // 1 arg: volume. Integer, 0-200.
+    let volume = u32::try_from(*args.i64("volume")?)
+      .ok()
+      .filter(|v| *v <= 200)
+      .ok_or_else(|| anyhow!("Volume must be between 0 and 200"))?;
+
+    let mut settings = VoiceSettings::load(&self.persistence, guild_id);
+    settings.volume = volume;
+    settings.save(&self.persistence, guild_id)?;
+
+    // Anything already queued should sound the same as what comes next
+    let manager = songbird::get(ctx)
+      .await
+      .expect("Songbird Voice client placed in at initialisation.");
+    if let Some(handler_lock) = manager.get(guild_id) {
+      let handler = handler_lock.lock().await;
+      for trk in handler.queue().current_queue() {
+        let _ = trk.set_volume(settings.gain());
+      }
+    }
+
+    itx
+      .edit_response(
+        &ctx.http,
+        EditInteractionResponse::new().content(format!("Volume set to {volume}%")),
+      )
+      .await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,199 @@
+use super::{
+  connect_util::{caller_channel, listeners},
+  play::track_metadata,
+  settings::VoiceSettings,
+};
+use crate::persistence::PersistentStore;
+use anyhow::anyhow;
+use serenity::{
+  all::{ChannelId, GuildId, UserId},
+  client::Context,
+  prelude::Mutex,
+};
+use songbird::Call;
+use std::{
+  collections::{HashMap, HashSet},
+  sync::Arc,
+};
+use tracing::info;
+use uuid::Uuid;
+
+pub enum SkipOutcome {
+  Skipped,
+  /// Not enough listeners agree yet, `votes` of the `needed`
+  Pending {
+    votes: usize,
+    needed: usize,
+  },
+  /// The voter isn't in the channel being played to, so their vote doesn't count
+  NotListening,
+}
+
+/// Votes towards skipping the current track, forgotten as soon as it changes
+struct Ballot {
+  track: Uuid,
+  voters: HashSet<UserId>,
+}
+
+impl Ballot {
+  fn cast(&mut self, track: Uuid, voter: UserId) -> usize {
+    if self.track != track {
+      self.track = track;
+      self.voters.clear();
+    }
+    self.voters.insert(voter);
+    self.voters.len()
+  }
+}
+
+/// Gatekeeps skipping when the guild has vote skip turned on. People can always
+/// skip their own tracks, anyone else's needs a majority of the channel.
+pub struct SkipVotes {
+  persistence: Arc<PersistentStore>,
+  ballots: Mutex<HashMap<GuildId, Ballot>>,
+}
+
+impl SkipVotes {
+  pub fn new(persistence: Arc<PersistentStore>) -> Self {
+    Self {
+      persistence,
+      ballots: Mutex::new(HashMap::new()),
+    }
+  }
+
+  pub async fn request_skip(
+    &self,
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    call: &Call,
+  ) -> Result<SkipOutcome, anyhow::Error> {
+    let queue = call.queue();
+    let current = queue
+      .current()
+      .ok_or_else(|| anyhow!("Nothing is playing"))?;
+    let requester = track_metadata(&current).and_then(|m| m.requested_by.as_ref().map(|u| u.0));
+    let settings = VoiceSettings::load(&self.persistence, guild_id);
+    if !settings.vote_skip || requester.is_none_or(|r| r == user_id) {
+      queue.skip()?;
+      return Ok(SkipOutcome::Skipped);
+    }
+
+    let channel = call
+      .current_channel()
+      .map(|c| ChannelId::new(c.0.get()))
+      .ok_or_else(|| anyhow!("I'm currently not in a voice channel"))?;
+    let voter_channel = caller_channel(ctx, guild_id, user_id).ok();
+    let needed = votes_needed(listeners(ctx, guild_id, channel));
+    let outcome = self
+      .vote(
+        guild_id,
+        current.uuid(),
+        user_id,
+        voter_channel,
+        channel,
+        needed,
+      )
+      .await;
+    if let SkipOutcome::Skipped = outcome {
+      queue.skip()?;
+    }
+    Ok(outcome)
+  }
+
+  async fn vote(
+    &self,
+    guild_id: GuildId,
+    track: Uuid,
+    voter: UserId,
+    voter_channel: Option<ChannelId>,
+    channel: ChannelId,
+    needed: usize,
+  ) -> SkipOutcome {
+    if voter_channel != Some(channel) {
+      info!("Ignoring skip vote from {} outside {}", voter, channel);
+      return SkipOutcome::NotListening;
+    }
+    let mut ballots = self.ballots.lock().await;
+    let votes = ballots
+      .entry(guild_id)
+      .or_insert_with(|| Ballot {
+        track,
+        voters: HashSet::new(),
+      })
+      .cast(track, voter);
+    info!("{} of {} votes to skip in {}", votes, needed, guild_id);
+    if votes < needed {
+      return SkipOutcome::Pending { votes, needed };
+    }
+    ballots.remove(&guild_id);
+    SkipOutcome::Skipped
+  }
+}
+
+fn votes_needed(listeners: usize) -> usize {
+  listeners / 2 + 1
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  #[test_case(0, 1 ; "Empty channel")]
+  #[test_case(1, 1 ; "Alone")]
+  #[test_case(2, 2 ; "Pair")]
+  #[test_case(3, 2 ; "Three")]
+  #[test_case(4, 3 ; "Four")]
+  fn needs_a_majority(listeners: usize, expected: usize) {
+    assert_eq!(expected, votes_needed(listeners));
+  }
+
+  #[test]
+  fn ballots_reset_with_the_track() {
+    let first = Uuid::new_v4();
+    let mut ballot = Ballot {
+      track: first,
+      voters: HashSet::new(),
+    };
+    assert_eq!(1, ballot.cast(first, UserId::new(1)));
+    assert_eq!(1, ballot.cast(first, UserId::new(1)));
+    assert_eq!(2, ballot.cast(first, UserId::new(2)));
+    assert_eq!(1, ballot.cast(Uuid::new_v4(), UserId::new(2)));
+  }
+
+  #[tokio::test]
+  async fn outsiders_votes_are_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let persistence = Arc::new(PersistentStore::new(dir.path().join("test.db")).unwrap());
+    let votes = SkipVotes::new(persistence);
+    let (guild, track, channel) = (GuildId::new(1), Uuid::new_v4(), ChannelId::new(2));
+
+    let outsider = votes
+      .vote(
+        guild,
+        track,
+        UserId::new(3),
+        Some(ChannelId::new(4)),
+        channel,
+        2,
+      )
+      .await;
+    assert!(matches!(outsider, SkipOutcome::NotListening));
+    let absent = votes
+      .vote(guild, track, UserId::new(5), None, channel, 2)
+      .await;
+    assert!(matches!(absent, SkipOutcome::NotListening));
+
+    let listener = votes
+      .vote(guild, track, UserId::new(6), Some(channel), channel, 2)
+      .await;
+    assert!(matches!(
+      listener,
+      SkipOutcome::Pending {
+        votes: 1,
+        needed: 2
+      }
+    ));
+  }
+}
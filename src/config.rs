@@ -11,6 +11,7 @@ use tracing::{info, warn};
 static INSTANCE: Lazy<RwLock<Config>> = Lazy::new(|| RwLock::new(Config::default()));
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
   pub api_key: String,
   pub app_id: u64,
@@ -22,6 +23,56 @@ pub struct Config {
   #[serde(with = "humantime_serde")]
   pub voice_channel_timeout: Duration,
   pub db_path: String,
+  pub soundboard_dir: String,
+  /// Files in here can be played with /play yt file:<name>
+  pub music_dir: String,
+  pub playlist_max_tracks: usize,
+  /// Docker daemons whose game servers /servers can manage
+  pub docker_hosts: Vec<DockerHost>,
+  /// Woken by /servers wake, eg `01:23:45:67:89:ab`
+  pub server_mac: String,
+  /// Where /servers sleep will ssh to shut the game server down
+  pub server_host: String,
+  pub server_user: String,
+  /// How long /servers start waits on a server to become ready before giving up
+  #[serde(with = "humantime_serde")]
+  pub server_start_timeout: Duration,
+  /// Channel id to post in when a managed server crashes or is stopped for
+  /// being idle, unset to stay quiet
+  pub server_alerts_channel: Option<u64>,
+  /// How long a server can go without players before it's stopped, 0s to never
+  #[serde(with = "humantime_serde")]
+  pub server_idle_timeout: Duration,
+  /// Where /servers backup puts its snapshots
+  pub backup_dir: String,
+  /// Snapshots kept per server before the oldest are removed, at least 1
+  pub backup_keep: usize,
+  /// Services that answer a GET with our public IP as plain text
+  pub ip_echoers: Vec<String>,
+  /// How long a looked up public IP is trusted before asking again
+  #[serde(with = "humantime_serde")]
+  pub ip_cache_ttl: Duration,
+  /// How often to check whether the public IP has moved, announcing it in the
+  /// alerts channel, 0s to not bother
+  #[serde(with = "humantime_serde")]
+  pub ip_check_interval: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockerHost {
+  /// Containers on this host are addressed as `name/container`
+  pub name: String,
+  /// `unix:///var/run/docker.sock`, `tcp://host:2375`, or with `tls` set `tcp://host:2376`
+  pub address: String,
+  pub tls: Option<DockerTls>,
+}
+
+/// Client certs for talking to a daemon started with `--tlsverify`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockerTls {
+  pub ca: String,
+  pub cert: String,
+  pub key: String,
 }
 
 impl Default for Config {
@@ -35,6 +86,29 @@ impl Default for Config {
       log_level: "INFO".to_string(),
       voice_channel_timeout: Duration::from_secs(600),
       db_path: "disbot.db".to_string(),
+      soundboard_dir: "sounds".to_string(),
+      music_dir: "music".to_string(),
+      playlist_max_tracks: 50,
+      docker_hosts: vec![DockerHost {
+        name: "local".to_string(),
+        address: "unix:///var/run/docker.sock".to_string(),
+        tls: None,
+      }],
+      server_mac: String::new(),
+      server_host: String::new(),
+      server_user: String::new(),
+      server_start_timeout: Duration::from_secs(600),
+      server_alerts_channel: None,
+      server_idle_timeout: Duration::from_secs(30 * 60),
+      backup_dir: "backups".to_string(),
+      backup_keep: 5,
+      ip_echoers: vec![
+        "https://api.ipify.org/".to_string(),
+        "https://api.my-ip.io/v1/ip".to_string(),
+        "https://ip.seeip.org/".to_string(),
+      ],
+      ip_cache_ttl: Duration::from_secs(60),
+      ip_check_interval: Duration::from_secs(10 * 60),
     }
   }
 }
@@ -1,11 +1,65 @@
+use crate::config::DockerHost;
 use anyhow::anyhow;
 use async_trait::async_trait;
 use bollard::{
-  query_parameters::{ListContainersOptions, StopContainerOptions},
-  service::{ContainerStateStatusEnum, ContainerSummary},
+  query_parameters::{
+    DownloadFromContainerOptions, EventsOptions, ListContainersOptions, LogsOptions,
+    RestartContainerOptions, StopContainerOptions,
+  },
+  service::{ContainerInspectResponse, ContainerStateStatusEnum, ContainerSummary},
 };
-use std::collections::HashMap;
-use tracing::warn;
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+/// Seconds to wait on a daemon before giving up, bollard's own default
+const TIMEOUT: u64 = 120;
+
+/// Only containers carrying this label, set to true, are ours to touch
+pub const MANAGED_LABEL: &str = "shibba";
+pub const GAME_LABEL: &str = "game";
+pub const VERSION_LABEL: &str = "version";
+/// A regex matched against the logs, eg `Done \(` for minecraft, marking when
+/// the server's actually ready for players rather than just running
+pub const READY_LABEL: &str = "ready";
+/// `off` to leave the server running when nobody's on it, or how long to wait
+/// before stopping it, eg `2h`
+pub const IDLE_LABEL: &str = "idle";
+/// Comma separated paths in the container to back up, instead of its writable mounts
+pub const BACKUP_LABEL: &str = "backup";
+/// Seconds a server gets to save and shut down before it's killed
+const STOP_GRACE: i32 = 120;
+/// How long a host that dropped its events gets before we ask again
+const EVENTS_RETRY: Duration = Duration::from_secs(60);
+
+/// Whether the container's labels mark it as a game server we manage
+pub fn is_managed(labels: Option<&HashMap<String, String>>) -> bool {
+  labels
+    .and_then(|l| l.get(MANAGED_LABEL))
+    .is_some_and(|v| v == "true")
+}
+
+/// Which of a container's log lines to fetch
+#[derive(Debug, Clone, Copy)]
+pub enum LogRange {
+  /// Written at or after this, in unix seconds
+  Since(i64),
+  /// The most recent lines
+  Last(usize),
+}
+
+/// Something that happened to a managed container, from the daemon's event feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerEvent {
+  pub name: String,
+  /// Docker's name for it, eg `die` or `kill`
+  pub action: String,
+  pub exit_code: Option<i64>,
+}
+
+/// Docker actions we pass on, enough to tell a crash from someone stopping it
+const WATCHED_ACTIONS: [&str; 3] = ["die", "kill", "stop"];
 
 #[async_trait]
 pub trait DockerClient: Send + Sync {
@@ -13,6 +67,24 @@ pub trait DockerClient: Send + Sync {
   async fn status(&self, name: &str) -> Result<ContainerStateStatusEnum, anyhow::Error>;
   async fn start(&self, name: &str) -> Result<(), anyhow::Error>;
   async fn stop(&self, name: &str) -> Result<(), anyhow::Error>;
+  async fn restart(&self, name: &str) -> Result<(), anyhow::Error>;
+  async fn inspect(&self, name: &str) -> Result<ContainerInspectResponse, anyhow::Error>;
+  /// Log lines from stdout and stderr, oldest first
+  async fn logs(&self, name: &str, range: LogRange) -> Result<Vec<String>, anyhow::Error>;
+  /// Follows events on managed containers until the daemon hangs up
+  async fn events(
+    &self,
+  ) -> Result<BoxStream<'static, Result<ContainerEvent, anyhow::Error>>, anyhow::Error>;
+  /// A tar of the path inside the container, in chunks as they arrive
+  async fn archive(
+    &self,
+    name: &str,
+    path: &str,
+  ) -> Result<BoxStream<'static, Result<Vec<u8>, anyhow::Error>>, anyhow::Error>;
+  /// The one way of naming the container, for keying anything kept about it
+  fn canonical(&self, name: &str) -> Result<String, anyhow::Error> {
+    Ok(name.to_string())
+  }
 }
 
 #[derive(Clone)]
@@ -21,22 +93,54 @@ pub struct BollardDocker {
 }
 
 impl BollardDocker {
-  pub fn new() -> Result<BollardDocker, anyhow::Error> {
-    Ok(BollardDocker {
-      client: bollard::Docker::connect_with_socket_defaults()?,
-    })
+  pub fn connect(host: &DockerHost) -> Result<BollardDocker, anyhow::Error> {
+    let addr = host.address.as_str();
+    let version = bollard::API_DEFAULT_VERSION;
+    let client = match (addr.split_once("://").map(|(s, _)| s), &host.tls) {
+      (Some("unix"), _) => bollard::Docker::connect_with_unix(addr, TIMEOUT, version)?,
+      (Some("tcp" | "https"), Some(tls)) => bollard::Docker::connect_with_ssl(
+        addr,
+        Path::new(&tls.key),
+        Path::new(&tls.cert),
+        Path::new(&tls.ca),
+        TIMEOUT,
+        version,
+      )?,
+      (Some("tcp" | "http"), None) => bollard::Docker::connect_with_http(addr, TIMEOUT, version)?,
+      (Some("https"), None) => return Err(anyhow!("{addr} needs tls certs to connect")),
+      _ => return Err(anyhow!("Unsupported docker address {addr}")),
+    };
+    Ok(BollardDocker { client })
+  }
+
+  /// Inspects the container, refusing any that aren't labelled as managed so
+  /// nothing else on the host can be started or stopped through us
+  async fn inspect_managed(&self, name: &str) -> Result<ContainerInspectResponse, anyhow::Error> {
+    let container = self
+      .client
+      .inspect_container(
+        name,
+        None::<bollard::query_parameters::InspectContainerOptions>,
+      )
+      .await
+      .map_err(|e| anyhow!(e))?;
+    let labels = container.config.as_ref().and_then(|c| c.labels.as_ref());
+    match is_managed(labels) {
+      true => Ok(container),
+      false => {
+        warn!("Refusing to manage unlabelled container {}", name);
+        Err(anyhow!("{name} isn't a server I look after"))
+      }
+    }
   }
 }
 
 #[async_trait]
 impl DockerClient for BollardDocker {
   async fn list(&self) -> Result<Vec<ContainerSummary>, anyhow::Error> {
-    let list_container_filters: HashMap<String, Vec<String>> = HashMap::new();
-    // TODO: Use a label filter and set that label ("shibba:true") on each container that can
-    //       be managed. Should only work on containers that have been started once, eg container is
-    //       already made. No need to interact with compose.
-    //       You'll also want labels for (Game, Version) to help inform what to start/stop
-    // list_container_filters.insert("status", vec!["running"]);
+    // Only containers that have been created already, we don't deal with compose
+    let list_container_filters =
+      HashMap::from([("label".to_string(), vec![format!("{MANAGED_LABEL}=true")])]);
 
     self
       .client
@@ -51,22 +155,15 @@ impl DockerClient for BollardDocker {
 
   async fn status(&self, name: &str) -> Result<ContainerStateStatusEnum, anyhow::Error> {
     self
-      .client
-      .inspect_container(
-        name,
-        None::<bollard::query_parameters::InspectContainerOptions>,
-      )
-      .await
-      .map_err(|e| anyhow!(e))
-      .and_then(|res| {
-        res
-          .state
-          .and_then(|s| s.status)
-          .ok_or(anyhow!("Container in Unknown State"))
-      })
+      .inspect_managed(name)
+      .await?
+      .state
+      .and_then(|s| s.status)
+      .ok_or(anyhow!("Container in Unknown State"))
   }
 
   async fn start(&self, name: &str) -> Result<(), anyhow::Error> {
+    self.inspect_managed(name).await?;
     self
       .client
       .start_container(
@@ -78,18 +175,120 @@ impl DockerClient for BollardDocker {
   }
 
   async fn stop(&self, name: &str) -> Result<(), anyhow::Error> {
+    self.inspect_managed(name).await?;
     self
       .client
       .stop_container(
         name,
         Some(StopContainerOptions {
-          t: Some(120),
+          t: Some(STOP_GRACE),
           ..Default::default()
         }),
       )
       .await
       .map_err(|e| anyhow!(e))
   }
+
+  async fn restart(&self, name: &str) -> Result<(), anyhow::Error> {
+    self.inspect_managed(name).await?;
+    self
+      .client
+      .restart_container(
+        name,
+        Some(RestartContainerOptions {
+          t: Some(STOP_GRACE),
+          ..Default::default()
+        }),
+      )
+      .await
+      .map_err(|e| anyhow!(e))
+  }
+
+  async fn inspect(&self, name: &str) -> Result<ContainerInspectResponse, anyhow::Error> {
+    self.inspect_managed(name).await
+  }
+
+  async fn logs(&self, name: &str, range: LogRange) -> Result<Vec<String>, anyhow::Error> {
+    self.inspect_managed(name).await?;
+    let (since, tail) = match range {
+      LogRange::Since(since) => (since as i32, "all".to_string()),
+      LogRange::Last(lines) => (0, lines.to_string()),
+    };
+    let chunks: Vec<_> = self
+      .client
+      .logs(
+        name,
+        Some(LogsOptions {
+          stdout: true,
+          stderr: true,
+          since,
+          tail,
+          ..Default::default()
+        }),
+      )
+      .try_collect()
+      .await
+      .map_err(|e| anyhow!(e))?;
+    Ok(
+      chunks
+        .iter()
+        .flat_map(|c| {
+          c.to_string()
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+        })
+        .collect(),
+    )
+  }
+
+  async fn events(
+    &self,
+  ) -> Result<BoxStream<'static, Result<ContainerEvent, anyhow::Error>>, anyhow::Error> {
+    let filters = HashMap::from([
+      ("type".to_string(), vec!["container".to_string()]),
+      ("label".to_string(), vec![format!("{MANAGED_LABEL}=true")]),
+      (
+        "event".to_string(),
+        WATCHED_ACTIONS.iter().map(|a| a.to_string()).collect(),
+      ),
+    ]);
+    let events = self
+      .client
+      .events(Some(EventsOptions {
+        filters: Some(filters),
+        ..Default::default()
+      }))
+      .map(|event| {
+        let event = event.map_err(|e| anyhow!(e))?;
+        let attributes = event.actor.and_then(|a| a.attributes).unwrap_or_default();
+        Ok(ContainerEvent {
+          name: attributes.get("name").cloned().unwrap_or_default(),
+          action: event.action.unwrap_or_default(),
+          exit_code: attributes.get("exitCode").and_then(|c| c.parse().ok()),
+        })
+      });
+    Ok(events.boxed())
+  }
+
+  async fn archive(
+    &self,
+    name: &str,
+    path: &str,
+  ) -> Result<BoxStream<'static, Result<Vec<u8>, anyhow::Error>>, anyhow::Error> {
+    self.inspect_managed(name).await?;
+    let tar = self
+      .client
+      .download_from_container(
+        name,
+        Some(DownloadFromContainerOptions {
+          path: path.to_string(),
+        }),
+      )
+      .map_ok(|chunk| chunk.to_vec())
+      .map_err(|e| anyhow!(e));
+    Ok(tar.boxed())
+  }
 }
 
 pub struct NoOpDocker;
@@ -118,20 +317,467 @@ impl DockerClient for NoOpDocker {
     warn!("Docker unavailable: stop operation attempted for {}", name);
     Err(anyhow!("Docker is not available"))
   }
+
+  async fn restart(&self, name: &str) -> Result<(), anyhow::Error> {
+    warn!(
+      "Docker unavailable: restart operation attempted for {}",
+      name
+    );
+    Err(anyhow!("Docker is not available"))
+  }
+
+  async fn inspect(&self, name: &str) -> Result<ContainerInspectResponse, anyhow::Error> {
+    warn!(
+      "Docker unavailable: inspect operation attempted for {}",
+      name
+    );
+    Err(anyhow!("Docker is not available"))
+  }
+
+  async fn logs(&self, name: &str, _range: LogRange) -> Result<Vec<String>, anyhow::Error> {
+    warn!("Docker unavailable: logs operation attempted for {}", name);
+    Err(anyhow!("Docker is not available"))
+  }
+
+  async fn events(
+    &self,
+  ) -> Result<BoxStream<'static, Result<ContainerEvent, anyhow::Error>>, anyhow::Error> {
+    Err(anyhow!("Docker is not available"))
+  }
+
+  async fn archive(
+    &self,
+    name: &str,
+    _path: &str,
+  ) -> Result<BoxStream<'static, Result<Vec<u8>, anyhow::Error>>, anyhow::Error> {
+    warn!(
+      "Docker unavailable: archive operation attempted for {}",
+      name
+    );
+    Err(anyhow!("Docker is not available"))
+  }
+}
+
+/// Every configured host behind one client. Containers go by `host/name`,
+/// though the host can be left off when there's only the one.
+pub struct DockerHosts {
+  hosts: Vec<(String, Arc<dyn DockerClient>)>,
+}
+
+impl DockerHosts {
+  /// Which host the container's on, and its name there
+  fn locate<'a, 't>(
+    &'a self,
+    target: &'t str,
+  ) -> Result<(&'a str, &'a dyn DockerClient, &'t str), anyhow::Error> {
+    let (host, name) = match (target.split_once('/'), self.hosts.as_slice()) {
+      (Some((host, name)), _) => (host, name),
+      (None, [(host, _)]) => (host.as_str(), target),
+      (None, _) => return Err(anyhow!("Which host is {target} on? Try host/{target}")),
+    };
+    self
+      .hosts
+      .iter()
+      .find(|(h, _)| h == host)
+      .map(|(h, client)| (h.as_str(), client.as_ref(), name))
+      .ok_or_else(|| anyhow!("No docker host called {host}"))
+  }
+
+  fn host(&self, target: &str) -> Result<(&dyn DockerClient, String), anyhow::Error> {
+    let (_, client, name) = self.locate(target)?;
+    Ok((client, name.to_string()))
+  }
 }
 
-pub fn create_docker_client() -> Box<dyn DockerClient> {
-  match BollardDocker::new() {
-    Ok(docker) => {
-      tracing::info!("Docker client connected successfully");
-      Box::new(docker)
-    }
-    Err(e) => {
-      warn!(
-        "Failed to connect to Docker, using no-op implementation: {}",
-        e
-      );
+#[async_trait]
+impl DockerClient for DockerHosts {
+  async fn list(&self) -> Result<Vec<ContainerSummary>, anyhow::Error> {
+    let mut all = vec![];
+    let mut failures = 0;
+    for (host, client) in &self.hosts {
+      match client.list().await {
+        Ok(containers) => all.extend(containers.into_iter().map(|mut c| {
+          // Names come with a leading slash, the host slots in after it
+          c.names = c.names.map(|names| {
+            names
+              .iter()
+              .map(|n| format!("/{host}/{}", n.trim_start_matches('/')))
+              .collect()
+          });
+          c
+        })),
+        Err(e) => {
+          // One host being down shouldn't hide the rest
+          warn!("Failed to list containers on {}: {}", host, e);
+          failures += 1;
+        }
+      }
+    }
+    match failures == self.hosts.len() {
+      true => Err(anyhow!("Couldn't reach any docker hosts")),
+      false => Ok(all),
+    }
+  }
+
+  async fn status(&self, name: &str) -> Result<ContainerStateStatusEnum, anyhow::Error> {
+    let (client, name) = self.host(name)?;
+    client.status(&name).await
+  }
+
+  async fn start(&self, name: &str) -> Result<(), anyhow::Error> {
+    let (client, name) = self.host(name)?;
+    client.start(&name).await
+  }
+
+  async fn stop(&self, name: &str) -> Result<(), anyhow::Error> {
+    let (client, name) = self.host(name)?;
+    client.stop(&name).await
+  }
+
+  async fn restart(&self, name: &str) -> Result<(), anyhow::Error> {
+    let (client, name) = self.host(name)?;
+    client.restart(&name).await
+  }
+
+  async fn inspect(&self, name: &str) -> Result<ContainerInspectResponse, anyhow::Error> {
+    let (client, name) = self.host(name)?;
+    client.inspect(&name).await
+  }
+
+  async fn logs(&self, name: &str, range: LogRange) -> Result<Vec<String>, anyhow::Error> {
+    let (client, name) = self.host(name)?;
+    client.logs(&name, range).await
+  }
+
+  async fn events(
+    &self,
+  ) -> Result<BoxStream<'static, Result<ContainerEvent, anyhow::Error>>, anyhow::Error> {
+    let streams = self
+      .hosts
+      .iter()
+      .map(|(host, client)| follow_host(host.clone(), client.clone(), EVENTS_RETRY));
+    Ok(futures::stream::select_all(streams).boxed())
+  }
+
+  async fn archive(
+    &self,
+    name: &str,
+    path: &str,
+  ) -> Result<BoxStream<'static, Result<Vec<u8>, anyhow::Error>>, anyhow::Error> {
+    let (client, name) = self.host(name)?;
+    client.archive(&name, path).await
+  }
+
+  /// Always `host/name`, as listed, even when the host could be left off
+  fn canonical(&self, name: &str) -> Result<String, anyhow::Error> {
+    let (host, _, name) = self.locate(name)?;
+    Ok(format!("{host}/{name}"))
+  }
+}
+
+pub fn create_docker_client(hosts: &[DockerHost]) -> Box<dyn DockerClient> {
+  let hosts: Vec<(String, Arc<dyn DockerClient>)> = hosts
+    .iter()
+    .filter_map(|host| match BollardDocker::connect(host) {
+      Ok(docker) => {
+        info!("Docker client for {} set up", host.name);
+        Some((host.name.clone(), Arc::new(docker) as Arc<dyn DockerClient>))
+      }
+      Err(e) => {
+        warn!("Failed to connect to Docker on {}: {}", host.name, e);
+        None
+      }
+    })
+    .collect();
+  match hosts.is_empty() {
+    true => {
+      warn!("No Docker hosts available, using no-op implementation");
       Box::new(NoOpDocker)
     }
+    false => Box::new(DockerHosts { hosts }),
+  }
+}
+
+/// Follows one host's events for good. Whenever they error or end that host
+/// alone is asked again after `retry`, the rest carry on regardless.
+fn follow_host(
+  host: String,
+  client: Arc<dyn DockerClient>,
+  retry: Duration,
+) -> BoxStream<'static, Result<ContainerEvent, anyhow::Error>> {
+  futures::stream::unfold(None, move |mut events: Option<BoxStream<_>>| {
+    let (host, client) = (host.clone(), client.clone());
+    async move {
+      loop {
+        let stream = match events.as_mut() {
+          Some(stream) => stream,
+          None => match client.events().await {
+            Ok(stream) => events.insert(stream),
+            Err(e) => {
+              warn!("Failed to follow events on {}: {}", host, e);
+              sleep(retry).await;
+              continue;
+            }
+          },
+        };
+        match stream.next().await {
+          Some(Ok(mut e)) => {
+            e.name = format!("{host}/{}", e.name);
+            return Some((Ok(e), events));
+          }
+          Some(Err(e)) => warn!("Lost events on {}: {}", host, e),
+          None => warn!("Events on {} ended", host),
+        }
+        events = None;
+        sleep(retry).await;
+      }
+    }
+  })
+  .boxed()
+}
+
+/// A stand in daemon for tests, holding a fixed set of containers
+#[cfg(test)]
+pub mod fake {
+  use super::*;
+  use bollard::service::ContainerSummaryStateEnum;
+  use std::sync::{Arc, Mutex};
+
+  /// Answers with its containers and notes what it was asked to start. Anything
+  /// else is an error rather than a panic, so a stray call fails the test plainly.
+  #[derive(Default)]
+  pub struct FakeDocker {
+    pub containers: Vec<(String, ContainerSummaryStateEnum)>,
+    pub started: Arc<Mutex<Vec<String>>>,
+  }
+
+  impl FakeDocker {
+    pub fn new(containers: &[(&str, ContainerSummaryStateEnum)]) -> Self {
+      Self {
+        containers: containers
+          .iter()
+          .map(|(name, state)| (name.to_string(), *state))
+          .collect(),
+        ..Default::default()
+      }
+    }
+  }
+
+  /// The fake as the only host, called `local` like the default config's
+  pub fn local(fake: FakeDocker) -> DockerHosts {
+    DockerHosts {
+      hosts: vec![("local".to_string(), Arc::new(fake))],
+    }
+  }
+
+  fn unused<T>() -> Result<T, anyhow::Error> {
+    Err(anyhow!("unused in test"))
+  }
+
+  #[async_trait]
+  impl DockerClient for FakeDocker {
+    async fn list(&self) -> Result<Vec<ContainerSummary>, anyhow::Error> {
+      if self.containers.is_empty() {
+        return Err(anyhow!("Daemon unreachable"));
+      }
+      Ok(
+        self
+          .containers
+          .iter()
+          .map(|(name, state)| ContainerSummary {
+            names: Some(vec![format!("/{name}")]),
+            state: Some(*state),
+            ..Default::default()
+          })
+          .collect(),
+      )
+    }
+
+    async fn status(&self, _: &str) -> Result<ContainerStateStatusEnum, anyhow::Error> {
+      unused()
+    }
+
+    async fn start(&self, name: &str) -> Result<(), anyhow::Error> {
+      self.started.lock().unwrap().push(name.to_string());
+      Ok(())
+    }
+
+    async fn stop(&self, _: &str) -> Result<(), anyhow::Error> {
+      unused()
+    }
+
+    async fn restart(&self, _: &str) -> Result<(), anyhow::Error> {
+      unused()
+    }
+
+    async fn inspect(&self, _: &str) -> Result<ContainerInspectResponse, anyhow::Error> {
+      unused()
+    }
+
+    async fn logs(&self, _: &str, _: LogRange) -> Result<Vec<String>, anyhow::Error> {
+      unused()
+    }
+
+    /// Every container dies, as if they'd all crashed at once
+    async fn events(
+      &self,
+    ) -> Result<BoxStream<'static, Result<ContainerEvent, anyhow::Error>>, anyhow::Error> {
+      if self.containers.is_empty() {
+        return Err(anyhow!("Daemon unreachable"));
+      }
+      let events = self
+        .containers
+        .iter()
+        .map(|(name, _)| {
+          Ok(ContainerEvent {
+            name: name.clone(),
+            action: "die".to_string(),
+            exit_code: Some(1),
+          })
+        })
+        .collect::<Vec<_>>();
+      Ok(futures::stream::iter(events).boxed())
+    }
+
+    async fn archive(
+      &self,
+      _: &str,
+      _: &str,
+    ) -> Result<BoxStream<'static, Result<Vec<u8>, anyhow::Error>>, anyhow::Error> {
+      unused()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{fake::FakeDocker, *};
+  use bollard::service::ContainerSummaryStateEnum::EXITED;
+  use std::sync::{Arc, Mutex};
+  use test_case::test_case;
+
+  fn hosts(hosts: &[(&str, Vec<&'static str>)]) -> (DockerHosts, Arc<Mutex<Vec<String>>>) {
+    let started = Arc::new(Mutex::new(vec![]));
+    let hosts = hosts
+      .iter()
+      .map(|(name, containers)| {
+        let containers: Vec<_> = containers.iter().map(|c| (*c, EXITED)).collect();
+        let fake = FakeDocker {
+          started: started.clone(),
+          ..FakeDocker::new(&containers)
+        };
+        (name.to_string(), Arc::new(fake) as Arc<dyn DockerClient>)
+      })
+      .collect();
+    (DockerHosts { hosts }, started)
+  }
+
+  #[tokio::test]
+  async fn containers_listed_across_hosts() {
+    let (docker, _) = hosts(&[
+      ("nas", vec!["valheim"]),
+      ("down", vec![]),
+      ("pi", vec!["minecraft"]),
+    ]);
+    let names: Vec<_> = docker
+      .list()
+      .await
+      .unwrap()
+      .into_iter()
+      .flat_map(|c| c.names.unwrap())
+      .collect();
+    assert_eq!(vec!["/nas/valheim", "/pi/minecraft"], names);
+
+    let (docker, _) = hosts(&[("down", vec![])]);
+    assert!(docker.list().await.is_err());
+  }
+
+  #[tokio::test]
+  async fn commands_routed_by_host() {
+    let (docker, started) = hosts(&[("nas", vec!["valheim"]), ("pi", vec!["minecraft"])]);
+    docker.start("pi/minecraft").await.unwrap();
+    assert_eq!(vec!["minecraft"], *started.lock().unwrap());
+    assert!(docker.start("minecraft").await.is_err());
+    assert!(docker.start("desktop/minecraft").await.is_err());
+
+    // With only one host there's no need to say which
+    let (docker, started) = hosts(&[("nas", vec!["valheim"])]);
+    docker.start("valheim").await.unwrap();
+    assert_eq!(vec!["valheim"], *started.lock().unwrap());
+  }
+
+  #[test]
+  fn names_made_canonical() {
+    let (docker, _) = hosts(&[("local", vec!["valheim"])]);
+    assert_eq!("local/valheim", docker.canonical("valheim").unwrap());
+    assert_eq!("local/valheim", docker.canonical("local/valheim").unwrap());
+
+    let (docker, _) = hosts(&[("nas", vec!["valheim"]), ("pi", vec!["minecraft"])]);
+    assert_eq!("pi/minecraft", docker.canonical("pi/minecraft").unwrap());
+    assert!(docker.canonical("minecraft").is_err());
+    assert!(docker.canonical("desktop/minecraft").is_err());
+  }
+
+  #[tokio::test]
+  async fn events_merged_across_hosts() {
+    let (docker, _) = hosts(&[
+      ("nas", vec!["valheim"]),
+      ("down", vec![]),
+      ("pi", vec!["minecraft"]),
+    ]);
+    // Hosts are followed for good, so only take what's there
+    let mut names: Vec<_> = docker
+      .events()
+      .await
+      .unwrap()
+      .take(2)
+      .map_ok(|e| e.name)
+      .try_collect()
+      .await
+      .unwrap();
+    names.sort();
+    assert_eq!(vec!["nas/valheim", "pi/minecraft"], names);
+  }
+
+  #[tokio::test]
+  async fn hosts_followed_again_once_their_events_end() {
+    let fake = FakeDocker::new(&[("valheim", EXITED)]);
+    let names: Vec<_> = follow_host("nas".to_string(), Arc::new(fake), Duration::ZERO)
+      .take(3)
+      .map_ok(|e| e.name)
+      .try_collect()
+      .await
+      .unwrap();
+    assert_eq!(vec!["nas/valheim"; 3], names);
+  }
+
+  #[test_case("tcp://10.0.0.5:2375", false => true; "plain tcp")]
+  #[test_case("https://10.0.0.5:2376", false => false; "tls without certs")]
+  #[test_case("tcp://10.0.0.5:2376", true => false; "certs missing on disk")]
+  #[test_case("ftp://10.0.0.5", false => false; "nonsense")]
+  fn host_addresses(address: &str, tls: bool) -> bool {
+    let host = DockerHost {
+      name: "test".to_string(),
+      address: address.to_string(),
+      tls: tls.then(|| crate::config::DockerTls {
+        ca: "ca.pem".to_string(),
+        cert: "cert.pem".to_string(),
+        key: "key.pem".to_string(),
+      }),
+    };
+    BollardDocker::connect(&host).is_ok()
+  }
+
+  #[test_case(Some(&[("shibba", "true")]) => true; "labelled")]
+  #[test_case(Some(&[("shibba", "false")]) => false; "opted out")]
+  #[test_case(Some(&[("game", "valheim")]) => false; "game but not ours")]
+  #[test_case(None => false; "no labels")]
+  fn only_labelled_containers_are_managed(labels: Option<&[(&str, &str)]>) -> bool {
+    let labels: Option<HashMap<String, String>> = labels.map(|l| {
+      l.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    });
+    is_managed(labels.as_ref())
   }
 }
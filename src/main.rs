@@ -117,7 +117,7 @@ async fn main() -> Result<(), anyhow::Error> {
     config.clone(),
     emoji,
     http,
-    docker::create_docker_client(),
+    docker::create_docker_client(&config.docker_hosts),
     persistence.clone(),
     &mut shutdown,
   ))
@@ -1,4 +1,9 @@
-use crate::cmd::{check_in::CheckInCtx, poll::pollstate::PollState};
+use crate::cmd::{
+  check_in::CheckInCtx,
+  poll::pollstate::PollState,
+  server::ServerPermissions,
+  voice::{SavedQueue, SoundLibrary, TrackHistory, VoiceSettings},
+};
 use anyhow::{anyhow, Result};
 use bincode::{Decode, Encode};
 use redb::{Database, ReadableTable, TableDefinition};
@@ -52,6 +57,12 @@ pub trait Expirable {
 
 const POLL_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("polls");
 const CHECKIN_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("checkins");
+const SOUNDBOARD_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("soundboards");
+const VOICE_QUEUE_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("voice_queues");
+const VOICE_SETTINGS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("voice_settings");
+const VOICE_HISTORY_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("voice_history");
+const SERVER_PERMISSIONS_TABLE: TableDefinition<&str, &[u8]> =
+  TableDefinition::new("server_permissions");
 
 impl<'a, K: Id, V: Encode> Handle<'a, K, V> {
   pub fn save(&self, key: &K, data: &V) -> Result<()> {
@@ -132,6 +143,11 @@ impl PersistentStore {
     {
       let _polls_table = write_txn.open_table(POLL_TABLE)?;
       let _checkins_table = write_txn.open_table(CHECKIN_TABLE)?;
+      let _soundboards_table = write_txn.open_table(SOUNDBOARD_TABLE)?;
+      let _voice_queues_table = write_txn.open_table(VOICE_QUEUE_TABLE)?;
+      let _voice_settings_table = write_txn.open_table(VOICE_SETTINGS_TABLE)?;
+      let _voice_history_table = write_txn.open_table(VOICE_HISTORY_TABLE)?;
+      let _server_permissions_table = write_txn.open_table(SERVER_PERMISSIONS_TABLE)?;
     }
     write_txn.commit()?;
 
@@ -155,13 +171,58 @@ impl PersistentStore {
       table: CHECKIN_TABLE,
     }
   }
+
+  pub fn soundboards<'a>(&'a self) -> Handle<'a, GuildId, SoundLibrary> {
+    Handle {
+      db: &self.db,
+      k: PhantomData,
+      v: PhantomData,
+      table: SOUNDBOARD_TABLE,
+    }
+  }
+
+  pub fn voice_queues<'a>(&'a self) -> Handle<'a, GuildId, SavedQueue> {
+    Handle {
+      db: &self.db,
+      k: PhantomData,
+      v: PhantomData,
+      table: VOICE_QUEUE_TABLE,
+    }
+  }
+
+  pub fn voice_settings<'a>(&'a self) -> Handle<'a, GuildId, VoiceSettings> {
+    Handle {
+      db: &self.db,
+      k: PhantomData,
+      v: PhantomData,
+      table: VOICE_SETTINGS_TABLE,
+    }
+  }
+
+  pub fn voice_history<'a>(&'a self) -> Handle<'a, GuildId, TrackHistory> {
+    Handle {
+      db: &self.db,
+      k: PhantomData,
+      v: PhantomData,
+      table: VOICE_HISTORY_TABLE,
+    }
+  }
+
+  pub fn server_permissions<'a>(&'a self) -> Handle<'a, GuildId, ServerPermissions> {
+    Handle {
+      db: &self.db,
+      k: PhantomData,
+      v: PhantomData,
+      table: SERVER_PERMISSIONS_TABLE,
+    }
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
   use crate::{
-    cmd::poll::pollstate::PollState,
+    cmd::{poll::pollstate::PollState, voice::ListMetadata},
     types::{Chan, Guil, NaiveT, Pid},
   };
   use chrono::NaiveTime;
@@ -276,4 +337,35 @@ mod tests {
     let all_polls = store.polls().load_all().unwrap();
     assert_eq!(all_polls.len(), 2);
   }
+
+  #[test]
+  fn test_voice_queue_round_trip() {
+    let temp_dir = tempdir().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let store = PersistentStore::new(db_path).unwrap();
+
+    let guild_id = <GuildId as From<u64>>::from(111111111);
+    let saved = SavedQueue {
+      voice_channel: Chan(ChannelId::from(123456789)),
+      text_channel: Chan(ChannelId::from(987654321)),
+      tracks: vec![ListMetadata {
+        title: "Never Gonna Give You Up".to_string(),
+        url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+        duration: Some(Duration::from_secs(213)),
+        requested_by: None,
+        requested_at: None,
+        source: Default::default(),
+      }],
+      position: Duration::from_secs(42),
+    };
+    store.voice_queues().save(&guild_id, &saved).unwrap();
+
+    // Reopening the store is what a restart looks like
+    drop(store);
+    let store = PersistentStore::new(temp_dir.path().join("test.db")).unwrap();
+    let loaded = store.voice_queues().load(&guild_id).unwrap().unwrap();
+    assert_eq!(*loaded.voice_channel, *saved.voice_channel);
+    assert_eq!(loaded.tracks[0].url, saved.tracks[0].url);
+    assert_eq!(loaded.position, saved.position);
+  }
 }
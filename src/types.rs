@@ -2,7 +2,7 @@ use bincode::{impl_borrow_decode, Decode, Encode};
 use chrono::{NaiveTime, Timelike};
 use derive_more::{Deref, Display};
 use serenity::{
-  all::{EmojiId, GuildId, RoleId},
+  all::{EmojiId, GuildId, RoleId, UserId},
   model::prelude::ChannelId,
 };
 use uuid::Uuid;
@@ -34,7 +34,7 @@ macro_rules! impl_decode {
 
 // Formats: https://discord.com/developers/docs/reference#message-formatting
 
-#[derive(Clone, Deref, Display)]
+#[derive(Clone, Debug, Deref, Display)]
 #[display("<#{_0}>")]
 pub struct Chan(pub ChannelId);
 impl_encode!(Chan, |s| s.0.get());
@@ -47,13 +47,20 @@ impl_encode!(Guil, |s| s.0.get());
 impl_decode!(Guil, |d| GuildId::new(d));
 impl_borrow_decode!(Guil);
 
-#[derive(Clone, Deref, Display)]
+#[derive(Clone, Debug, Deref, Display)]
 #[display("<@&{_0}>")]
 pub struct Rol(pub RoleId);
 impl_encode!(Rol, |s| s.0.get());
 impl_decode!(Rol, |d| RoleId::new(d));
 impl_borrow_decode!(Rol);
 
+#[derive(Clone, Debug, Deref, Display)]
+#[display("<@{_0}>")]
+pub struct Usr(pub UserId);
+impl_encode!(Usr, |s| s.0.get());
+impl_decode!(Usr, |d| UserId::new(d));
+impl_borrow_decode!(Usr);
+
 #[derive(Clone, Deref)]
 pub struct Emoj(pub EmojiId);
 impl_encode!(Emoj, |s| s.0.get());
@@ -1,5 +1,6 @@
 use crate::web::templates;
 use crate::{
+  cmd::voice::{BassBoost, VoiceSettings},
   config::{Config, FormData},
   persistence::PersistentStore,
 };
@@ -9,6 +10,7 @@ use axum::{
   response::{Html, IntoResponse, Redirect, Response},
 };
 use humantime::parse_duration;
+use serenity::all::GuildId;
 use std::{collections::HashMap, sync::Arc};
 
 // Helper function to get config or return default
@@ -30,12 +32,14 @@ fn render_error_response(error: &str, persistence: &Arc<PersistentStore>) -> Htm
     .into_iter()
     .map(|(_, p)| p)
     .collect();
+  let voice_settings = persistence.voice_settings().load_all().unwrap_or_default();
   Html(templates::render_admin_page(
     &config,
     Some(error),
     None,
     checkin_configs,
     active_polls,
+    voice_settings,
   ))
 }
 
@@ -56,6 +60,7 @@ pub async fn get_admin(
     .into_iter()
     .map(|(_, p)| p)
     .collect();
+  let voice_settings = persistence.voice_settings().load_all().unwrap_or_default();
 
   Ok(Html(templates::render_admin_page(
     &config,
@@ -63,6 +68,7 @@ pub async fn get_admin(
     success,
     checkin_configs,
     active_polls,
+    voice_settings,
   )))
 }
 
@@ -136,6 +142,49 @@ fn parse_form_data(params: HashMap<String, String>) -> Result<FormData, String>
   })
 }
 
+pub async fn post_filters(
+  Extension(persistence): Extension<Arc<PersistentStore>>,
+  Form(params): Form<HashMap<String, String>>,
+) -> Response {
+  let result = parse_filters_form(&params, &persistence).and_then(|(guild_id, settings)| {
+    settings
+      .save(&persistence, guild_id)
+      .map_err(|e| format!("Failed to save audio filters: {e}"))
+  });
+  match result {
+    Ok(_) => Redirect::to("/admin?success=1").into_response(),
+    Err(error) => render_error_response(&error, &persistence).into_response(),
+  }
+}
+
+fn parse_filters_form(
+  params: &HashMap<String, String>,
+  persistence: &PersistentStore,
+) -> Result<(GuildId, VoiceSettings), String> {
+  let guild_id = params
+    .get("guild_id")
+    .ok_or("Missing guild_id")?
+    .parse::<u64>()
+    .map(GuildId::new)
+    .map_err(|_| "Invalid guild_id")?;
+  let bass_boost = params.get("bass_boost").ok_or("Missing bass_boost")?;
+  let speed = params
+    .get("speed")
+    .ok_or("Missing speed")?
+    .parse::<u32>()
+    .map_err(|_| "Invalid speed")?;
+  if !(50..=200).contains(&speed) {
+    return Err("Speed must be between 50 and 200".to_string());
+  }
+
+  let mut settings = VoiceSettings::load(persistence, guild_id);
+  // Unchecked boxes aren't sent at all
+  settings.filters.normalize = params.contains_key("normalize");
+  settings.filters.bass_boost = BassBoost::parse(bass_boost).map_err(|e| e.to_string())?;
+  settings.filters.speed = speed;
+  Ok((guild_id, settings))
+}
+
 pub async fn get_favicon() -> Result<impl IntoResponse, StatusCode> {
   let favicon_data = include_bytes!("../img/shrug-cat.png");
 
@@ -2,7 +2,10 @@ pub mod handlers;
 pub mod templates;
 
 use crate::{persistence::PersistentStore, WebBindAddress};
-use axum::{routing::get, Extension, Router};
+use axum::{
+  routing::{get, post},
+  Extension, Router,
+};
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 use tracing::info;
@@ -13,6 +16,7 @@ pub fn create_router(config_path: String, persistence: Arc<PersistentStore>) ->
       "/admin",
       get(handlers::get_admin).post(handlers::post_admin),
     )
+    .route("/admin/filters", post(handlers::post_filters))
     .route("/favicon.ico", get(handlers::get_favicon))
     .layer(Extension(config_path))
     .layer(Extension(persistence))
@@ -1,6 +1,7 @@
 use crate::{
   cmd::check_in::{time_until, CheckInCtx},
   cmd::poll::pollstate::PollState,
+  cmd::voice::{BassBoost, VoiceSettings},
   config::Config,
 };
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
@@ -61,6 +62,7 @@ pub fn render_admin_page(
   success: Option<&str>,
   checkin_configs: Vec<(GuildId, CheckInCtx)>,
   active_polls: Vec<PollState>,
+  voice_settings: Vec<(GuildId, VoiceSettings)>,
 ) -> String {
   let api_key_display = if config.api_key.is_empty() {
     ""
@@ -204,6 +206,56 @@ pub fn render_admin_page(
       .join("")
   };
 
+  // Generate Audio Filters table data, each row being its own little form
+  let filters_table_rows = if voice_settings.is_empty() {
+    r#"<tr><td colspan="5" class="no-data">No guild has saved voice settings yet</td></tr>"#
+      .to_string()
+  } else {
+    voice_settings
+      .iter()
+      .map(|(guild_id, settings)| {
+        let filters = &settings.filters;
+        let form_id = format!("filters-{guild_id}");
+        let bass_options = [BassBoost::Off, BassBoost::Low, BassBoost::High]
+          .iter()
+          .map(|b| {
+            format!(
+              r#"<option value="{0}" {1}>{0}</option>"#,
+              b.name(),
+              if *b == filters.bass_boost {
+                "selected"
+              } else {
+                ""
+              }
+            )
+          })
+          .collect::<Vec<String>>()
+          .join("");
+        format!(
+          r#"<tr>
+            <td title="{}">{}</td>
+            <td><input type="checkbox" name="normalize" form="{form_id}" {}></td>
+            <td><select name="bass_boost" form="{form_id}">{}</select></td>
+            <td><input type="number" name="speed" form="{form_id}" min="50" max="200" value="{}"></td>
+            <td>
+              <form id="{form_id}" method="post" action="/admin/filters">
+                <input type="hidden" name="guild_id" value="{}">
+                <button type="submit">Save</button>
+              </form>
+            </td>
+          </tr>"#,
+          html_escape(&guild_id.to_string()),
+          html_escape(&truncate_id(&guild_id.to_string(), 10)),
+          if filters.normalize { "checked" } else { "" },
+          bass_options,
+          filters.speed,
+          html_escape(&guild_id.to_string()),
+        )
+      })
+      .collect::<Vec<String>>()
+      .join("")
+  };
+
   format!(
     r#"<!DOCTYPE html>
 <html lang="en">
@@ -662,6 +714,28 @@ pub fn render_admin_page(
                         </tbody>
                     </table>
                 </div>
+
+                <div class="checkin-section">
+                    <h3>🎚️ Audio Filters</h3>
+                    <div class="section-info">
+                        ℹ️ Per guild audio processing, also set with <code>/play filter</code>. Changes apply from the next track.
+                    </div>
+
+                    <table class="admin-table">
+                        <thead>
+                            <tr>
+                                <th>Guild ID</th>
+                                <th>Normalize</th>
+                                <th>Bass Boost</th>
+                                <th>Speed %</th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {filters_table_rows}
+                        </tbody>
+                    </table>
+                </div>
             </div>
         </div>
     </div>
@@ -701,6 +775,7 @@ pub fn render_admin_page(
     timeout = format_duration(config.voice_channel_timeout),
     checkin_table_rows = checkin_table_rows,
     polls_table_rows = polls_table_rows,
+    filters_table_rows = filters_table_rows,
   )
 }
 